@@ -0,0 +1,355 @@
+use core::{array, f32::consts::TAU};
+
+/// One sinusoid of an analysis frame.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Partial {
+  /// Hz
+  pub frequency: f32,
+  pub amplitude: f32,
+  /// Start phase in cycles, used when the partial is born
+  pub phase: f32,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Sinusoid {
+  /// Rotating phasor, `im` is the output
+  re: f32,
+  im: f32,
+  /// Rotation per sample
+  cos: f32,
+  sin: f32,
+  ratio: f32,
+  phase: f32,
+  amplitude: f32,
+  target: f32,
+  step: f32,
+  audible: bool,
+}
+
+impl Sinusoid {
+  fn new(ratio: f32) -> Self {
+    Self {
+      re: 1.0,
+      im: 0.0,
+      cos: 1.0,
+      sin: 0.0,
+      ratio,
+      phase: 0.0,
+      amplitude: 0.0,
+      target: 0.0,
+      step: 0.0,
+      audible: false,
+    }
+  }
+
+  fn set_angle(&mut self, phase: f32) {
+    let (sin, cos) = f32::sin_cos(TAU * phase);
+    self.re = cos;
+    self.im = sin;
+  }
+}
+
+/// Bank of `N` recursive sine oscillators.
+///
+/// Each partial runs at `ratio` times the frequency passed to `play`, with
+/// its own amplitude and phase. Ratios default to the harmonic series and
+/// only the fundamental sounds. Partials at or above Nyquist are muted.
+///
+/// For resynthesis, [`set_frame`](AdditiveBank::set_frame) loads the
+/// partials of an analysis frame with their frequencies in Hz as ratios,
+/// so `play(1.0)` reproduces them and other values transpose.
+///
+/// ```
+/// use rust_dsp::additive::AdditiveBank;
+///
+/// let mut bank = AdditiveBank::<64>::new(48000);
+/// // sawtooth spectrum
+/// for n in 0..64 {
+///   bank.set_amplitude(n, 0.5 / (n + 1) as f32);
+/// }
+/// let out = bank.play(110.0);
+/// ```
+pub struct AdditiveBank<const N: usize> {
+  partials: [Sinusoid; N],
+  fundamental: f32,
+  /// Cycles of the played frequency since the start, where each partial is
+  /// at `ratio * cycles + phase`
+  cycles: f64,
+  /// Rotations need recomputing
+  dirty: bool,
+  /// Samples left of the amplitude ramps
+  ramp: usize,
+  /// Samples until the phasors are renormalised
+  normalise: usize,
+  sr_recip: f32,
+}
+
+/// Interval of renormalising the recursion, in samples.
+const NORMALISE: usize = 256;
+
+impl<const N: usize> AdditiveBank<N> {
+  pub fn new(samplerate: u32) -> Self {
+    let mut partials = array::from_fn(|n| Sinusoid::new((n + 1) as f32));
+    if let Some(first) = partials.first_mut() {
+      first.amplitude = 1.0;
+      first.target = 1.0;
+    }
+    Self {
+      partials,
+      fundamental: 0.0,
+      cycles: 0.0,
+      dirty: true,
+      ramp: 0,
+      normalise: NORMALISE,
+      sr_recip: 1.0 / samplerate as f32,
+    }
+  }
+
+  #[inline]
+  pub fn play(&mut self, frequency: f32) -> f32 {
+    if self.dirty || frequency != self.fundamental {
+      self.fundamental = frequency;
+      self.dirty = false;
+      self.update();
+    }
+
+    let ramping = self.ramp > 0;
+    if ramping { self.ramp -= 1; }
+    self.normalise -= 1;
+    let normalise = self.normalise == 0;
+    if normalise { self.normalise = NORMALISE; }
+
+    let mut out = 0.0;
+    for p in self.partials.iter_mut() {
+      if ramping {
+        p.amplitude = if self.ramp == 0 { p.target } else { p.amplitude + p.step };
+      }
+      // muted partials keep turning to stay in phase
+      if !p.audible { continue; }
+      let re = p.re * p.cos - p.im * p.sin;
+      let im = p.re * p.sin + p.im * p.cos;
+      if normalise {
+        // the rounding errors of the recursion slowly change the amplitude
+        let gain = 1.5 - 0.5 * (re * re + im * im);
+        p.re = re * gain;
+        p.im = im * gain;
+      } else {
+        p.re = re;
+        p.im = im;
+      }
+      out += p.im * p.amplitude;
+    }
+    self.cycles += (self.fundamental * self.sr_recip) as f64;
+    out
+  }
+
+  fn update(&mut self) {
+    let fundamental = self.fundamental;
+    for p in self.partials.iter_mut() {
+      let increment = p.ratio * fundamental * self.sr_recip;
+      let audible = increment.abs() < 0.5;
+      if audible && !p.audible {
+        // stood still above Nyquist, pick up where it would have been
+        p.set_angle((p.ratio as f64 * self.cycles + p.phase as f64).rem_euclid(1.0) as f32);
+      }
+      p.audible = audible;
+      if p.audible {
+        (p.sin, p.cos) = f32::sin_cos(TAU * increment);
+      }
+    }
+  }
+
+  /// Frequency of `partial` relative to the played frequency.
+  pub fn set_ratio(&mut self, partial: usize, ratio: f32) {
+    if let Some(p) = self.partials.get_mut(partial) {
+      p.ratio = ratio;
+      self.dirty = true;
+    }
+  }
+
+  pub fn set_amplitude(&mut self, partial: usize, amplitude: f32) {
+    if let Some(p) = self.partials.get_mut(partial) {
+      p.amplitude = amplitude;
+      p.target = amplitude;
+      // leave a running frame ramp
+      p.step = 0.0;
+    }
+  }
+
+  /// Phase offset of `partial` in cycles.
+  pub fn set_phase(&mut self, partial: usize, phase: f32) {
+    if let Some(p) = self.partials.get_mut(partial) {
+      let (sin, cos) = f32::sin_cos(TAU * (phase - p.phase));
+      (p.re, p.im) = (p.re * cos - p.im * sin, p.re * sin + p.im * cos);
+      p.phase = phase;
+    }
+  }
+
+  /// Set the amplitudes of the first partials, muting the rest.
+  pub fn set_amplitudes(&mut self, amplitudes: &[f32]) {
+    for (n, p) in self.partials.iter_mut().enumerate() {
+      p.amplitude = amplitudes.get(n).copied().unwrap_or(0.0);
+      p.target = p.amplitude;
+    }
+    self.ramp = 0;
+  }
+
+  /// Restore the harmonic series.
+  pub fn set_harmonic(&mut self) {
+    for (n, p) in self.partials.iter_mut().enumerate() {
+      p.ratio = (n + 1) as f32;
+    }
+    self.dirty = true;
+  }
+
+  /// Move to an analysis frame over `ramp` samples, usually the hop size.
+  ///
+  /// Partials are tracked by index, `frame[n]` continues the track of
+  /// partial `n` from the previous frame. Frequencies change at once and
+  /// amplitudes are ramped, new tracks start at their phase and tracks
+  /// missing from the frame fade out.
+  pub fn set_frame(&mut self, frame: &[Partial], ramp: usize) {
+    let ramp = ramp.max(1);
+    for (n, p) in self.partials.iter_mut().enumerate() {
+      let partial = frame.get(n).copied().unwrap_or_default();
+      if partial.amplitude != 0.0 && p.amplitude == 0.0 {
+        p.set_angle(partial.phase);
+        p.phase = partial.phase;
+      }
+      if partial.frequency > 0.0 || partial.amplitude != 0.0 {
+        p.ratio = partial.frequency;
+      }
+      p.target = partial.amplitude;
+      p.step = (p.target - p.amplitude) / ramp as f32;
+    }
+    self.ramp = ramp;
+    self.dirty = true;
+  }
+
+  /// Restart all partials at their phase.
+  pub fn reset(&mut self) {
+    for p in self.partials.iter_mut() {
+      p.set_angle(p.phase);
+    }
+    self.cycles = 0.0;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.sr_recip = 1.0 / samplerate as f32;
+    self.dirty = true;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLERATE: u32 = 48000;
+
+  #[test]
+  fn fundamental_is_sine() {
+    let mut bank = AdditiveBank::<8>::new(SAMPLERATE);
+    for n in 1..1000 {
+      let expected = f32::sin(TAU * 440.0 * n as f32 / SAMPLERATE as f32);
+      assert!((bank.play(440.0) - expected).abs() < 1e-3, "{n}");
+    }
+  }
+
+  #[test]
+  fn amplitude_is_stable() {
+    let mut bank = AdditiveBank::<1>::new(SAMPLERATE);
+    let mut peak = 0.0f32;
+    for n in 0..SAMPLERATE * 10 {
+      let out = bank.play(1234.5);
+      if n > SAMPLERATE * 9 { peak = peak.max(out.abs()); }
+    }
+    assert!((peak - 1.0).abs() < 1e-3, "{peak}");
+  }
+
+  #[test]
+  fn partials_above_nyquist_are_muted() {
+    let mut bank = AdditiveBank::<4>::new(SAMPLERATE);
+    bank.set_amplitudes(&[0.0, 0.0, 1.0]);
+    // third harmonic of 10 kHz
+    assert!((0..100).all(|_| bank.play(10000.0) == 0.0));
+    assert!((0..100).any(|_| bank.play(1000.0) != 0.0));
+  }
+
+  #[test]
+  fn phase_and_ratio() {
+    let mut bank = AdditiveBank::<2>::new(SAMPLERATE);
+    bank.set_amplitudes(&[0.0, 1.0]);
+    bank.set_ratio(1, 1.5);
+    bank.set_phase(1, 0.25);
+    for n in 1..100 {
+      let expected = f32::cos(TAU * 150.0 * n as f32 / SAMPLERATE as f32);
+      assert!((bank.play(100.0) - expected).abs() < 1e-3, "{n}");
+    }
+  }
+
+  #[test]
+  fn resynthesis_ramps_between_frames() {
+    let mut bank = AdditiveBank::<4>::new(SAMPLERATE);
+    bank.set_amplitudes(&[]);
+    let frame = [
+      Partial { frequency: 220.0, amplitude: 0.5, phase: 0.25 },
+      Partial { frequency: 330.0, amplitude: 0.25, phase: 0.0 },
+    ];
+    bank.set_frame(&frame, 64);
+    // born at its phase, fading in
+    let first = bank.play(1.0);
+    assert!(first > 0.0 && first < 0.05, "{first}");
+    (0..63).for_each(|_| { bank.play(1.0); });
+    assert_eq!(0.5, bank.partials[0].amplitude);
+    assert_eq!(0.25, bank.partials[1].amplitude);
+
+    // the second track ends
+    bank.set_frame(&frame[..1], 64);
+    (0..64).for_each(|_| { bank.play(1.0); });
+    assert_eq!(0.0, bank.partials[1].amplitude);
+    let peak = (0..480).map(|_| bank.play(1.0).abs()).fold(0.0, f32::max);
+    assert!((peak - 0.5).abs() < 1e-2, "{peak}");
+  }
+
+  #[test]
+  fn amplitude_overrides_ramp() {
+    let mut bank = AdditiveBank::<2>::new(SAMPLERATE);
+    bank.set_frame(&[Partial { frequency: 220.0, amplitude: 1.0, phase: 0.0 }], 64);
+    (0..10).for_each(|_| { bank.play(1.0); });
+    bank.set_amplitude(0, 0.25);
+    (0..10).for_each(|_| { bank.play(1.0); });
+    assert_eq!(0.25, bank.partials[0].amplitude);
+    (0..100).for_each(|_| { bank.play(1.0); });
+    assert_eq!(0.25, bank.partials[0].amplitude);
+  }
+
+  #[test]
+  fn muted_partials_stay_in_phase() {
+    let mut bank = AdditiveBank::<2>::new(SAMPLERATE);
+    bank.set_amplitudes(&[1.0, 0.5]);
+    bank.set_phase(1, 0.25);
+    let mut cycles = 0.0f64;
+    let mut play = |bank: &mut AdditiveBank<2>, freq: f32, samples: usize| {
+      let mut error = 0.0f32;
+      for _ in 0..samples {
+        let out = bank.play(freq);
+        cycles += (freq / SAMPLERATE as f32) as f64;
+        let expected = f64::sin(core::f64::consts::TAU * cycles)
+          + 0.5 * f64::cos(core::f64::consts::TAU * 2.0 * cycles);
+        error = error.max((out - expected as f32).abs());
+      }
+      error
+    };
+    play(&mut bank, 100.0, 100);
+    bank.set_amplitude(1, 0.0);
+    play(&mut bank, 100.0, 1000);
+    bank.set_amplitude(1, 0.5);
+    let error = play(&mut bank, 100.0, 1000);
+    assert!(error < 1e-3, "{error}");
+
+    // the second partial passes Nyquist and comes back
+    play(&mut bank, 13000.0, 123);
+    let error = play(&mut bank, 100.0, 1000);
+    assert!(error < 1e-3, "{error}");
+  }
+}
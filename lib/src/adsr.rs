@@ -100,7 +100,7 @@ impl ADSREnvelope {
       }
     };
 
-    match dbg!(self.stage) {
+    match self.stage {
       EnvStage::Atk => {
         if self.count >= (self.atk_duration * self.sr) as usize { 
           self.stage = EnvStage::Dec; 
@@ -147,6 +147,10 @@ impl ADSREnvelope {
     self.stage = EnvStage::Atk;
   }
 
+  /// `false` once the release has finished.
+  #[inline(always)]
+  pub fn is_playing(&self) -> bool { self.playing }
+
   #[inline(always)]
   pub fn set_attack_val (&mut self,    atk_value: f32) { self.atk_value    = atk_value; }
   #[inline(always)]
@@ -0,0 +1,122 @@
+use crate::dsp::math::is_pow2;
+use alloc::vec::Vec;
+use core::f32::consts::TAU;
+
+/// In-place radix-2 FFT on split real/imaginary buffers.
+///
+/// Twiddle factors and the bit-reversal table are calculated once on init,
+/// so a single instance can be reused for every frame of the same size.
+pub struct Fft {
+  size: usize,
+  cos: Vec<f32>,
+  sin: Vec<f32>,
+  reversed: Vec<usize>,
+}
+
+impl Fft {
+  /// Size needs to be a power of 2.
+  pub fn new(size: usize) -> Result<Self, &'static str> {
+    if !is_pow2(size) {
+      return Err("Size of FFT is not a power of 2");
+    }
+    let half = size >> 1;
+    let cos = (0..half).map(|k| f32::cos(TAU * k as f32 / size as f32)).collect();
+    let sin = (0..half).map(|k| f32::sin(TAU * k as f32 / size as f32)).collect();
+    let bits = size.trailing_zeros();
+    let reversed = (0..size)
+      .map(|i| if bits == 0 { 0 } else { i.reverse_bits() >> (usize::BITS - bits) })
+      .collect();
+    Ok(Self { size, cos, sin, reversed })
+  }
+
+  pub fn size(&self) -> usize {
+    self.size
+  }
+
+  /// Forward transform, `re` and `im` must both be of length `size`.
+  pub fn forward(&self, re: &mut [f32], im: &mut [f32]) {
+    self.transform(re, im, -1.0);
+  }
+
+  /// Inverse transform, scaled by `1 / size` so that `inverse(forward(x)) == x`.
+  pub fn inverse(&self, re: &mut [f32], im: &mut [f32]) {
+    self.transform(re, im, 1.0);
+    let scale = 1.0 / self.size as f32;
+    re.iter_mut().for_each(|x| *x *= scale);
+    im.iter_mut().for_each(|x| *x *= scale);
+  }
+
+  fn transform(&self, re: &mut [f32], im: &mut [f32], sign: f32) {
+    debug_assert!(re.len() == self.size && im.len() == self.size);
+    for (i, &j) in self.reversed.iter().enumerate() {
+      if i < j {
+        re.swap(i, j);
+        im.swap(i, j);
+      }
+    }
+
+    let mut len = 2;
+    while len <= self.size {
+      let half = len >> 1;
+      let stride = self.size / len;
+      for start in (0..self.size).step_by(len) {
+        for k in 0..half {
+          let wr = self.cos[k * stride];
+          let wi = sign * self.sin[k * stride];
+          let a = start + k;
+          let b = a + half;
+          let tr = re[b] * wr - im[b] * wi;
+          let ti = re[b] * wi + im[b] * wr;
+          re[b] = re[a] - tr;
+          im[b] = im[a] - ti;
+          re[a] += tr;
+          im[a] += ti;
+        }
+      }
+      len <<= 1;
+    }
+  }
+}
+
+/// Magnitudes of the first `size / 2 + 1` bins.
+pub fn magnitudes(re: &[f32], im: &[f32], out: &mut [f32]) {
+  for (m, (r, i)) in out.iter_mut().zip(re.iter().zip(im)) {
+    *m = f32::sqrt(r * r + i * i);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use alloc::vec;
+
+  #[test]
+  fn not_pow2() {
+    assert!(Fft::new(100).is_err());
+  }
+
+  #[test]
+  fn sine_bin() {
+    const SIZE: usize = 64;
+    let fft = Fft::new(SIZE).unwrap();
+    let mut re: Vec<f32> = (0..SIZE).map(|n| f32::sin(TAU * 4.0 * n as f32 / SIZE as f32)).collect();
+    let mut im = vec![0.0; SIZE];
+    fft.forward(&mut re, &mut im);
+    let mut mags = vec![0.0; SIZE / 2 + 1];
+    magnitudes(&re, &im, &mut mags);
+    assert!((mags[4] - SIZE as f32 / 2.0).abs() < 1e-3);
+    assert!(mags.iter().enumerate().filter(|(i, _)| *i != 4).all(|(_, m)| *m < 1e-3));
+  }
+
+  #[test]
+  fn roundtrip() {
+    const SIZE: usize = 32;
+    let fft = Fft::new(SIZE).unwrap();
+    let input: Vec<f32> = (0..SIZE).map(|n| (n as f32 * 0.37).sin() + 0.25).collect();
+    let mut re = input.clone();
+    let mut im = vec![0.0; SIZE];
+    fft.forward(&mut re, &mut im);
+    fft.inverse(&mut re, &mut im);
+    assert!(re.iter().zip(&input).all(|(a, b)| (a - b).abs() < 1e-5));
+  }
+}
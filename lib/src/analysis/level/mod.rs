@@ -0,0 +1,283 @@
+use crate::dsp::math::{ms_to_coeff, ms_to_samples, volume_to_db};
+use alloc::{vec, vec::Vec};
+
+/// Common interface for level detectors.
+///
+/// `process` returns the level in linear gain, `db` returns the latest
+/// level converted with [`volume_to_db`].
+pub trait Detector {
+  fn process(&mut self, sample: f32) -> f32;
+  fn level(&self) -> f32;
+  fn reset(&mut self);
+  fn set_samplerate(&mut self, samplerate: u32);
+
+  #[inline]
+  fn db(&self) -> f32 {
+    volume_to_db(self.level())
+  }
+
+  /// Process a block, leaving the level of each sample in `out`.
+  fn process_block(&mut self, input: &[f32], out: &mut [f32]) {
+    for (o, i) in out.iter_mut().zip(input) {
+      *o = self.process(*i);
+    }
+  }
+}
+
+/// Peak detector, jumps to new peaks instantly and falls back with `release`.
+pub struct Peak {
+  level: f32,
+  release: f32,
+  rel_coeff: f32,
+  samplerate: f32,
+}
+
+impl Peak {
+  pub fn new(samplerate: u32) -> Self {
+    let mut peak = Self { level: 0.0, release: 300.0, rel_coeff: 0.0, samplerate: samplerate as f32 };
+    peak.set_release(300.0);
+    peak
+  }
+
+  /// Release time in milliseconds
+  pub fn set_release(&mut self, ms: f32) {
+    self.release = ms;
+    self.rel_coeff = ms_to_coeff(ms, self.samplerate);
+  }
+}
+
+impl Detector for Peak {
+  #[inline]
+  fn process(&mut self, sample: f32) -> f32 {
+    let x = sample.abs();
+    self.level = if x >= self.level { x } else { x + self.rel_coeff * (self.level - x) };
+    self.level
+  }
+
+  fn level(&self) -> f32 { self.level }
+  fn reset(&mut self) { self.level = 0.0; }
+
+  fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.set_release(self.release);
+  }
+}
+
+/// RMS over a sliding rectangular window.
+pub struct Rms {
+  window: Vec<f32>,
+  position: usize,
+  length: usize,
+  sum: f64,
+  time: f32,
+  samplerate: f32,
+}
+
+impl Rms {
+  /// `window` is the length of the window in milliseconds.
+  pub fn new(samplerate: u32, window: f32) -> Self {
+    let length = usize::max(1, ms_to_samples(window, samplerate as f32));
+    Self {
+      window: vec![0.0; length],
+      position: 0,
+      length,
+      sum: 0.0,
+      time: window,
+      samplerate: samplerate as f32,
+    }
+  }
+
+  /// Resizing the window resets the detector.
+  pub fn set_window(&mut self, ms: f32) {
+    self.time = ms;
+    self.length = usize::max(1, ms_to_samples(ms, self.samplerate));
+    self.window = vec![0.0; self.length];
+    self.reset();
+  }
+}
+
+impl Detector for Rms {
+  #[inline]
+  fn process(&mut self, sample: f32) -> f32 {
+    let square = sample * sample;
+    self.sum += (square - self.window[self.position]) as f64;
+    self.window[self.position] = square;
+    self.position += 1;
+    if self.position >= self.length {
+      self.position = 0;
+      // Recalculate once per window to get rid of accumulated rounding errors
+      self.sum = self.window.iter().map(|x| *x as f64).sum();
+    }
+    self.level()
+  }
+
+  #[inline]
+  fn level(&self) -> f32 {
+    f64::sqrt(f64::max(0.0, self.sum) / self.length as f64) as f32
+  }
+
+  fn reset(&mut self) {
+    self.window.iter_mut().for_each(|x| *x = 0.0);
+    self.position = 0;
+    self.sum = 0.0;
+  }
+
+  fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.set_window(self.time);
+  }
+}
+
+/// RMS with exponential averaging of the squared signal.
+pub struct ExpRms {
+  mean_square: f32,
+  time: f32,
+  coeff: f32,
+  samplerate: f32,
+}
+
+impl ExpRms {
+  /// `time` is the averaging time constant in milliseconds.
+  pub fn new(samplerate: u32, time: f32) -> Self {
+    Self {
+      mean_square: 0.0,
+      time,
+      coeff: ms_to_coeff(time, samplerate as f32),
+      samplerate: samplerate as f32,
+    }
+  }
+
+  pub fn set_time(&mut self, ms: f32) {
+    self.time = ms;
+    self.coeff = ms_to_coeff(ms, self.samplerate);
+  }
+}
+
+impl Detector for ExpRms {
+  #[inline]
+  fn process(&mut self, sample: f32) -> f32 {
+    let square = sample * sample;
+    self.mean_square = square + self.coeff * (self.mean_square - square);
+    self.level()
+  }
+
+  #[inline]
+  fn level(&self) -> f32 { self.mean_square.sqrt() }
+  fn reset(&mut self) { self.mean_square = 0.0; }
+
+  fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.set_time(self.time);
+  }
+}
+
+/// Envelope follower with separate attack and release times.
+///
+/// Follows the rectified signal, rising with the attack time constant and
+/// falling with the release time constant.
+pub struct EnvelopeFollower {
+  level: f32,
+  attack: f32,
+  release: f32,
+  atk_coeff: f32,
+  rel_coeff: f32,
+  samplerate: f32,
+}
+
+impl EnvelopeFollower {
+  pub fn new(samplerate: u32) -> Self {
+    let mut follower = Self {
+      level: 0.0,
+      attack: 0.0,
+      release: 0.0,
+      atk_coeff: 0.0,
+      rel_coeff: 0.0,
+      samplerate: samplerate as f32,
+    };
+    follower.set_attack(10.0);
+    follower.set_release(100.0);
+    follower
+  }
+
+  /// Attack time in milliseconds
+  pub fn set_attack(&mut self, ms: f32) {
+    self.attack = ms;
+    self.atk_coeff = ms_to_coeff(ms, self.samplerate);
+  }
+
+  /// Release time in milliseconds
+  pub fn set_release(&mut self, ms: f32) {
+    self.release = ms;
+    self.rel_coeff = ms_to_coeff(ms, self.samplerate);
+  }
+}
+
+impl Detector for EnvelopeFollower {
+  #[inline]
+  fn process(&mut self, sample: f32) -> f32 {
+    let x = sample.abs();
+    let coeff = if x > self.level { self.atk_coeff } else { self.rel_coeff };
+    self.level = x + coeff * (self.level - x);
+    self.level
+  }
+
+  fn level(&self) -> f32 { self.level }
+  fn reset(&mut self) { self.level = 0.0; }
+
+  fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.set_attack(self.attack);
+    self.set_release(self.release);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use core::f32::consts::{FRAC_1_SQRT_2, TAU};
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn sine(n: usize) -> f32 {
+    f32::sin(TAU * 480.0 * n as f32 / SAMPLERATE as f32)
+  }
+
+  #[test]
+  fn rms_of_sine() {
+    let mut rms = Rms::new(SAMPLERATE, 100.0);
+    let mut level = 0.0;
+    for n in 0..48000 { level = rms.process(sine(n)); }
+    assert!((level - FRAC_1_SQRT_2).abs() < 1e-3, "{level}");
+  }
+
+  #[test]
+  fn exp_rms_of_sine() {
+    let mut rms = ExpRms::new(SAMPLERATE, 50.0);
+    for n in 0..48000 { rms.process(sine(n)); }
+    assert!((rms.db() - volume_to_db(FRAC_1_SQRT_2)).abs() < 0.1, "{}", rms.db());
+  }
+
+  #[test]
+  fn peak_holds_and_releases() {
+    let mut peak = Peak::new(SAMPLERATE);
+    peak.set_release(10.0);
+    assert_eq!(0.8, peak.process(-0.8));
+    let released = (0..480).map(|_| peak.process(0.0)).last().unwrap();
+    assert!((released - 0.8 * f32::exp(-1.0)).abs() < 1e-3, "{released}");
+  }
+
+  #[test]
+  fn follower_attack_time() {
+    let mut env = EnvelopeFollower::new(SAMPLERATE);
+    env.set_attack(10.0);
+    let level = (0..480).map(|_| env.process(1.0)).last().unwrap();
+    assert!((level - (1.0 - f32::exp(-1.0))).abs() < 1e-3, "{level}");
+  }
+
+  #[test]
+  fn silence_in_db() {
+    let mut env = EnvelopeFollower::new(SAMPLERATE);
+    env.process(0.0);
+    assert_eq!(f32::NEG_INFINITY, env.db());
+  }
+}
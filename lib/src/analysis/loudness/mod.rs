@@ -0,0 +1,382 @@
+use super::truepeak::TruePeak;
+use crate::dsp::math::volume_to_db;
+use crate::filter::{
+  biquad::{twopole::Biquad, BiquadCoeffs},
+  Filter,
+};
+use alloc::{collections::VecDeque, vec, vec::Vec};
+
+/// Blocks below this loudness are ignored in the integrated loudness and LRA.
+const ABSOLUTE_GATE: f32 = -70.0;
+/// Integrated loudness ignores blocks this far below the ungated mean.
+const RELATIVE_GATE: f32 = -10.0;
+/// Loudness range ignores short-term values this far below the ungated mean.
+const LRA_GATE: f32 = -20.0;
+/// 100 ms steps in the momentary and short-term windows.
+const MOMENTARY_STEPS: usize = 4;
+const SHORT_TERM_STEPS: usize = 30;
+/// Resolution and size of the gating histograms, 0.1 LU bins from the
+/// absolute gate up to +30 LUFS.
+const BINS_PER_LU: f32 = 10.0;
+const BINS: usize = 1000;
+
+/// K-weighting pre-filter, a high-shelf modelling the acoustic effect of
+/// the head followed by a highpass. The coefficients follow the ITU-R BS.1770
+/// filter design, and match the tabled 48 kHz values at that samplerate.
+pub fn k_weighting(samplerate: u32) -> [BiquadCoeffs; 2] {
+  let sr = samplerate as f64;
+
+  let (f0, gain, q) = (1681.974450955533, 3.999843853973347, 0.7071752369554196);
+  let k = f64::tan(core::f64::consts::PI * f0 / sr);
+  let vh = f64::powf(10.0, gain / 20.0);
+  let vb = f64::powf(vh, 0.4996667741545416);
+  let a0 = 1.0 + k / q + k * k;
+  let shelf = BiquadCoeffs::new(
+    [
+      ((vh + vb * k / q + k * k) / a0) as f32,
+      (2.0 * (k * k - vh) / a0) as f32,
+      ((vh - vb * k / q + k * k) / a0) as f32,
+    ],
+    [(2.0 * (k * k - 1.0) / a0) as f32, ((1.0 - k / q + k * k) / a0) as f32],
+  );
+
+  let (f0, q) = (38.13547087602444, 0.5003270373238773);
+  let k = f64::tan(core::f64::consts::PI * f0 / sr);
+  let a0 = 1.0 + k / q + k * k;
+  let highpass = BiquadCoeffs::new(
+    [1.0, -2.0, 1.0],
+    [(2.0 * (k * k - 1.0) / a0) as f32, ((1.0 - k / q + k * k) / a0) as f32],
+  );
+  [shelf, highpass]
+}
+
+/// Loudness in LUFS of a mean square `power`.
+#[inline]
+fn lufs(power: f64) -> f32 {
+  (-0.691 + 10.0 * power.log10()) as f32
+}
+
+/// Loudness meter following ITU-R BS.1770 and EBU R128.
+///
+/// Each channel is K-weighted and its mean square summed with a channel
+/// weight. The meter keeps the loudness of the last 400 ms (momentary) and
+/// 3 s (short-term), updated every 100 ms. Momentary blocks are collected
+/// for the gated integrated loudness and short-term blocks for the loudness
+/// range, both in histograms of 0.1 LU bins so the memory stays fixed
+/// however long the meter runs.
+///
+/// Channel weights default to `1.0`. For 5.1 material, set the surround
+/// channels to `1.41` and the LFE channel to `0.0`.
+///
+/// ```
+/// use rust_dsp::analysis::loudness::LoudnessMeter;
+///
+/// let mut meter = LoudnessMeter::new(48000, 2);
+/// meter.process_interleaved(&[0.25, -0.25, 0.5, -0.5]);
+/// let lufs = meter.integrated();
+/// let gain = -23.0 - lufs;
+/// ```
+pub struct LoudnessMeter {
+  filters: Vec<[Biquad; 2]>,
+  peaks: Vec<TruePeak>,
+  weights: Vec<f32>,
+
+  step: usize,
+  counter: usize,
+  sum: f64,
+  /// Mean square of the latest 100 ms steps, newest last
+  steps: VecDeque<f64>,
+  count: usize,
+  blocks: Histogram,
+  short_terms: Histogram,
+  samplerate: u32,
+}
+
+impl LoudnessMeter {
+  pub fn new(samplerate: u32, channels: usize) -> Self {
+    let [shelf, highpass] = k_weighting(samplerate);
+    Self {
+      filters: vec![[Biquad::new(shelf), Biquad::new(highpass)]; channels],
+      peaks: (0..channels).map(|_| TruePeak::new()).collect(),
+      weights: vec![1.0; channels],
+      step: usize::max(1, samplerate as usize / 10),
+      counter: 0,
+      sum: 0.0,
+      steps: VecDeque::from(vec![0.0; SHORT_TERM_STEPS]),
+      count: 0,
+      blocks: Histogram::new(),
+      short_terms: Histogram::new(),
+      samplerate,
+    }
+  }
+
+  /// Measure one frame, holding one sample per channel.
+  #[inline]
+  pub fn process(&mut self, frame: &[f32]) {
+    debug_assert!(frame.len() == self.filters.len());
+    for (((x, [shelf, highpass]), tp), w) in frame.iter()
+      .zip(self.filters.iter_mut())
+      .zip(self.peaks.iter_mut())
+      .zip(self.weights.iter())
+    {
+      tp.process(*x);
+      let y = highpass.process(shelf.process(*x)) as f64;
+      self.sum += *w as f64 * y * y;
+    }
+
+    self.counter += 1;
+    if self.counter == self.step {
+      self.next_step();
+    }
+  }
+
+  /// Measure a block of interleaved frames.
+  pub fn process_interleaved(&mut self, samples: &[f32]) {
+    let channels = self.filters.len();
+    for frame in samples.chunks_exact(channels) {
+      self.process(frame);
+    }
+  }
+
+  fn next_step(&mut self) {
+    self.steps.pop_front();
+    self.steps.push_back(self.sum / self.step as f64);
+    self.sum = 0.0;
+    self.counter = 0;
+    self.count += 1;
+    if self.count >= MOMENTARY_STEPS {
+      self.blocks.add(self.window(MOMENTARY_STEPS));
+    }
+    if self.count >= SHORT_TERM_STEPS {
+      self.short_terms.add(self.window(SHORT_TERM_STEPS));
+    }
+  }
+
+  /// Mean square of the latest `steps` steps.
+  fn window(&self, steps: usize) -> f64 {
+    self.steps.iter().rev().take(steps).sum::<f64>() / steps as f64
+  }
+
+  /// Loudness of the last 400 ms in LUFS.
+  pub fn momentary(&self) -> f32 {
+    lufs(self.window(MOMENTARY_STEPS))
+  }
+
+  /// Loudness of the last 3 s in LUFS.
+  pub fn short_term(&self) -> f32 {
+    lufs(self.window(SHORT_TERM_STEPS))
+  }
+
+  /// Gated loudness in LUFS of everything measured since creation or the
+  /// last [`LoudnessMeter::reset`]. `f32::NEG_INFINITY` until a block passes
+  /// the gate.
+  ///
+  /// Runs over the whole histogram, so it is meant to be read at a lower
+  /// rate than the signal, e.g. when the 100 ms window has moved.
+  pub fn integrated(&self) -> f32 {
+    let Some(mean) = self.blocks.mean_above(ABSOLUTE_GATE) else {
+      return f32::NEG_INFINITY;
+    };
+    let gate = f32::max(ABSOLUTE_GATE, lufs(mean) + RELATIVE_GATE);
+    self.blocks.mean_above(gate).map_or(f32::NEG_INFINITY, lufs)
+  }
+
+  /// Loudness range in LU following EBU Tech 3342. It is the spread between
+  /// the 10th and 95th percentile of the gated short-term loudness.
+  pub fn loudness_range(&self) -> f32 {
+    let Some(mean) = self.short_terms.mean_above(ABSOLUTE_GATE) else {
+      return 0.0;
+    };
+    let gate = f32::max(ABSOLUTE_GATE, lufs(mean) + LRA_GATE);
+    let high = self.short_terms.percentile(0.95, gate);
+    let low = self.short_terms.percentile(0.10, gate);
+    high - low
+  }
+
+  /// Highest true-peak of all channels in dBTP.
+  pub fn true_peak(&self) -> f32 {
+    volume_to_db(self.peaks.iter().map(|tp| tp.max()).fold(0.0, f32::max))
+  }
+
+  /// Weight of `channel` in the summed loudness.
+  pub fn set_weight(&mut self, channel: usize, weight: f32) {
+    self.weights[channel] = weight;
+  }
+
+  /// Clear all measurements and filter states.
+  pub fn reset(&mut self) {
+    let [shelf, highpass] = k_weighting(self.samplerate);
+    self.filters.iter_mut().for_each(|f| *f = [Biquad::new(shelf), Biquad::new(highpass)]);
+    self.peaks.iter_mut().for_each(|tp| tp.reset());
+    self.counter = 0;
+    self.sum = 0.0;
+    self.steps.iter_mut().for_each(|s| *s = 0.0);
+    self.count = 0;
+    self.blocks.clear();
+    self.short_terms.clear();
+  }
+
+  /// Changes the filters to the new samplerate and resets the meter.
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate;
+    self.step = usize::max(1, samplerate as usize / 10);
+    self.reset();
+  }
+}
+
+/// Block powers binned by loudness. Each bin keeps the count and the sum of
+/// its powers, so the gated means are exact and only the gates and
+/// percentiles are rounded to a bin.
+struct Histogram {
+  counts: Vec<u32>,
+  sums: Vec<f64>,
+}
+
+impl Histogram {
+  fn new() -> Self {
+    Self { counts: vec![0; BINS], sums: vec![0.0; BINS] }
+  }
+
+  /// Add a block, dropping it if it is below the absolute gate.
+  fn add(&mut self, power: f64) {
+    let loudness = lufs(power);
+    if loudness <= ABSOLUTE_GATE {
+      return;
+    }
+    let bin = usize::min(((loudness - ABSOLUTE_GATE) * BINS_PER_LU) as usize, BINS - 1);
+    self.counts[bin] += 1;
+    self.sums[bin] += power;
+  }
+
+  /// Loudness at the centre of `bin` in LUFS.
+  fn centre(bin: usize) -> f32 {
+    ABSOLUTE_GATE + (bin as f32 + 0.5) / BINS_PER_LU
+  }
+
+  /// Bins centred above `gate`, lowest first.
+  fn above(&self, gate: f32) -> impl Iterator<Item = (usize, u32)> + '_ {
+    self.counts.iter()
+      .copied()
+      .enumerate()
+      .filter(move |(bin, count)| *count > 0 && Self::centre(*bin) > gate)
+  }
+
+  /// Mean power of the blocks above `gate`, `None` if there are none.
+  fn mean_above(&self, gate: f32) -> Option<f64> {
+    let (sum, n) = self.above(gate)
+      .fold((0.0, 0u64), |(sum, n), (bin, count)| (sum + self.sums[bin], n + count as u64));
+    (n > 0).then(|| sum / n as f64)
+  }
+
+  /// Loudness at the `p` percentile of the blocks above `gate`, `0.0` if
+  /// there are none.
+  fn percentile(&self, p: f32, gate: f32) -> f32 {
+    let n: u64 = self.above(gate).map(|(_, count)| count as u64).sum();
+    if n == 0 {
+      return 0.0;
+    }
+    let k = (p * (n - 1) as f32).round() as u64;
+    let mut seen = 0;
+    for (bin, count) in self.above(gate) {
+      seen += count as u64;
+      if seen > k {
+        return Self::centre(bin);
+      }
+    }
+    0.0
+  }
+
+  fn clear(&mut self) {
+    self.counts.iter_mut().for_each(|c| *c = 0);
+    self.sums.iter_mut().for_each(|s| *s = 0.0);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dsp::math::db_to_volume;
+  use core::f32::consts::TAU;
+
+  const SAMPLERATE: u32 = 48000;
+
+  /// Feed `seconds` of a sine at `db` dBFS to every channel where `on` is set.
+  fn sine(meter: &mut LoudnessMeter, freq: f32, db: f32, seconds: f32, on: &[bool]) {
+    let amp = db_to_volume(db);
+    let mut frame = vec![0.0; on.len()];
+    for n in 0..(seconds * SAMPLERATE as f32) as usize {
+      let x = amp * f32::sin(TAU * freq * n as f32 / SAMPLERATE as f32);
+      frame.iter_mut().zip(on).for_each(|(s, on)| *s = if *on { x } else { 0.0 });
+      meter.process(&frame);
+    }
+  }
+
+  #[test]
+  fn single_channel_calibration() {
+    // 0 dBFS 997 Hz in one channel reads -3.01 LUFS
+    let mut meter = LoudnessMeter::new(SAMPLERATE, 2);
+    sine(&mut meter, 997.0, 0.0, 3.0, &[true, false]);
+    assert!((meter.momentary() + 3.01).abs() < 0.05, "{}", meter.momentary());
+    assert!((meter.short_term() + 3.01).abs() < 0.05, "{}", meter.short_term());
+  }
+
+  #[test]
+  fn stereo_reference_level() {
+    // EBU Tech 3341, -23 dBFS 1 kHz in both channels reads -23 LUFS
+    let mut meter = LoudnessMeter::new(SAMPLERATE, 2);
+    sine(&mut meter, 1000.0, -23.0, 4.0, &[true, true]);
+    for lufs in [meter.momentary(), meter.short_term(), meter.integrated()] {
+      assert!((lufs + 23.0).abs() < 0.1, "{lufs}");
+    }
+  }
+
+  #[test]
+  fn silence_is_gated() {
+    let mut meter = LoudnessMeter::new(SAMPLERATE, 1);
+    assert_eq!(f32::NEG_INFINITY, meter.integrated());
+    sine(&mut meter, 1000.0, -20.0, 2.0, &[true]);
+    let before = meter.integrated();
+    for _ in 0..SAMPLERATE * 2 { meter.process(&[0.0]); }
+    // only the blocks overlapping the end of the tone pass the gate,
+    // averaging in the silence would drop the loudness by 3 dB
+    assert!((meter.integrated() - before).abs() < 0.5, "{} {before}", meter.integrated());
+    assert!(meter.momentary() < ABSOLUTE_GATE);
+  }
+
+  #[test]
+  fn relative_gate() {
+    // EBU Tech 3341 case 3, the quiet parts are more than 10 LU down
+    let mut meter = LoudnessMeter::new(SAMPLERATE, 2);
+    sine(&mut meter, 1000.0, -36.0, 2.0, &[true, true]);
+    sine(&mut meter, 1000.0, -23.0, 20.0, &[true, true]);
+    sine(&mut meter, 1000.0, -36.0, 2.0, &[true, true]);
+    assert!((meter.integrated() + 23.0).abs() < 0.1, "{}", meter.integrated());
+  }
+
+  #[test]
+  fn loudness_range() {
+    // EBU Tech 3342 case 1
+    let mut meter = LoudnessMeter::new(SAMPLERATE, 1);
+    sine(&mut meter, 1000.0, -20.0, 20.0, &[true]);
+    sine(&mut meter, 1000.0, -30.0, 20.0, &[true]);
+    assert!((meter.loudness_range() - 10.0).abs() < 1.0, "{}", meter.loudness_range());
+  }
+
+  #[test]
+  fn true_peak() {
+    let mut meter = LoudnessMeter::new(SAMPLERATE, 1);
+    for n in 0..4800 {
+      meter.process(&[0.5 * f32::sin(TAU * 0.25 * n as f32 + core::f32::consts::FRAC_PI_4)]);
+    }
+    assert!((meter.true_peak() + 6.02).abs() < 0.5, "{}", meter.true_peak());
+  }
+
+  #[test]
+  fn reset() {
+    let mut meter = LoudnessMeter::new(SAMPLERATE, 1);
+    sine(&mut meter, 1000.0, -10.0, 1.0, &[true]);
+    meter.reset();
+    assert_eq!(f32::NEG_INFINITY, meter.integrated());
+    assert_eq!(f32::NEG_INFINITY, meter.momentary());
+  }
+}
@@ -0,0 +1,5 @@
+pub mod fft;
+pub mod onset;
+pub mod level;
+pub mod truepeak;
+pub mod loudness;
@@ -0,0 +1,328 @@
+use super::fft::{magnitudes, Fft};
+use crate::dsp::math::is_pow2;
+use alloc::{vec, vec::Vec};
+use core::f32::consts::PI;
+
+/// Function used to reduce each analysis frame to a single value.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DetectionFunction {
+  /// Sum of the positive magnitude differences between consecutive spectra.
+  #[default] SpectralFlux,
+  /// Magnitude spectrum weighted by bin number, emphasizes percussive content.
+  HighFrequencyContent,
+  /// Positive difference in windowed frame energy.
+  Energy,
+}
+
+impl DetectionFunction {
+  /// Default threshold offset, the functions differ in scale.
+  pub fn default_delta(&self) -> f32 {
+    match self {
+      DetectionFunction::SpectralFlux => 0.3,
+      DetectionFunction::HighFrequencyContent => 0.004,
+      DetectionFunction::Energy => 0.004,
+    }
+  }
+}
+
+/// Onset detector with adaptive thresholding and peak picking.
+///
+/// Input is analysed in overlapping frames of `frame_size` samples, every
+/// `hop_size` samples. A frame is reported as an onset when its detection
+/// function is a local maximum that exceeds the median of the recent history
+/// by `delta`, and when `min_interval` has passed since the last onset.
+/// `delta` is in units of the detection function, see
+/// [`DetectionFunction::default_delta`].
+///
+/// ```
+/// use rust_dsp::analysis::onset::{OnsetDetector, DetectionFunction};
+///
+/// let mut onset = OnsetDetector::new(48000, 1024, 256, DetectionFunction::SpectralFlux).unwrap();
+/// // 1.0 on the sample an onset is detected, 0.0 otherwise.
+/// let trig = onset.process(0.0);
+/// ```
+pub struct OnsetDetector {
+  function: DetectionFunction,
+  fft: Fft,
+  window: Vec<f32>,
+  input: Vec<f32>,
+  re: Vec<f32>,
+  im: Vec<f32>,
+  mags: Vec<f32>,
+  prev_mags: Vec<f32>,
+  prev_energy: f32,
+
+  history: Vec<f32>,
+  sorted: Vec<f32>,
+  history_pos: usize,
+  odf: [f32; 3],
+
+  write_pos: usize,
+  hop_size: usize,
+  hop_count: usize,
+  frame_count: usize,
+  last_onset: Option<usize>,
+
+  delta: f32,
+  multiplier: f32,
+  min_interval: usize,
+  samplerate: u32,
+}
+
+impl OnsetDetector {
+  /// `frame_size` needs to be a power of 2, and `hop_size` in the range `1..=frame_size`.
+  pub fn new(samplerate: u32, frame_size: usize, hop_size: usize, function: DetectionFunction) -> Result<Self, &'static str> {
+    if !is_pow2(frame_size) {
+      return Err("Frame size is not a power of 2");
+    }
+    if hop_size == 0 || hop_size > frame_size {
+      return Err("Hop size needs to be within 1..=frame_size");
+    }
+    let bins = frame_size / 2 + 1;
+    let window = (0..frame_size)
+      .map(|n| f32::sin(PI * n as f32 / frame_size as f32).powi(2))
+      .collect();
+    // ~100 ms of detection function history
+    let history_len = usize::max(3, (0.1 * samplerate as f32 / hop_size as f32) as usize);
+    let mut detector = Self {
+      function,
+      fft: Fft::new(frame_size)?,
+      window,
+      input: vec![0.0; frame_size],
+      re: vec![0.0; frame_size],
+      im: vec![0.0; frame_size],
+      mags: vec![0.0; bins],
+      prev_mags: vec![0.0; bins],
+      prev_energy: 0.0,
+      history: vec![0.0; history_len],
+      sorted: vec![0.0; history_len],
+      history_pos: 0,
+      odf: [0.0; 3],
+      write_pos: 0,
+      hop_size,
+      hop_count: 0,
+      frame_count: 0,
+      last_onset: None,
+      delta: 0.0,
+      multiplier: 1.5,
+      min_interval: 0,
+      samplerate,
+    };
+    detector.set_delta(function.default_delta());
+    detector.set_min_interval(0.05);
+    Ok(detector)
+  }
+
+  /// Analyse the next sample. Returns `1.0` on the sample an onset is
+  /// detected and `0.0` otherwise, following the [`TrigTrait`](crate::trig::TrigTrait) convention.
+  ///
+  /// Detection is delayed by one hop, as the peak picking needs to see the
+  /// frame following the peak.
+  #[inline]
+  pub fn process(&mut self, sample: f32) -> f32 {
+    self.input[self.write_pos] = sample;
+    self.write_pos = (self.write_pos + 1) & (self.input.len() - 1);
+    self.hop_count += 1;
+    if self.hop_count < self.hop_size {
+      return 0.0;
+    }
+    self.hop_count = 0;
+    if self.analyse() { 1.0 } else { 0.0 }
+  }
+
+  /// Runs the detector over an entire buffer, such as the contents of a
+  /// [`Buffer`](crate::buffer::Buffer) or a granulator buffer, and returns the
+  /// sample positions of the detected onsets.
+  ///
+  /// The detector is reset before and after, and positions are compensated
+  /// for the analysis latency.
+  pub fn detect(&mut self, buffer: &[f32]) -> Vec<usize> {
+    self.reset();
+    let latency = self.latency();
+    let mut onsets = Vec::new();
+    for (i, sample) in buffer.iter().enumerate() {
+      if self.process(*sample) >= 1.0 {
+        onsets.push(i.saturating_sub(latency));
+      }
+    }
+    self.reset();
+    onsets
+  }
+
+  /// Number of samples between an onset in the input and its detection.
+  pub fn latency(&self) -> usize {
+    self.hop_size + self.input.len() / 2
+  }
+
+  /// Last calculated value of the detection function.
+  pub fn odf(&self) -> f32 {
+    self.odf[2]
+  }
+
+  pub fn reset(&mut self) {
+    self.input.iter_mut().for_each(|x| *x = 0.0);
+    self.prev_mags.iter_mut().for_each(|x| *x = 0.0);
+    self.history.iter_mut().for_each(|x| *x = 0.0);
+    self.prev_energy = 0.0;
+    self.odf = [0.0; 3];
+    self.write_pos = 0;
+    self.hop_count = 0;
+    self.frame_count = 0;
+    self.history_pos = 0;
+    self.last_onset = None;
+  }
+
+  /// Changes the detection function and resets `delta` to its default.
+  pub fn set_function(&mut self, function: DetectionFunction) {
+    self.function = function;
+    self.delta = function.default_delta();
+    self.prev_energy = 0.0;
+  }
+
+  /// Fixed offset added to the adaptive threshold.
+  pub fn set_delta(&mut self, delta: f32) {
+    self.delta = delta;
+  }
+
+  /// Scales the median of the detection function history.
+  pub fn set_multiplier(&mut self, multiplier: f32) {
+    self.multiplier = multiplier;
+  }
+
+  /// Minimum time in seconds between two onsets.
+  pub fn set_min_interval(&mut self, seconds: f32) {
+    self.min_interval = (seconds * self.samplerate as f32 / self.hop_size as f32) as usize;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    let seconds = self.min_interval as f32 * self.hop_size as f32 / self.samplerate as f32;
+    self.samplerate = samplerate;
+    self.set_min_interval(seconds);
+  }
+
+  fn analyse(&mut self) -> bool {
+    let value = self.detection_function();
+    self.odf = [self.odf[1], self.odf[2], value];
+    self.frame_count += 1;
+
+    // median of the frames preceding the peak candidate
+    self.sorted.copy_from_slice(&self.history);
+    let mid = self.sorted.len() / 2;
+    let (_, median, _) = self.sorted.select_nth_unstable_by(mid, f32::total_cmp);
+    let threshold = self.delta + self.multiplier * *median;
+
+    let candidate = self.odf[1];
+    self.history[self.history_pos] = candidate;
+    self.history_pos = (self.history_pos + 1) % self.history.len();
+
+    let is_peak = candidate > self.odf[0] && candidate >= self.odf[2] && candidate > threshold;
+    let frame = self.frame_count - 1;
+    let waited = self.last_onset.is_none_or(|last| frame - last > self.min_interval);
+    if is_peak && waited {
+      self.last_onset = Some(frame);
+      return true;
+    }
+    false
+  }
+
+  fn detection_function(&mut self) -> f32 {
+    let size = self.input.len();
+    for i in 0..size {
+      let sample = self.input[(self.write_pos + i) & (size - 1)];
+      self.re[i] = sample * self.window[i];
+      self.im[i] = 0.0;
+    }
+
+    if self.function == DetectionFunction::Energy {
+      let energy = self.re.iter().map(|x| x * x).sum::<f32>() / size as f32;
+      let diff = f32::max(0.0, energy - self.prev_energy);
+      self.prev_energy = energy;
+      return diff;
+    }
+
+    self.fft.forward(&mut self.re, &mut self.im);
+    magnitudes(&self.re, &self.im, &mut self.mags);
+    let norm = 2.0 / size as f32;
+
+    match self.function {
+      DetectionFunction::SpectralFlux => {
+        let mut flux = 0.0;
+        for (m, p) in self.mags.iter().zip(self.prev_mags.iter_mut()) {
+          let m = m * norm;
+          flux += f32::max(0.0, m - *p);
+          *p = m;
+        }
+        flux
+      },
+      DetectionFunction::HighFrequencyContent => {
+        let bins = self.mags.len() as f32;
+        let hfc = self.mags.iter()
+          .enumerate()
+          .map(|(k, m)| k as f32 * (m * norm).powi(2))
+          .sum::<f32>() / bins;
+        let diff = f32::max(0.0, hfc - self.prev_energy);
+        self.prev_energy = hfc;
+        diff
+      },
+      DetectionFunction::Energy => unreachable!(),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::noise::Prng;
+
+  const SAMPLERATE: u32 = 48000;
+
+  /// Silence interrupted by short decaying noise bursts.
+  fn bursts(positions: &[usize], len: usize) -> Vec<f32> {
+    let mut rng = Prng::new(1234);
+    let mut buffer = vec![0.0; len];
+    for &p in positions {
+      for (i, x) in buffer[p..].iter_mut().take(4800).enumerate() {
+        *x = rng.frand_bipolar() * f32::exp(-(i as f32) / 800.0);
+      }
+    }
+    buffer
+  }
+
+  fn assert_onsets(function: DetectionFunction) {
+    let positions = [12000, 30000, 60000];
+    let buffer = bursts(&positions, 80000);
+    let mut onset = OnsetDetector::new(SAMPLERATE, 1024, 256, function).unwrap();
+    let found = onset.detect(&buffer);
+    assert_eq!(positions.len(), found.len(), "{function:?}: {found:?}");
+    for (p, f) in positions.iter().zip(&found) {
+      assert!(p.abs_diff(*f) < 1024, "{function:?}: expected {p}, found {f}");
+    }
+  }
+
+  #[test]
+  fn spectral_flux() {
+    assert_onsets(DetectionFunction::SpectralFlux);
+  }
+
+  #[test]
+  fn high_frequency_content() {
+    assert_onsets(DetectionFunction::HighFrequencyContent);
+  }
+
+  #[test]
+  fn energy() {
+    assert_onsets(DetectionFunction::Energy);
+  }
+
+  #[test]
+  fn silence() {
+    let mut onset = OnsetDetector::new(SAMPLERATE, 512, 128, DetectionFunction::SpectralFlux).unwrap();
+    assert!((0..48000).all(|_| onset.process(0.0) == 0.0));
+  }
+
+  #[test]
+  fn invalid_sizes() {
+    assert!(OnsetDetector::new(SAMPLERATE, 1000, 256, DetectionFunction::Energy).is_err());
+    assert!(OnsetDetector::new(SAMPLERATE, 1024, 2048, DetectionFunction::Energy).is_err());
+  }
+}
@@ -0,0 +1,113 @@
+use core::f32::consts::PI;
+
+/// Number of interpolated points between two samples.
+pub const OVERSAMPLING: usize = 4;
+const TAPS: usize = 12;
+
+/// True-peak detector following ITU-R BS.1770, estimating the peak level
+/// between samples by 4x oversampling through a polyphase windowed-sinc
+/// interpolator.
+///
+/// The interpolated points lie around the 6th most recent sample, so the
+/// estimate is delayed by [`TruePeak::LATENCY`] samples.
+pub struct TruePeak {
+  coeffs: [[f32; TAPS]; OVERSAMPLING],
+  history: [f32; TAPS],
+  position: usize,
+  max: f32,
+}
+
+impl Default for TruePeak {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl TruePeak {
+  pub const LATENCY: usize = TAPS / 2;
+
+  pub fn new() -> Self {
+    let mut coeffs = [[0.0; TAPS]; OVERSAMPLING];
+    let half = TAPS as f32 / 2.0;
+    for (p, phase) in coeffs.iter_mut().enumerate() {
+      // Points at 1/8, 3/8, 5/8 and 7/8 between the two center taps
+      let offset = (p as f32 + 0.5) / OVERSAMPLING as f32;
+      for (j, c) in phase.iter_mut().enumerate() {
+        let t = j as f32 - (half - 1.0) - offset;
+        let sinc = if t.abs() < f32::EPSILON { 1.0 } else { f32::sin(PI * t) / (PI * t) };
+        let window = 0.5 + 0.5 * f32::cos(PI * t / half);
+        *c = sinc * window;
+      }
+      let sum: f32 = phase.iter().sum();
+      phase.iter_mut().for_each(|c| *c /= sum);
+    }
+    Self { coeffs, history: [0.0; TAPS], position: 0, max: 0.0 }
+  }
+
+  /// Absolute peak, in linear gain, of the sample `LATENCY` samples back and
+  /// the interpolated points following it.
+  #[inline]
+  pub fn process(&mut self, sample: f32) -> f32 {
+    self.history[self.position] = sample;
+    self.position = (self.position + 1) % TAPS;
+    // oldest sample first
+    let mut peak = self.delayed().abs();
+    for phase in self.coeffs.iter() {
+      let mut sum = 0.0;
+      for (j, c) in phase.iter().enumerate() {
+        sum += c * self.history[(self.position + j) % TAPS];
+      }
+      peak = f32::max(peak, sum.abs());
+    }
+    self.max = f32::max(self.max, peak);
+    peak
+  }
+
+  /// The plain sample aligned with the latest estimate.
+  #[inline]
+  pub fn delayed(&self) -> f32 {
+    self.history[(self.position + Self::LATENCY - 1) % TAPS]
+  }
+
+  /// Highest peak since creation or the last [`TruePeak::reset_max`].
+  pub fn max(&self) -> f32 {
+    self.max
+  }
+
+  pub fn reset_max(&mut self) {
+    self.max = 0.0;
+  }
+
+  pub fn reset(&mut self) {
+    self.history = [0.0; TAPS];
+    self.position = 0;
+    self.max = 0.0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use core::f32::consts::{FRAC_PI_4, TAU};
+
+  #[test]
+  fn inter_sample_peak() {
+    // fs/4 sine sampled at 45 degrees, every sample is at ~0.707
+    let mut tp = TruePeak::new();
+    let mut sample_peak = 0.0f32;
+    for n in 0..4800 {
+      let x = f32::sin(TAU * 0.25 * n as f32 + FRAC_PI_4);
+      sample_peak = sample_peak.max(x.abs());
+      tp.process(x);
+    }
+    assert!(sample_peak < 0.71);
+    assert!((tp.max() - 1.0).abs() < 0.05, "{}", tp.max());
+  }
+
+  #[test]
+  fn passes_dc() {
+    let mut tp = TruePeak::new();
+    let out = (0..64).map(|_| tp.process(0.5)).last().unwrap();
+    assert!((out - 0.5).abs() < 1e-5);
+  }
+}
@@ -0,0 +1,315 @@
+use crate::midibitfield::MidiBitField;
+use crate::noise::Prng;
+use alloc::vec::Vec;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum ArpMode {
+  #[default] Up,
+  Down,
+  /// Up and back down, without repeating the top and bottom notes
+  UpDown,
+  Random,
+  /// In the order the notes were pressed
+  AsPlayed,
+  /// All notes at once, moving through the octaves
+  Chord,
+}
+
+/// Arpeggiator playing the held notes of a [`MidiBitField`].
+///
+/// Steps on each rising edge of the clock, like the
+/// [`trig`](crate::trig) patterns. The notes of the step are reported on the
+/// sample they start, while the gate stays high for the gate length, a
+/// fraction of the measured clock period.
+///
+/// ```
+/// use rust_dsp::{arp::{Arpeggiator, ArpMode}, dsp::math::midi_to_freq, polytable::PolyTable};
+///
+/// let mut arp = Arpeggiator::new();
+/// arp.set_mode(ArpMode::UpDown);
+/// arp.set_octaves(2);
+/// arp.note_on(60);
+/// arp.note_on(64);
+/// arp.note_on(67);
+///
+/// let mut poly = PolyTable::<4>::new();
+/// let trig = arp.play(1.0);
+/// arp.notes(&mut |note| poly.trigger(Some(midi_to_freq(note, 440.0))));
+/// let gate = arp.gate() > 0.0;
+/// ```
+pub struct Arpeggiator {
+  /// Physically held keys
+  pressed: MidiBitField,
+  /// Notes in the pattern, kept after release when latched
+  held: MidiBitField,
+  order: Vec<u8>,
+  pattern: Vec<u8>,
+  current: Vec<u8>,
+  triggered: bool,
+
+  mode: ArpMode,
+  octaves: u8,
+  gate_length: f32,
+  latch: bool,
+  rng: Prng,
+
+  step: usize,
+  prev_clock: f32,
+  period: usize,
+  counter: usize,
+  started: bool,
+  gate: usize,
+}
+
+impl Default for Arpeggiator {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Arpeggiator {
+  pub fn new() -> Self {
+    Self {
+      pressed: MidiBitField::new(),
+      held: MidiBitField::new(),
+      order: Vec::with_capacity(128),
+      pattern: Vec::with_capacity(128),
+      current: Vec::with_capacity(128),
+      triggered: false,
+      mode: ArpMode::Up,
+      octaves: 1,
+      gate_length: 0.5,
+      latch: false,
+      rng: Prng::new(0),
+      step: 0,
+      prev_clock: 0.0,
+      period: 0,
+      counter: 0,
+      started: false,
+      gate: 0,
+    }
+  }
+
+  pub fn note_on(&mut self, note: u8) {
+    if note >= 128 { return }
+    // a new chord replaces a latched one
+    if self.latch && self.pressed.is_empty() {
+      self.held.reset();
+      self.order.clear();
+    }
+    let _ = self.pressed.add(note);
+    if !self.held.check(note) {
+      let _ = self.held.add(note);
+      self.order.push(note);
+    }
+  }
+
+  pub fn note_off(&mut self, note: u8) {
+    let _ = self.pressed.remove(note);
+    if !self.latch {
+      let _ = self.held.remove(note);
+      self.order.retain(|n| *n != note);
+    }
+  }
+
+  /// Notes in the pattern
+  pub fn held(&self) -> &MidiBitField {
+    &self.held
+  }
+
+  /// Returns `1.0` on the samples notes start.
+  #[inline]
+  pub fn play(&mut self, clock: f32) -> f32 {
+    self.triggered = false;
+    if self.gate > 0 {
+      self.gate -= 1;
+    }
+    if clock > 0.0 && self.prev_clock <= 0.0 {
+      if self.started { self.period = self.counter; }
+      self.started = true;
+      self.counter = 0;
+      self.next_step();
+    }
+    self.prev_clock = clock;
+    self.counter += 1;
+    if self.triggered { 1.0 } else { 0.0 }
+  }
+
+  fn next_step(&mut self) {
+    self.update_pattern();
+    self.current.clear();
+    let len = self.pattern.len();
+    if len == 0 {
+      self.gate = 0;
+      return;
+    }
+    match self.mode {
+      ArpMode::Up | ArpMode::AsPlayed => self.current.push(self.pattern[self.step % len]),
+      ArpMode::Down => self.current.push(self.pattern[len - 1 - self.step % len]),
+      ArpMode::UpDown => {
+        let cycle = usize::max(1, 2 * len - 2);
+        let i = self.step % cycle;
+        self.current.push(self.pattern[if i < len { i } else { cycle - i }]);
+      }
+      ArpMode::Random => {
+        let i = (self.rng.frand_unipolar() * len as f32) as usize;
+        self.current.push(self.pattern[i.min(len - 1)]);
+      }
+      ArpMode::Chord => {
+        let octave = 12 * (self.step % self.octaves as usize) as u8;
+        self.held.notes(&mut |note| {
+          if note + octave < 128 { self.current.push(note + octave); }
+        });
+      }
+    }
+    self.step += 1;
+    self.triggered = !self.current.is_empty();
+    // the gate stays open until the next clock, before a period is measured
+    self.gate = if self.period == 0 {
+      usize::MAX
+    } else {
+      usize::max(1, (self.gate_length * self.period as f32) as usize)
+    };
+  }
+
+  /// Held notes over the octave range, in playing order.
+  fn update_pattern(&mut self) {
+    self.pattern.clear();
+    for octave in 0..self.octaves {
+      let offset = 12 * octave;
+      let pattern = &mut self.pattern;
+      let mut push = |note: u8| if note + offset < 128 { pattern.push(note + offset) };
+      if self.mode == ArpMode::AsPlayed {
+        self.order.iter().for_each(|n| push(*n));
+      } else {
+        self.held.notes(&mut push);
+      }
+    }
+  }
+
+  /// Calls `func` with each note started on this sample.
+  pub fn notes(&self, func: &mut impl FnMut(u8)) {
+    if !self.triggered { return }
+    self.current.iter().for_each(|n| func(*n));
+  }
+
+  /// `1.0` while the notes of the current step are held.
+  pub fn gate(&self) -> f32 {
+    if self.gate > 0 && !self.current.is_empty() { 1.0 } else { 0.0 }
+  }
+
+  pub fn set_mode(&mut self, mode: ArpMode) {
+    self.mode = mode;
+  }
+
+  /// Number of octaves the pattern spans, `1..=10`.
+  pub fn set_octaves(&mut self, octaves: u8) {
+    self.octaves = octaves.clamp(1, 10);
+  }
+
+  /// Gate length as a fraction of the clock period.
+  pub fn set_gate_length(&mut self, length: f32) {
+    self.gate_length = length.max(0.0);
+  }
+
+  /// Keep playing the last notes after the keys are released.
+  pub fn set_latch(&mut self, latch: bool) {
+    self.latch = latch;
+    if !latch {
+      self.held = MidiBitField::new();
+      self.order.retain(|n| self.pressed.check(*n));
+      self.pressed.notes(&mut |n| { let _ = self.held.add(n); });
+    }
+  }
+
+  /// Seed of the random mode
+  pub fn set_seed(&mut self, seed: u32) {
+    self.rng.reset(seed);
+  }
+
+  /// Start the pattern from the first step on the next clock.
+  pub fn reset(&mut self) {
+    self.step = 0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Notes started over `steps` clocks, 10 samples apart.
+  fn run(arp: &mut Arpeggiator, steps: usize) -> Vec<Vec<u8>> {
+    let mut out = Vec::new();
+    for n in 0..steps * 10 {
+      if arp.play(if n % 10 == 0 { 1.0 } else { 0.0 }) > 0.0 {
+        let mut notes = Vec::new();
+        arp.notes(&mut |note| notes.push(note));
+        out.push(notes);
+      }
+    }
+    out
+  }
+
+  fn single(arp: &mut Arpeggiator, steps: usize) -> Vec<u8> {
+    run(arp, steps).into_iter().map(|n| n[0]).collect()
+  }
+
+  fn with_notes(mode: ArpMode, notes: &[u8]) -> Arpeggiator {
+    let mut arp = Arpeggiator::new();
+    arp.set_mode(mode);
+    notes.iter().for_each(|n| arp.note_on(*n));
+    arp
+  }
+
+  #[test]
+  fn modes() {
+    let notes = [64, 60, 67];
+    assert_eq!(vec![60, 64, 67, 60], single(&mut with_notes(ArpMode::Up, &notes), 4));
+    assert_eq!(vec![67, 64, 60, 67], single(&mut with_notes(ArpMode::Down, &notes), 4));
+    assert_eq!(vec![60, 64, 67, 64, 60, 64], single(&mut with_notes(ArpMode::UpDown, &notes), 6));
+    assert_eq!(vec![64, 60, 67, 64], single(&mut with_notes(ArpMode::AsPlayed, &notes), 4));
+    assert_eq!(vec![vec![60, 64, 67]], run(&mut with_notes(ArpMode::Chord, &notes), 1));
+    let random = single(&mut with_notes(ArpMode::Random, &notes), 32);
+    assert!(random.iter().all(|n| notes.contains(n)));
+  }
+
+  #[test]
+  fn octaves() {
+    let mut arp = with_notes(ArpMode::Up, &[60, 64]);
+    arp.set_octaves(2);
+    assert_eq!(vec![60, 64, 72, 76, 60], single(&mut arp, 5));
+    let mut arp = with_notes(ArpMode::Chord, &[60, 64]);
+    arp.set_octaves(2);
+    assert_eq!(vec![vec![60, 64], vec![72, 76]], run(&mut arp, 2));
+  }
+
+  #[test]
+  fn note_off_and_latch() {
+    let mut arp = with_notes(ArpMode::Up, &[60, 64]);
+    arp.note_off(60);
+    assert_eq!(vec![64, 64], single(&mut arp, 2));
+    arp.note_off(64);
+    assert!(run(&mut arp, 2).is_empty());
+
+    arp.set_latch(true);
+    arp.note_on(60);
+    arp.note_on(62);
+    arp.note_off(60);
+    arp.note_off(62);
+    arp.reset();
+    assert_eq!(vec![60, 62], single(&mut arp, 2));
+    // a new chord replaces the latched one
+    arp.note_on(65);
+    assert_eq!(vec![65], single(&mut arp, 1));
+  }
+
+  #[test]
+  fn gate_length() {
+    let mut arp = with_notes(ArpMode::Up, &[60]);
+    arp.set_gate_length(0.3);
+    let gates: Vec<f32> = (0..40).map(|n| { arp.play(if n % 10 == 0 { 1.0 } else { 0.0 }); arp.gate() }).collect();
+    // the first step has no measured period and holds until the next
+    assert!(gates[..10].iter().all(|g| *g == 1.0));
+    assert_eq!(3.0, gates[20..30].iter().sum::<f32>());
+  }
+}
@@ -0,0 +1,322 @@
+use crate::dsp::time::Division;
+
+/// Quarter notes of margin for rounding errors in the grid
+const EPSILON: f64 = 1e-9;
+
+/// Sample accurate master clock.
+///
+/// The position is kept in quarter notes and advanced every sample from the
+/// current tempo, so tempo changes bend the timing without the triggers
+/// drifting from the grid. Triggers follow the
+/// [`TrigTrait`](crate::trig::TrigTrait) convention and are `1.0` on the
+/// sample where the grid line falls, so they can clock
+/// [`Euclid`](crate::trig::Euclid) and [`Sequencer`](crate::trig::Sequencer)
+/// directly.
+///
+/// The beat is the note value of the time signature denominator, so in 6/8
+/// there are six eighth note beats to the bar. Swing delays every second
+/// trigger of streams shorter than a beat.
+///
+/// ```
+/// use rust_dsp::{clock::Clock, dsp::time::Division, trig::{Euclid, Impulse, TrigTrait}};
+///
+/// let mut clock = Clock::new(48000);
+/// clock.set_bpm(96.0);
+/// clock.set_swing(0.3);
+/// clock.start();
+///
+/// let mut euclid = Euclid::new(16, 5);
+/// let mut impulse = Impulse::new(48000);
+/// clock.tick();
+/// let sixteenths = euclid.play(clock.division(Division::Note(16)));
+/// let bars = clock.bar();
+/// let free = impulse.play(clock.seconds(Division::Note(8)));
+/// ```
+pub struct Clock {
+  bpm: f32,
+  numerator: u32,
+  denominator: u32,
+  swing: f32,
+  playing: bool,
+  prev: f64,
+  position: f64,
+  /// Position at the last tempo change or locate
+  anchor: f64,
+  elapsed: u64,
+  increment: f64,
+  samplerate: f32,
+}
+
+impl Clock {
+  /// Stopped at the start, at 120 BPM in 4/4.
+  pub fn new(samplerate: u32) -> Self {
+    let mut clock = Self {
+      bpm: 120.0,
+      numerator: 4,
+      denominator: 4,
+      swing: 0.0,
+      playing: false,
+      prev: 0.0,
+      position: 0.0,
+      anchor: 0.0,
+      elapsed: 0,
+      increment: 0.0,
+      samplerate: samplerate as f32,
+    };
+    clock.set_bpm(120.0);
+    clock
+  }
+
+  /// Advance one sample, call before reading the triggers of the sample.
+  #[inline]
+  pub fn tick(&mut self) {
+    self.prev = self.position;
+    if self.playing {
+      // counted from the anchor rather than accumulated, which would drift
+      self.elapsed += 1;
+      self.position = self.anchor + self.elapsed as f64 * self.increment;
+    }
+  }
+
+  /// `1.0` on each grid line `quarters` quarter notes apart, swung when
+  /// shorter than a beat.
+  #[inline]
+  pub fn every(&self, quarters: f64) -> f32 {
+    let swing = if quarters < self.beat_length() { self.swing as f64 } else { 0.0 };
+    let before = |x: f64| {
+      let pairs = (x / (2.0 * quarters)).floor();
+      let rest = x - pairs * 2.0 * quarters;
+      2.0 * pairs + (rest > 0.0) as u8 as f64 + (rest > quarters * (1.0 + 0.5 * swing)) as u8 as f64
+    };
+    // grid lines in [prev, position), with a margin for rounding errors
+    // landing the position just past a grid line
+    if before(self.position - EPSILON) > before(self.prev - EPSILON) { 1.0 } else { 0.0 }
+  }
+
+  /// Trigger stream of a musical division.
+  #[inline]
+  pub fn division(&self, division: Division) -> f32 {
+    self.every(division.beats() as f64)
+  }
+
+  /// One trigger every `n` beats.
+  #[inline]
+  pub fn divide(&self, n: u32) -> f32 {
+    self.every(self.beat_length() * n.max(1) as f64)
+  }
+
+  /// `n` triggers per beat.
+  #[inline]
+  pub fn multiply(&self, n: u32) -> f32 {
+    self.every(self.beat_length() / n.max(1) as f64)
+  }
+
+  #[inline]
+  pub fn beat(&self) -> f32 {
+    self.every(self.beat_length())
+  }
+
+  #[inline]
+  pub fn bar(&self) -> f32 {
+    self.every(self.bar_length())
+  }
+
+  /// Length of a beat in quarter notes
+  fn beat_length(&self) -> f64 {
+    4.0 / self.denominator as f64
+  }
+
+  /// Length of a bar in quarter notes
+  fn bar_length(&self) -> f64 {
+    self.beat_length() * self.numerator as f64
+  }
+
+  /// Position in quarter notes since the start.
+  pub fn position(&self) -> f64 {
+    self.position
+  }
+
+  /// Current bar, counting from `0`.
+  pub fn bars(&self) -> u64 {
+    (self.position / self.bar_length()) as u64
+  }
+
+  /// Current beat within the bar, counting from `0`.
+  pub fn beat_in_bar(&self) -> u32 {
+    ((self.position % self.bar_length()) / self.beat_length()) as u32
+  }
+
+  /// Progress through the current `division`, `0.0..1.0`, e.g. to drive
+  /// a synced oscillator.
+  pub fn phase(&self, division: Division) -> f32 {
+    let length = division.beats() as f64;
+    ((self.position % length) / length) as f32
+  }
+
+  /// Length of `division` in seconds at the current tempo, for the
+  /// duration of [`TrigTrait::play`](crate::trig::TrigTrait::play).
+  pub fn seconds(&self, division: Division) -> f32 {
+    division.seconds(self.bpm)
+  }
+
+  /// Play from the start.
+  pub fn start(&mut self) {
+    self.locate(0.0);
+    self.playing = true;
+  }
+
+  /// Pause, keeping the position.
+  pub fn stop(&mut self) {
+    self.playing = false;
+  }
+
+  /// Play from the current position.
+  pub fn resume(&mut self) {
+    self.playing = true;
+  }
+
+  /// Jump to `quarters` quarter notes. A grid line at the new position
+  /// triggers on the next tick.
+  pub fn locate(&mut self, quarters: f64) {
+    self.position = quarters.max(0.0);
+    self.prev = self.position;
+    self.anchor = self.position;
+    self.elapsed = 0;
+  }
+
+  pub fn is_playing(&self) -> bool {
+    self.playing
+  }
+
+  pub fn bpm(&self) -> f32 {
+    self.bpm
+  }
+
+  /// Tempo in quarter notes per minute, applies from the next sample
+  /// without moving the position.
+  pub fn set_bpm(&mut self, bpm: f32) {
+    self.bpm = bpm.max(0.0);
+    self.anchor = self.position;
+    self.elapsed = 0;
+    self.increment = self.bpm as f64 / (60.0 * self.samplerate as f64);
+  }
+
+  /// `numerator` beats of `1/denominator` notes to the bar.
+  pub fn set_time_signature(&mut self, numerator: u32, denominator: u32) {
+    self.numerator = numerator.max(1);
+    self.denominator = denominator.max(1);
+  }
+
+  /// Delay of every second trigger, `0.0` is straight and `1.0` delays by
+  /// half the trigger spacing.
+  pub fn set_swing(&mut self, swing: f32) {
+    self.swing = swing.clamp(0.0, 1.0);
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.set_bpm(self.bpm);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trig::Sequencer;
+
+  const SAMPLERATE: u32 = 48000;
+
+  /// Sample indices where `stream` triggers during `samples`.
+  fn triggers(clock: &mut Clock, samples: usize, stream: impl Fn(&Clock) -> f32) -> Vec<usize> {
+    (0..samples).filter(|_| { clock.tick(); stream(clock) > 0.0 }).collect()
+  }
+
+  fn started() -> Clock {
+    let mut clock = Clock::new(SAMPLERATE);
+    clock.start();
+    clock
+  }
+
+  #[test]
+  fn beats_and_bars() {
+    let mut clock = started();
+    assert_eq!(vec![0, 24000, 48000, 72000], triggers(&mut clock, 96000, Clock::beat));
+    let mut clock = started();
+    clock.set_time_signature(3, 4);
+    assert_eq!(vec![0, 72000], triggers(&mut clock, 96000, Clock::bar));
+    assert_eq!(1, clock.bars());
+    assert_eq!(1, clock.beat_in_bar());
+  }
+
+  #[test]
+  fn divide_and_multiply() {
+    let mut clock = started();
+    assert_eq!(8, triggers(&mut clock, 48000, |c| c.multiply(4)).len());
+    let mut clock = started();
+    assert_eq!(vec![0, 72000], triggers(&mut clock, 96000, |c| c.divide(3)));
+    let mut clock = started();
+    assert_eq!(vec![0, 36000], triggers(&mut clock, 48000, |c| c.division(Division::Dotted(4))));
+  }
+
+  #[test]
+  fn compound_meter_beats() {
+    let mut clock = started();
+    clock.set_time_signature(6, 8);
+    assert_eq!(vec![0, 12000, 24000, 36000], triggers(&mut clock, 48000, Clock::beat));
+  }
+
+  #[test]
+  fn swing() {
+    let mut clock = started();
+    clock.set_swing(0.5);
+    // eighths are 12000 samples, the offbeats move a quarter of that
+    assert_eq!(vec![0, 15000, 24000, 39000], triggers(&mut clock, 48000, |c| c.division(Division::Note(8))));
+    // beats are not swung
+    let mut clock = started();
+    clock.set_swing(0.5);
+    assert_eq!(vec![0, 24000], triggers(&mut clock, 48000, Clock::beat));
+  }
+
+  #[test]
+  fn tempo_change_keeps_grid() {
+    let mut clock = started();
+    let mut hits = triggers(&mut clock, 12000, Clock::beat);
+    clock.set_bpm(60.0);
+    // the remaining half beat takes twice as long
+    hits.extend(triggers(&mut clock, 48000, Clock::beat).into_iter().map(|n| n + 12000));
+    assert_eq!(vec![0, 36000], hits);
+    assert!((clock.position() - 1.5).abs() < 1e-9);
+  }
+
+  #[test]
+  fn no_drift() {
+    let mut clock = started();
+    clock.set_bpm(137.0);
+    let seconds = 600;
+    (0..SAMPLERATE * seconds).for_each(|_| clock.tick());
+    let expected = 137.0 / 60.0 * seconds as f64;
+    assert!((clock.position() - expected).abs() < 1e-6, "{}", clock.position());
+  }
+
+  #[test]
+  fn transport() {
+    let mut clock = Clock::new(SAMPLERATE);
+    assert!(triggers(&mut clock, 48000, Clock::beat).is_empty());
+    clock.start();
+    triggers(&mut clock, 30000, Clock::beat);
+    clock.stop();
+    assert!(triggers(&mut clock, 48000, Clock::beat).is_empty());
+    clock.resume();
+    assert_eq!(vec![18000], triggers(&mut clock, 24000, Clock::beat));
+    clock.locate(4.0);
+    assert_eq!(vec![0], triggers(&mut clock, 100, Clock::bar));
+  }
+
+  #[test]
+  fn clocks_sequencer() {
+    let mut clock = started();
+    let mut seq = Sequencer::new(4);
+    let hits: usize = (0..48000).map(|_| { clock.tick(); seq.play(clock.division(Division::Note(16))) as usize }).sum();
+    assert_eq!(8, hits);
+  }
+}
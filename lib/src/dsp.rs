@@ -230,7 +230,7 @@ pub mod math {
   /// Translate midi-number to frequency
   #[inline]
   pub fn midi_to_freq(midi: u8, tuning: f32) -> f32 {
-    let exp: f32 = (midi - 69) as f32 / 12.0;
+    let exp: f32 = (midi as f32 - 69.0) / 12.0;
     tuning * f32::powf(2.0, exp)
   }
 
@@ -273,6 +273,69 @@ pub mod math {
   pub fn wavelength_to_samples(wavelength: f32, samplerate: f32) -> usize {
     (samplerate / (343.0 / wavelength)) as usize
   }
+
+  /// Translate a time in milliseconds to number of samples
+  #[inline]
+  pub fn ms_to_samples(ms: f32, samplerate: f32) -> usize {
+    (ms * 0.001 * samplerate) as usize
+  }
+
+  /// Translate a time constant in milliseconds to a one-pole coefficient,
+  /// `y = x + coeff * (y - x)` reaches ~63% of a step within `ms`.
+  #[inline]
+  pub fn ms_to_coeff(ms: f32, samplerate: f32) -> f32 {
+    if ms <= 0.0 { return 0.0 }
+    f32::exp(-1000.0 / (ms * samplerate))
+  }
+}
+
+pub mod time {
+  /// Musical note length, relative to a quarter note beat.
+  #[derive(Clone, Copy, Debug, PartialEq)]
+  pub enum Division {
+    /// `n` bars of 4/4
+    Bars(u32),
+    /// `1/n` note, `Note(4)` is a quarter note
+    Note(u32),
+    /// `1/n` note lengthened by half
+    Dotted(u32),
+    /// `1/n` note, three in the time of two
+    Triplet(u32),
+  }
+
+  impl Division {
+    /// Length in quarter note beats
+    pub fn beats(&self) -> f32 {
+      match *self {
+        Division::Bars(n) => 4.0 * n as f32,
+        Division::Note(n) => 4.0 / n as f32,
+        Division::Dotted(n) => 6.0 / n as f32,
+        Division::Triplet(n) => 8.0 / 3.0 / n as f32,
+      }
+    }
+
+    /// Length in seconds at `bpm` quarter notes per minute
+    pub fn seconds(&self, bpm: f32) -> f32 {
+      bpm_to_seconds(bpm) * self.beats()
+    }
+
+    /// Repetition rate in Hz at `bpm` quarter notes per minute
+    pub fn hz(&self, bpm: f32) -> f32 {
+      1.0 / self.seconds(bpm)
+    }
+  }
+
+  /// Length of one beat in seconds
+  #[inline]
+  pub fn bpm_to_seconds(bpm: f32) -> f32 {
+    60.0 / bpm
+  }
+
+  /// Length of one beat in samples
+  #[inline]
+  pub fn bpm_to_samples(bpm: f32, samplerate: f32) -> f32 {
+    bpm_to_seconds(bpm) * samplerate
+  }
 }
 
 #[cfg(test)]
@@ -296,5 +359,20 @@ mod test {
     assert_eq!(0.0, pan_exp2(-1.0).0);
     assert!((pan_exp2(-1.0).1 - 1.0).abs() < f32::EPSILON);
   }
-}
 
+  #[test]
+  fn midi_below_a4() {
+    use crate::dsp::math::midi_to_freq;
+    assert_eq!(220.0, midi_to_freq(57, 440.0));
+    assert!((midi_to_freq(0, 440.0) - 8.175799).abs() < 1e-4);
+  }
+
+  #[test]
+  fn divisions() {
+    use crate::dsp::time::Division;
+    assert_eq!(2.0, Division::Note(4).hz(120.0));
+    assert_eq!(2.0, Division::Bars(1).seconds(120.0));
+    assert_eq!(0.75, Division::Dotted(8).beats());
+    assert!((Division::Triplet(8).beats() - 1.0 / 3.0).abs() < 1e-6);
+  }
+}
@@ -0,0 +1,311 @@
+use super::{GainComputer, Link};
+use crate::dsp::math::{db_to_volume, hz_to_radian, ms_to_coeff, volume_to_db};
+use crate::filter::{
+  biquad::{calc, twopole::Biquad, BiquadTrait},
+  Filter,
+};
+use core::f32::consts::FRAC_1_SQRT_2;
+
+/// Time constant of the average gain reduction used by auto release.
+const AUTO_RELEASE_AVERAGE: f32 = 500.0;
+/// Auto release of sustained gain reduction is this many times slower.
+const AUTO_RELEASE_SLOWDOWN: f32 = 5.0;
+
+#[derive(Clone, Copy)]
+struct Channel {
+  reduction: f32,
+  average: f32,
+  hpf: Biquad,
+}
+
+/// Feed-forward compressor with soft knee and optional external sidechain.
+///
+/// The sidechain level is detected per sample in dB, passed through the
+/// [`GainComputer`] and the resulting gain reduction is smoothed with the
+/// attack and release times. The sidechain can be highpassed to keep low
+/// frequencies from pumping the compressor.
+///
+/// ```
+/// use rust_dsp::dynamics::Compressor;
+///
+/// let mut comp = Compressor::new(48000);
+/// comp.set_threshold(-18.0);
+/// comp.set_ratio(4.0);
+/// comp.set_sidechain_hpf(Some(120.0));
+/// let out = comp.process(0.5);
+/// let meter = comp.gain_reduction();
+/// ```
+pub struct Compressor {
+  computer: GainComputer,
+  channels: [Channel; 2],
+  link: Link,
+  makeup: f32,
+  attack: f32,
+  release: f32,
+  atk_coeff: f32,
+  rel_coeff: f32,
+  /// Release coefficient of sustained reduction with auto release
+  slow_coeff: f32,
+  avg_coeff: f32,
+  auto_release: bool,
+  hpf_freq: Option<f32>,
+  samplerate: f32,
+}
+
+impl Compressor {
+  pub fn new(samplerate: u32) -> Self {
+    let channel = Channel {
+      reduction: 0.0,
+      average: 0.0,
+      hpf: Biquad::new(calc::hpf(hz_to_radian(20.0, samplerate as f32), FRAC_1_SQRT_2)),
+    };
+    let mut comp = Self {
+      computer: GainComputer::new(-12.0, 4.0, 6.0),
+      channels: [channel; 2],
+      link: Link::Linked,
+      makeup: 1.0,
+      attack: 10.0,
+      release: 100.0,
+      atk_coeff: 0.0,
+      rel_coeff: 0.0,
+      slow_coeff: 0.0,
+      avg_coeff: 0.0,
+      auto_release: false,
+      hpf_freq: None,
+      samplerate: samplerate as f32,
+    };
+    comp.set_samplerate(samplerate);
+    comp
+  }
+
+  /// Compress `input` using itself as sidechain.
+  #[inline]
+  pub fn process(&mut self, input: f32) -> f32 {
+    self.process_keyed(input, input)
+  }
+
+  /// Compress `input` using the level of `sidechain`.
+  #[inline]
+  pub fn process_keyed(&mut self, input: f32, sidechain: f32) -> f32 {
+    let level = self.detect(0, sidechain);
+    let gain = self.smooth(0, self.computer.gain(level));
+    input * db_to_volume(gain) * self.makeup
+  }
+
+  /// Compress a stereo pair, using itself as sidechain.
+  #[inline]
+  pub fn process_stereo(&mut self, input: (f32, f32)) -> (f32, f32) {
+    self.process_stereo_keyed(input, input)
+  }
+
+  /// Compress a stereo pair using the level of a stereo `sidechain`.
+  /// Depending on [`Link`] the channels share a detector or are
+  /// compressed independently.
+  pub fn process_stereo_keyed(&mut self, input: (f32, f32), sidechain: (f32, f32)) -> (f32, f32) {
+    let left = self.detect(0, sidechain.0);
+    let right = self.detect(1, sidechain.1);
+    let (gain_l, gain_r) = match self.link {
+      Link::Linked => {
+        let gain = self.smooth(0, self.computer.gain(f32::max(left, right)));
+        self.channels[1].reduction = self.channels[0].reduction;
+        (gain, gain)
+      },
+      Link::Unlinked => (
+        self.smooth(0, self.computer.gain(left)),
+        self.smooth(1, self.computer.gain(right)),
+      )
+    };
+    (
+      input.0 * db_to_volume(gain_l) * self.makeup,
+      input.1 * db_to_volume(gain_r) * self.makeup,
+    )
+  }
+
+  /// Current gain reduction in dB, `<= 0.0`, excluding makeup gain.
+  /// Reports the channel with the most reduction when unlinked.
+  pub fn gain_reduction(&self) -> f32 {
+    f32::min(self.channels[0].reduction, self.channels[1].reduction)
+  }
+
+  #[inline]
+  fn detect(&mut self, channel: usize, sidechain: f32) -> f32 {
+    let ch = &mut self.channels[channel];
+    let key = if self.hpf_freq.is_some() { ch.hpf.process(sidechain) } else { sidechain };
+    volume_to_db(key.abs())
+  }
+
+  /// Smooth the target gain in the log domain, attack when the reduction
+  /// increases and release when it recovers.
+  #[inline]
+  fn smooth(&mut self, channel: usize, target: f32) -> f32 {
+    let ch = &mut self.channels[channel];
+    let coeff = if target < ch.reduction {
+      self.atk_coeff
+    } else if self.auto_release {
+      // Sustained reduction releases slower than short peaks
+      let sustained = if ch.reduction < -f32::EPSILON {
+        (ch.average / ch.reduction).clamp(0.0, 1.0)
+      } else {
+        0.0
+      };
+      self.rel_coeff + sustained * (self.slow_coeff - self.rel_coeff)
+    } else {
+      self.rel_coeff
+    };
+    ch.reduction = target + coeff * (ch.reduction - target);
+    ch.average = ch.reduction + self.avg_coeff * (ch.average - ch.reduction);
+    ch.reduction
+  }
+
+  /// Threshold in dB
+  pub fn set_threshold(&mut self, threshold: f32) { self.computer.threshold = threshold; }
+  /// Ratio `n:1`, `1.0` disables compression
+  pub fn set_ratio(&mut self, ratio: f32) { self.computer.ratio = f32::max(1.0, ratio); }
+  /// Width of the soft knee in dB
+  pub fn set_knee(&mut self, knee: f32) { self.computer.knee = f32::max(0.0, knee); }
+  /// Makeup gain in dB
+  pub fn set_makeup(&mut self, makeup: f32) { self.makeup = db_to_volume(makeup); }
+  pub fn set_link(&mut self, link: Link) { self.link = link; }
+
+  /// Attack time in milliseconds
+  pub fn set_attack(&mut self, ms: f32) {
+    self.attack = ms;
+    self.atk_coeff = ms_to_coeff(ms, self.samplerate);
+  }
+
+  /// Release time in milliseconds. With auto release this is the shortest
+  /// release, used for transients.
+  pub fn set_release(&mut self, ms: f32) {
+    self.release = ms;
+    self.rel_coeff = ms_to_coeff(ms, self.samplerate);
+    self.slow_coeff = ms_to_coeff(ms * AUTO_RELEASE_SLOWDOWN, self.samplerate);
+  }
+
+  /// Program dependent release, sustained gain reduction recovers up to 5
+  /// times slower than short peaks.
+  pub fn set_auto_release(&mut self, auto_release: bool) {
+    self.auto_release = auto_release;
+  }
+
+  /// Highpass the sidechain at `freq` Hz, `None` disables the filter.
+  pub fn set_sidechain_hpf(&mut self, freq: Option<f32>) {
+    self.hpf_freq = freq;
+    if let Some(freq) = freq {
+      let coeffs = calc::hpf(hz_to_radian(freq, self.samplerate), FRAC_1_SQRT_2);
+      self.channels.iter_mut().for_each(|ch| ch.hpf.update(&coeffs));
+    }
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.avg_coeff = ms_to_coeff(AUTO_RELEASE_AVERAGE, self.samplerate);
+    self.set_attack(self.attack);
+    self.set_release(self.release);
+    self.set_sidechain_hpf(self.hpf_freq);
+  }
+
+  pub fn reset(&mut self) {
+    for ch in self.channels.iter_mut() {
+      ch.reduction = 0.0;
+      ch.average = 0.0;
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use core::f32::consts::TAU;
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn settle(comp: &mut Compressor, input: f32) -> f32 {
+    (0..SAMPLERATE).map(|_| comp.process(input)).last().unwrap()
+  }
+
+  #[test]
+  fn static_gain() {
+    let mut comp = Compressor::new(SAMPLERATE);
+    comp.set_threshold(-20.0);
+    comp.set_ratio(4.0);
+    comp.set_knee(0.0);
+    let input = db_to_volume(-6.0);
+    let out = settle(&mut comp, input);
+    assert!((volume_to_db(out) - (-20.0 + 14.0 / 4.0)).abs() < 0.01, "{}", volume_to_db(out));
+    assert!((comp.gain_reduction() + 10.5).abs() < 0.01);
+  }
+
+  #[test]
+  fn below_threshold_untouched() {
+    let mut comp = Compressor::new(SAMPLERATE);
+    comp.set_threshold(-6.0);
+    comp.set_knee(0.0);
+    assert_eq!(0.25, settle(&mut comp, 0.25));
+    assert_eq!(0.0, comp.gain_reduction());
+  }
+
+  #[test]
+  fn makeup() {
+    let mut comp = Compressor::new(SAMPLERATE);
+    comp.set_threshold(0.0);
+    comp.set_makeup(6.0);
+    let out = settle(&mut comp, 0.25);
+    assert!((out - 0.25 * db_to_volume(6.0)).abs() < 1e-6);
+  }
+
+  #[test]
+  fn attack_time() {
+    let mut comp = Compressor::new(SAMPLERATE);
+    comp.set_threshold(-20.0);
+    comp.set_ratio(f32::INFINITY);
+    comp.set_knee(0.0);
+    comp.set_attack(10.0);
+    // one time constant reaches ~63% of the final reduction
+    for _ in 0..480 { comp.process(1.0); }
+    let reached = comp.gain_reduction() / -20.0;
+    assert!((reached - (1.0 - f32::exp(-1.0))).abs() < 0.01, "{reached}");
+  }
+
+  #[test]
+  fn sidechain_hpf() {
+    let rumble = |n: u32| f32::sin(TAU * 30.0 * n as f32 / SAMPLERATE as f32);
+    let mut filtered = Compressor::new(SAMPLERATE);
+    filtered.set_threshold(-20.0);
+    filtered.set_sidechain_hpf(Some(1000.0));
+    let mut plain = Compressor::new(SAMPLERATE);
+    plain.set_threshold(-20.0);
+    for n in 0..SAMPLERATE {
+      filtered.process_keyed(0.5, rumble(n));
+      plain.process_keyed(0.5, rumble(n));
+    }
+    assert!(filtered.gain_reduction() > plain.gain_reduction() + 10.0);
+  }
+
+  #[test]
+  fn stereo_link() {
+    let mut linked = Compressor::new(SAMPLERATE);
+    let mut unlinked = Compressor::new(SAMPLERATE);
+    unlinked.set_link(Link::Unlinked);
+    let (mut l, mut u) = ((0.0, 0.0), (0.0, 0.0));
+    for _ in 0..SAMPLERATE {
+      l = linked.process_stereo((1.0, 0.1));
+      u = unlinked.process_stereo((1.0, 0.1));
+    }
+    assert!((l.0 / l.1 - 10.0).abs() < 1e-3);
+    assert!((u.1 - 0.1).abs() < 1e-6);
+    assert!(u.0 < 1.0);
+  }
+
+  #[test]
+  fn auto_release_is_slower_when_sustained() {
+    let release = |auto: bool| {
+      let mut comp = Compressor::new(SAMPLERATE);
+      comp.set_threshold(-20.0);
+      comp.set_auto_release(auto);
+      settle(&mut comp, 1.0);
+      for _ in 0..2400 { comp.process(0.0); }
+      comp.gain_reduction()
+    };
+    assert!(release(true) < release(false));
+  }
+}
@@ -0,0 +1,300 @@
+use crate::analysis::level::{Detector, Peak};
+use crate::dsp::math::{db_to_volume, hz_to_radian, ms_to_coeff, ms_to_samples, volume_to_db};
+use crate::filter::{
+  biquad::{calc, twopole::Biquad, BiquadCoeffs, BiquadTrait},
+  Filter,
+};
+use core::f32::consts::FRAC_1_SQRT_2;
+
+/// Change of state reported by [`Gate::event`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum GateEvent {
+  Open,
+  Close,
+}
+
+/// Noise gate and downward expander.
+///
+/// The gate opens when the key signal rises above `open` dB, and closes when
+/// it has stayed below `close` dB for the hold time. Keeping `close` below
+/// `open` gives hysteresis, which stops the gate from chattering on signals
+/// hovering around the threshold. When closed, the signal is attenuated by
+/// at most `range` dB, either fully (gate) or by the expansion ratio.
+///
+/// The open state can drive an envelope directly from audio:
+/// ```
+/// use rust_dsp::{adsr::ADSREnvelope, dynamics::gate::Gate};
+///
+/// let mut gate = Gate::new(48000);
+/// let mut adsr = ADSREnvelope::new(48000);
+/// let input = 0.5;
+/// gate.process(input);
+/// let env = adsr.play(gate.is_open());
+/// ```
+pub struct Gate {
+  detector: Peak,
+  hpf: Option<Biquad>,
+  lpf: Option<Biquad>,
+  hpf_freq: Option<f32>,
+  lpf_freq: Option<f32>,
+
+  open: f32,
+  close: f32,
+  range: f32,
+  ratio: f32,
+  attack: f32,
+  release: f32,
+  hold: f32,
+  atk_coeff: f32,
+  rel_coeff: f32,
+  hold_samples: usize,
+
+  is_open: bool,
+  event: Option<GateEvent>,
+  counter: usize,
+  gain: f32,
+  samplerate: f32,
+}
+
+impl Gate {
+  pub fn new(samplerate: u32) -> Self {
+    let mut detector = Peak::new(samplerate);
+    detector.set_release(10.0);
+    let mut gate = Self {
+      detector,
+      hpf: None,
+      lpf: None,
+      hpf_freq: None,
+      lpf_freq: None,
+      open: -40.0,
+      close: -46.0,
+      range: 80.0,
+      ratio: f32::INFINITY,
+      attack: 1.0,
+      release: 100.0,
+      hold: 20.0,
+      atk_coeff: 0.0,
+      rel_coeff: 0.0,
+      hold_samples: 0,
+      is_open: false,
+      event: None,
+      counter: 0,
+      gain: -80.0,
+      samplerate: samplerate as f32,
+    };
+    gate.set_samplerate(samplerate);
+    gate
+  }
+
+  /// Gate `input` using itself as key.
+  #[inline]
+  pub fn process(&mut self, input: f32) -> f32 {
+    self.process_keyed(input, input)
+  }
+
+  /// Gate `input` using the level of `key`.
+  #[inline]
+  pub fn process_keyed(&mut self, input: f32, key: f32) -> f32 {
+    let mut key = key;
+    if let Some(hpf) = self.hpf.as_mut() { key = hpf.process(key); }
+    if let Some(lpf) = self.lpf.as_mut() { key = lpf.process(key); }
+    let level = volume_to_db(self.detector.process(key));
+
+    self.event = None;
+    if level >= self.open && !self.is_open {
+      self.is_open = true;
+      self.event = Some(GateEvent::Open);
+    }
+    if self.is_open {
+      if level >= self.close {
+        self.counter = self.hold_samples;
+      } else if self.counter > 0 {
+        self.counter -= 1;
+      } else {
+        self.is_open = false;
+        self.event = Some(GateEvent::Close);
+      }
+    }
+
+    let target = if self.is_open {
+      0.0
+    } else if self.ratio.is_infinite() {
+      -self.range
+    } else {
+      f32::max(-self.range, f32::min(0.0, (level - self.close) * (self.ratio - 1.0)))
+    };
+    let coeff = if target > self.gain { self.atk_coeff } else { self.rel_coeff };
+    self.gain = target + coeff * (self.gain - target);
+    input * db_to_volume(self.gain)
+  }
+
+  pub fn is_open(&self) -> bool {
+    self.is_open
+  }
+
+  /// State change caused by the latest processed sample.
+  pub fn event(&self) -> Option<GateEvent> {
+    self.event
+  }
+
+  /// `1.0` on the sample the gate opens, following the
+  /// [`TrigTrait`](crate::trig::TrigTrait) convention.
+  pub fn trigger(&self) -> f32 {
+    if self.event == Some(GateEvent::Open) { 1.0 } else { 0.0 }
+  }
+
+  /// Current gain in dB, `<= 0.0`.
+  pub fn gain(&self) -> f32 {
+    self.gain
+  }
+
+  /// Open and close thresholds in dB. `close` is limited to be at most `open`.
+  pub fn set_thresholds(&mut self, open: f32, close: f32) {
+    self.open = open;
+    self.close = f32::min(open, close);
+  }
+
+  /// Maximum attenuation in dB when closed.
+  pub fn set_range(&mut self, range: f32) { self.range = range.abs(); }
+
+  /// Expansion ratio `1:n` below the close threshold,
+  /// `f32::INFINITY` (default) makes a gate.
+  pub fn set_ratio(&mut self, ratio: f32) { self.ratio = f32::max(1.0, ratio); }
+
+  /// Attack (opening) time in milliseconds
+  pub fn set_attack(&mut self, ms: f32) {
+    self.attack = ms;
+    self.atk_coeff = ms_to_coeff(ms, self.samplerate);
+  }
+
+  /// Release (closing) time in milliseconds
+  pub fn set_release(&mut self, ms: f32) {
+    self.release = ms;
+    self.rel_coeff = ms_to_coeff(ms, self.samplerate);
+  }
+
+  /// Time in milliseconds the gate stays open after the key falls below
+  /// the close threshold.
+  pub fn set_hold(&mut self, ms: f32) {
+    self.hold = ms;
+    self.hold_samples = ms_to_samples(ms, self.samplerate);
+  }
+
+  /// Highpass the key signal at `freq` Hz, `None` disables the filter.
+  pub fn set_key_hpf(&mut self, freq: Option<f32>) {
+    self.hpf_freq = freq;
+    let coeffs = freq.map(|f| calc::hpf(hz_to_radian(f, self.samplerate), FRAC_1_SQRT_2));
+    update_filter(&mut self.hpf, coeffs);
+  }
+
+  /// Lowpass the key signal at `freq` Hz, `None` disables the filter.
+  pub fn set_key_lpf(&mut self, freq: Option<f32>) {
+    self.lpf_freq = freq;
+    let coeffs = freq.map(|f| calc::lpf(hz_to_radian(f, self.samplerate), FRAC_1_SQRT_2));
+    update_filter(&mut self.lpf, coeffs);
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.detector.set_samplerate(samplerate);
+    self.set_attack(self.attack);
+    self.set_release(self.release);
+    self.set_hold(self.hold);
+    self.set_key_hpf(self.hpf_freq);
+    self.set_key_lpf(self.lpf_freq);
+  }
+}
+
+/// Keeps the filter state when only the coefficients change.
+fn update_filter(filter: &mut Option<Biquad>, coeffs: Option<BiquadCoeffs>) {
+  match (coeffs, filter.as_mut()) {
+    (Some(c), Some(f)) => f.update(&c),
+    (Some(c), None) => *filter = Some(Biquad::new(c)),
+    (None, _) => *filter = None,
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::adsr::ADSREnvelope;
+
+  const SAMPLERATE: u32 = 48000;
+
+  #[test]
+  fn opens_and_closes() {
+    let mut gate = Gate::new(SAMPLERATE);
+    gate.set_thresholds(-20.0, -30.0);
+    gate.set_hold(0.0);
+    assert_eq!(0.0, gate.trigger());
+    gate.process(0.5);
+    assert_eq!(Some(GateEvent::Open), gate.event());
+    assert_eq!(1.0, gate.trigger());
+    gate.process(0.5);
+    assert_eq!(None, gate.event());
+    assert!(gate.is_open());
+    let closed = (0..SAMPLERATE).any(|_| { gate.process(0.0); gate.event() == Some(GateEvent::Close) });
+    assert!(closed);
+    for _ in 0..SAMPLERATE { gate.process(0.0); }
+    assert!((gate.gain() + 80.0).abs() < 0.1);
+  }
+
+  #[test]
+  fn hysteresis() {
+    let mut gate = Gate::new(SAMPLERATE);
+    gate.set_thresholds(-20.0, -30.0);
+    gate.set_hold(0.0);
+    // -25 dB does not open a closed gate
+    for _ in 0..4800 { gate.process(db_to_volume(-25.0)); }
+    assert!(!gate.is_open());
+    // but keeps an open gate open
+    gate.process(1.0);
+    for _ in 0..4800 { gate.process(db_to_volume(-25.0)); }
+    assert!(gate.is_open());
+  }
+
+  #[test]
+  fn hold_time() {
+    let mut gate = Gate::new(SAMPLERATE);
+    gate.set_hold(50.0);
+    gate.process(1.0);
+    // the peak detector needs to fall below the close threshold first
+    let mut samples = 0;
+    while gate.is_open() {
+      gate.process(0.0);
+      samples += 1;
+    }
+    assert!(samples >= 2400, "{samples}");
+  }
+
+  #[test]
+  fn expander_ratio() {
+    let mut gate = Gate::new(SAMPLERATE);
+    gate.set_thresholds(-20.0, -20.0);
+    gate.set_ratio(2.0);
+    gate.set_range(40.0);
+    let input = db_to_volume(-30.0);
+    let out = (0..SAMPLERATE).map(|_| gate.process(input)).last().unwrap();
+    // 10 dB below threshold expands to 20 dB below
+    assert!((volume_to_db(out) + 40.0).abs() < 0.1, "{}", volume_to_db(out));
+  }
+
+  #[test]
+  fn key_filter() {
+    let mut gate = Gate::new(SAMPLERATE);
+    gate.set_key_hpf(Some(2000.0));
+    // DC never opens the gate through the highpass
+    for _ in 0..4800 { gate.process(0.5); }
+    assert!(!gate.is_open());
+  }
+
+  #[test]
+  fn drives_adsr() {
+    let mut gate = Gate::new(SAMPLERATE);
+    let mut adsr = ADSREnvelope::new(SAMPLERATE);
+    adsr.set_attack_dur(0.01);
+    assert_eq!(0.0, adsr.play(gate.is_open()));
+    let env = (0..480).map(|_| { gate.process(0.5); adsr.play(gate.is_open()) }).last().unwrap();
+    assert!(env > 0.5);
+  }
+}
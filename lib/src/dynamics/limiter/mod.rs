@@ -0,0 +1,232 @@
+use crate::analysis::truepeak::TruePeak;
+use crate::delay::DelayLine;
+use crate::dsp::math::{db_to_volume, ms_to_coeff, ms_to_samples, next_pow2, volume_to_db};
+use alloc::{collections::VecDeque, vec, vec::Vec};
+use core::array;
+
+/// Lookahead brickwall limiter.
+///
+/// The input is delayed by the lookahead time, while the gain needed to keep
+/// each sample below the ceiling is held for the length of the lookahead and
+/// smoothed with a moving average of the same length. The gain therefore
+/// reaches its target before the peak arrives, and never lets a sample
+/// overshoot the ceiling. With true-peak detection enabled, inter-sample
+/// peaks are estimated by 4x oversampling.
+///
+/// All channels share the same gain.
+///
+/// ```
+/// use rust_dsp::dynamics::limiter::Limiter;
+///
+/// let mut limiter = Limiter::<2>::new(48000, 5.0);
+/// limiter.set_ceiling(-1.0);
+/// let [l, r] = limiter.process([0.9, -1.2]);
+/// ```
+pub struct Limiter<const CHANNELS: usize> {
+  delays: [DelayLine; CHANNELS],
+  detectors: [TruePeak; CHANNELS],
+  true_peak: bool,
+
+  /// Sliding minimum of the required gain, `(sample index, gain)`
+  hold: VecDeque<(usize, f32)>,
+  required: Vec<f32>,
+  average: Vec<f32>,
+  sum: f64,
+  index: usize,
+  envelope: f32,
+
+  lookahead: usize,
+  ceiling: f32,
+  release: f32,
+  rel_coeff: f32,
+  samplerate: f32,
+}
+
+impl<const CHANNELS: usize> Limiter<CHANNELS> {
+  /// `lookahead` in milliseconds, at least one sample.
+  pub fn new(samplerate: u32, lookahead: f32) -> Self {
+    let lookahead = usize::max(1, ms_to_samples(lookahead, samplerate as f32));
+    let latency = lookahead + TruePeak::LATENCY;
+    let size = next_pow2(latency + 1);
+    let mut limiter = Self {
+      delays: array::from_fn(|_| DelayLine::new(latency, size).unwrap()),
+      detectors: array::from_fn(|_| TruePeak::new()),
+      true_peak: true,
+      hold: VecDeque::with_capacity(lookahead + 2),
+      required: vec![1.0; lookahead + 1],
+      average: vec![1.0; lookahead],
+      sum: lookahead as f64,
+      index: 0,
+      envelope: 1.0,
+      lookahead,
+      ceiling: 1.0,
+      release: 0.0,
+      rel_coeff: 0.0,
+      samplerate: samplerate as f32,
+    };
+    limiter.set_release(50.0);
+    limiter
+  }
+
+  #[inline]
+  pub fn process(&mut self, input: [f32; CHANNELS]) -> [f32; CHANNELS] {
+    // The detectors report the peak `TruePeak::LATENCY` samples back, the
+    // delay line adds the lookahead on top of that.
+    let mut peak = 0.0f32;
+    for (x, tp) in input.iter().zip(self.detectors.iter_mut()) {
+      let true_peak = tp.process(*x);
+      peak = peak.max(if self.true_peak { true_peak } else { tp.delayed().abs() });
+    }
+    let mut delayed = [0.0; CHANNELS];
+    for ((d, x), line) in delayed.iter_mut().zip(input).zip(self.delays.iter_mut()) {
+      *d = line.read_and_write(x);
+    }
+
+    let required = if peak > self.ceiling { self.ceiling / peak } else { 1.0 };
+    let gain = self.gain(required);
+    delayed.map(|x| x * gain)
+  }
+
+  /// Convenience for a single channel limiter.
+  #[inline]
+  pub fn process_mono(&mut self, input: f32) -> f32 {
+    debug_assert!(CHANNELS == 1);
+    self.process([input; CHANNELS])[0]
+  }
+
+  #[inline]
+  fn gain(&mut self, required: f32) -> f32 {
+    let len = self.required.len();
+    self.required[self.index % len] = required;
+    // required gain of the sample leaving the delay line
+    let outgoing = self.required[(self.index + 1) % len];
+
+    // hold the minimum over the last `lookahead + 1` samples
+    while self.hold.back().is_some_and(|(_, g)| *g >= required) {
+      self.hold.pop_back();
+    }
+    self.hold.push_back((self.index, required));
+    while self.hold.front().is_some_and(|(i, _)| i + len <= self.index) {
+      self.hold.pop_front();
+    }
+    let held = self.hold.front().map_or(1.0, |(_, g)| *g);
+
+    // instant attack, exponential release
+    self.envelope = if held < self.envelope {
+      held
+    } else {
+      held + self.rel_coeff * (self.envelope - held)
+    };
+
+    // moving average over the lookahead
+    let slot = self.index % self.lookahead;
+    self.sum += (self.envelope - self.average[slot]) as f64;
+    self.average[slot] = self.envelope;
+    self.index += 1;
+    if slot == self.lookahead - 1 {
+      self.sum = self.average.iter().map(|g| *g as f64).sum();
+    }
+    let gain = (self.sum / self.lookahead as f64) as f32;
+    // The average is already below the outgoing requirement, this only
+    // guards against rounding errors.
+    gain.min(outgoing)
+  }
+
+  /// Samples of delay added by the limiter.
+  pub fn latency(&self) -> usize {
+    self.lookahead + TruePeak::LATENCY
+  }
+
+  /// Current gain reduction in dB, `<= 0.0`.
+  pub fn gain_reduction(&self) -> f32 {
+    volume_to_db((self.sum / self.lookahead as f64) as f32).min(0.0)
+  }
+
+  /// Ceiling in dB
+  pub fn set_ceiling(&mut self, ceiling: f32) {
+    self.ceiling = db_to_volume(ceiling);
+  }
+
+  /// Release time in milliseconds
+  pub fn set_release(&mut self, ms: f32) {
+    self.release = ms;
+    self.rel_coeff = ms_to_coeff(ms, self.samplerate);
+  }
+
+  /// Include inter-sample peaks in the detection, enabled by default.
+  pub fn set_true_peak(&mut self, true_peak: bool) {
+    self.true_peak = true_peak;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::noise::Prng;
+  use core::f32::consts::{FRAC_PI_4, TAU};
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn assert_below_ceiling(signal: impl Iterator<Item = f32>, ceiling_db: f32) {
+    let mut limiter = Limiter::<1>::new(SAMPLERATE, 5.0);
+    limiter.set_ceiling(ceiling_db);
+    let ceiling = db_to_volume(ceiling_db);
+    for (n, x) in signal.enumerate() {
+      let y = limiter.process_mono(x);
+      assert!(y.abs() <= ceiling, "sample {n}: {y} exceeds {ceiling}");
+    }
+  }
+
+  #[test]
+  fn loud_noise() {
+    let mut rng = Prng::new(1234);
+    assert_below_ceiling((0..SAMPLERATE).map(|_| rng.frand_bipolar() * 8.0), -1.0);
+  }
+
+  #[test]
+  fn impulses() {
+    assert_below_ceiling((0..SAMPLERATE).map(|n| if n % 997 == 0 { 20.0 } else { 0.0 }), -0.3);
+  }
+
+  #[test]
+  fn step_into_full_scale_square() {
+    let signal = (0..SAMPLERATE as usize).map(|n| match n {
+      n if n < 1000 => 0.0,
+      n if (n / 3) % 2 == 0 => 4.0,
+      _ => -4.0,
+    });
+    assert_below_ceiling(signal, 0.0);
+  }
+
+  #[test]
+  fn alternating_extremes() {
+    let signal = (0..SAMPLERATE).map(|n| if n % 2 == 0 { 1000.0 } else { -0.001 });
+    assert_below_ceiling(signal, -6.0);
+  }
+
+  #[test]
+  fn quiet_signal_is_only_delayed() {
+    let mut limiter = Limiter::<2>::new(SAMPLERATE, 2.0);
+    let latency = limiter.latency();
+    let input: Vec<f32> = (0..4800).map(|n| 0.5 * f32::sin(n as f32 * 0.01)).collect();
+    let output: Vec<[f32; 2]> = input.iter().map(|x| limiter.process([*x, -*x])).collect();
+    for (x, y) in input.iter().zip(&output[latency..]) {
+      assert_eq!([*x, -*x], *y);
+    }
+  }
+
+  #[test]
+  fn true_peak_ceiling() {
+    let mut limiter = Limiter::<1>::new(SAMPLERATE, 5.0);
+    limiter.set_ceiling(-3.0);
+    let mut meter = TruePeak::new();
+    for n in 0..SAMPLERATE {
+      // fs/4 sine with samples at 45 degrees, true peak is 1.0
+      let y = limiter.process_mono(f32::sin(TAU * 0.25 * n as f32 + FRAC_PI_4));
+      meter.process(y);
+      // skip the ringing of the meter's own step response
+      if n == SAMPLERATE / 2 { meter.reset_max(); }
+    }
+    assert!(volume_to_db(meter.max()) < -3.0 + 0.2, "{}", volume_to_db(meter.max()));
+  }
+}
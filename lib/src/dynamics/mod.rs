@@ -0,0 +1,75 @@
+pub mod compressor;
+pub mod limiter;
+pub mod gate;
+pub mod multiband;
+
+pub use compressor::Compressor;
+pub use limiter::Limiter;
+pub use gate::Gate;
+pub use multiband::Multiband;
+
+/// How the channels of a stereo processor share their gain.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Link {
+  /// Both channels are detected together and receive the same gain.
+  #[default] Linked,
+  /// Each channel has its own detector and gain.
+  Unlinked,
+}
+
+/// Static gain curve of a downward compressor, with a quadratic soft knee.
+///
+/// All levels are in dB. The knee width is centered around the threshold,
+/// `knee == 0.0` gives a hard knee.
+#[derive(Clone, Copy, Debug)]
+pub struct GainComputer {
+  pub threshold: f32,
+  pub ratio: f32,
+  pub knee: f32,
+}
+
+impl GainComputer {
+  pub fn new(threshold: f32, ratio: f32, knee: f32) -> Self {
+    Self { threshold, ratio, knee }
+  }
+
+  /// Gain in dB, `<= 0.0`, to apply on a signal at `level` dB.
+  #[inline]
+  pub fn gain(&self, level: f32) -> f32 {
+    let slope = 1.0 / self.ratio - 1.0;
+    let over = level - self.threshold;
+    if 2.0 * over <= -self.knee {
+      0.0
+    } else if 2.0 * over.abs() < self.knee {
+      let x = over + self.knee * 0.5;
+      slope * x * x / (2.0 * self.knee)
+    } else {
+      slope * over
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  #[test]
+  fn hard_knee() {
+    let gc = GainComputer::new(-20.0, 4.0, 0.0);
+    assert_eq!(0.0, gc.gain(-30.0));
+    assert_eq!(0.0, gc.gain(-20.0));
+    assert_eq!(-7.5, gc.gain(-10.0));
+  }
+
+  #[test]
+  fn soft_knee_is_continuous() {
+    let gc = GainComputer::new(-20.0, 4.0, 10.0);
+    let eps = 1e-3;
+    for edge in [-25.0, -15.0] {
+      assert!((gc.gain(edge - eps) - gc.gain(edge + eps)).abs() < 1e-2);
+    }
+    // halfway through the knee the curve is below the hard knee
+    assert!(gc.gain(-20.0) < 0.0);
+    assert_eq!(-7.5, gc.gain(-10.0));
+  }
+}
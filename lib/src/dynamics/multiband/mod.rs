@@ -0,0 +1,269 @@
+use super::Compressor;
+use crate::dsp::math::hz_to_radian;
+use crate::filter::{
+  biquad::{calc, fourpole::Biquad4, BiquadTrait},
+  Filter,
+};
+use alloc::vec::Vec;
+use core::f32::consts::FRAC_1_SQRT_2;
+
+/// Lowest frequency a crossover can be moved to, in Hz.
+const MIN_CROSSOVER: f32 = 1.0;
+
+/// Linkwitz-Riley 4th order crossover.
+///
+/// Each side is two cascaded Butterworth sections, which makes the lowpass
+/// and highpass outputs sum to an allpass with a flat magnitude response.
+#[derive(Clone, Copy)]
+pub struct Crossover {
+  lpf: Biquad4,
+  hpf: Biquad4,
+}
+
+impl Crossover {
+  pub fn new(freq: f32, samplerate: u32) -> Self {
+    let omega = hz_to_radian(freq, samplerate as f32);
+    Self {
+      lpf: Biquad4::new(calc::lpf(omega, FRAC_1_SQRT_2)),
+      hpf: Biquad4::new(calc::hpf(omega, FRAC_1_SQRT_2)),
+    }
+  }
+
+  /// Returns `(low, high)`
+  #[inline]
+  pub fn split(&mut self, sample: f32) -> (f32, f32) {
+    (self.lpf.process(sample), self.hpf.process(sample))
+  }
+
+  /// Phase response of the crossover without splitting, used to keep bands
+  /// that skip this crossover aligned with those that pass through it.
+  #[inline]
+  pub fn allpass(&mut self, sample: f32) -> f32 {
+    let (low, high) = self.split(sample);
+    low + high
+  }
+
+  pub fn set_frequency(&mut self, freq: f32, samplerate: u32) {
+    let omega = hz_to_radian(freq, samplerate as f32);
+    self.lpf.update(&calc::lpf(omega, FRAC_1_SQRT_2));
+    self.hpf.update(&calc::hpf(omega, FRAC_1_SQRT_2));
+  }
+}
+
+struct Band {
+  compressor: Compressor,
+  /// Allpasses of the crossovers above this band
+  compensation: Vec<Crossover>,
+  signal: f32,
+  solo: bool,
+  bypass: bool,
+}
+
+/// Multiband compressor with 3 to 5 bands.
+///
+/// The input is split by a chain of [`Crossover`]s, lowest frequency first,
+/// and each band gets its own [`Compressor`] with separate detector and gain
+/// computer. Bands are phase compensated, so with all bands bypassed the
+/// summed output has a flat magnitude response.
+///
+/// ```
+/// use rust_dsp::dynamics::multiband::Multiband;
+///
+/// let mut mb = Multiband::new(48000, &[200.0, 2000.0, 8000.0]).unwrap();
+/// mb.band(0).set_threshold(-24.0);
+/// mb.set_solo(1, true);
+/// let out = mb.process(0.5);
+/// ```
+pub struct Multiband {
+  crossovers: Vec<Crossover>,
+  frequencies: Vec<f32>,
+  bands: Vec<Band>,
+  samplerate: u32,
+}
+
+impl Multiband {
+  /// `frequencies` are the 2 to 4 crossover points in ascending order,
+  /// giving 3 to 5 bands.
+  pub fn new(samplerate: u32, frequencies: &[f32]) -> Result<Self, &'static str> {
+    if !(2..=4).contains(&frequencies.len()) {
+      return Err("Multiband needs 2 to 4 crossover frequencies");
+    }
+    if frequencies.windows(2).any(|f| f[0] >= f[1]) {
+      return Err("Crossover frequencies need to be ascending");
+    }
+    let crossovers: Vec<Crossover> = frequencies.iter().map(|f| Crossover::new(*f, samplerate)).collect();
+    let bands = (0..=frequencies.len()).map(|i| Band {
+      compressor: Compressor::new(samplerate),
+      compensation: crossovers.iter().skip(i + 1).copied().collect(),
+      signal: 0.0,
+      solo: false,
+      bypass: false,
+    }).collect();
+    Ok(Self { crossovers, frequencies: frequencies.to_vec(), bands, samplerate })
+  }
+
+  #[inline]
+  pub fn process(&mut self, sample: f32) -> f32 {
+    let last = self.bands.len() - 1;
+    let mut rest = sample;
+    for (band, xover) in self.bands.iter_mut().zip(self.crossovers.iter_mut()) {
+      let (low, high) = xover.split(rest);
+      band.signal = low;
+      rest = high;
+    }
+    self.bands[last].signal = rest;
+
+    let solo = self.bands.iter().any(|b| b.solo);
+    let mut out = 0.0;
+    for band in self.bands.iter_mut() {
+      let mut sig = band.signal;
+      for ap in band.compensation.iter_mut() {
+        sig = ap.allpass(sig);
+      }
+      if !band.bypass {
+        sig = band.compressor.process(sig);
+      }
+      if !solo || band.solo {
+        out += sig;
+      }
+    }
+    out
+  }
+
+  /// Compressor of band `index`, lowest band is `0`.
+  pub fn band(&mut self, index: usize) -> &mut Compressor {
+    &mut self.bands[index].compressor
+  }
+
+  pub fn bands(&self) -> usize {
+    self.bands.len()
+  }
+
+  /// Gain reduction of band `index` in dB
+  pub fn gain_reduction(&self, index: usize) -> f32 {
+    self.bands[index].compressor.gain_reduction()
+  }
+
+  /// Only soloed bands are summed to the output, when any band is soloed.
+  pub fn set_solo(&mut self, index: usize, solo: bool) {
+    self.bands[index].solo = solo;
+  }
+
+  /// Pass the band through without compression.
+  pub fn set_bypass(&mut self, index: usize, bypass: bool) {
+    self.bands[index].bypass = bypass;
+  }
+
+  /// Move crossover `index`, kept strictly between its neighbours and at
+  /// least 1 Hz so the crossovers stay ascending. Ignored when there is no
+  /// room between the neighbours.
+  pub fn set_crossover(&mut self, index: usize, freq: f32) {
+    let min = if index == 0 { MIN_CROSSOVER } else { self.frequencies[index - 1].next_up() };
+    let max = self.frequencies.get(index + 1).copied().unwrap_or(self.samplerate as f32 * 0.5).next_down();
+    if min > max {
+      return;
+    }
+    let freq = freq.clamp(min, max);
+    self.frequencies[index] = freq;
+    self.crossovers[index].set_frequency(freq, self.samplerate);
+    // bands below the crossover compensate for it
+    for (i, band) in self.bands.iter_mut().enumerate().take(index) {
+      band.compensation[index - i - 1].set_frequency(freq, self.samplerate);
+    }
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate;
+    for (i, freq) in self.frequencies.clone().into_iter().enumerate() {
+      self.set_crossover(i, freq);
+    }
+    self.bands.iter_mut().for_each(|b| b.compressor.set_samplerate(samplerate));
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dsp::math::volume_to_db;
+  use core::f32::consts::TAU;
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn rms_db(mb: &mut Multiband, freq: f32, amp: f32) -> f32 {
+    let mut sum = 0.0;
+    let len = SAMPLERATE as usize / 2;
+    for n in 0..SAMPLERATE as usize {
+      let y = mb.process(amp * f32::sin(TAU * freq * n as f32 / SAMPLERATE as f32));
+      // skip the filters settling
+      if n >= len { sum += y * y; }
+    }
+    volume_to_db(f32::sqrt(sum / len as f32))
+  }
+
+  fn bypassed(frequencies: &[f32]) -> Multiband {
+    let mut mb = Multiband::new(SAMPLERATE, frequencies).unwrap();
+    (0..mb.bands()).for_each(|i| mb.set_bypass(i, true));
+    mb
+  }
+
+  #[test]
+  fn invalid_crossovers() {
+    assert!(Multiband::new(SAMPLERATE, &[1000.0]).is_err());
+    assert!(Multiband::new(SAMPLERATE, &[100.0, 200.0, 300.0, 400.0, 500.0]).is_err());
+    assert!(Multiband::new(SAMPLERATE, &[2000.0, 200.0]).is_err());
+  }
+
+  #[test]
+  fn flat_sum() {
+    let sine_db = volume_to_db(0.5 * core::f32::consts::FRAC_1_SQRT_2);
+    for frequencies in [&[250.0, 2500.0][..], &[100.0, 500.0, 2000.0, 8000.0][..]] {
+      for freq in [50.0, 300.0, 1000.0, 3000.0, 12000.0] {
+        let level = rms_db(&mut bypassed(frequencies), freq, 0.5);
+        assert!((level - sine_db).abs() < 0.1, "{freq} Hz: {level} dB");
+      }
+    }
+  }
+
+  #[test]
+  fn solo() {
+    let mut mb = bypassed(&[250.0, 2500.0]);
+    mb.set_solo(0, true);
+    assert!(rms_db(&mut mb, 8000.0, 0.5) < -60.0);
+  }
+
+  #[test]
+  fn bands_compress_independently() {
+    let mut mb = Multiband::new(SAMPLERATE, &[250.0, 2500.0]).unwrap();
+    for i in 0..3 {
+      mb.band(i).set_threshold(-20.0);
+      mb.band(i).set_ratio(10.0);
+    }
+    rms_db(&mut mb, 80.0, 1.0);
+    assert!(mb.gain_reduction(0) < -10.0);
+    assert!(mb.gain_reduction(2) > -0.1);
+  }
+
+  #[test]
+  fn move_crossover() {
+    let mut mb = bypassed(&[250.0, 2500.0, 6000.0]);
+    mb.set_crossover(1, 1000.0);
+    mb.set_crossover(2, 3000.0);
+    assert_eq!(vec![250.0, 1000.0, 3000.0], mb.frequencies);
+    let level = rms_db(&mut mb, 700.0, 0.5);
+    assert!((level - volume_to_db(0.5 * core::f32::consts::FRAC_1_SQRT_2)).abs() < 0.1);
+  }
+
+  #[test]
+  fn crossovers_stay_ascending() {
+    let mut mb = bypassed(&[250.0, 2500.0, 6000.0]);
+    mb.set_crossover(0, 0.0);
+    mb.set_crossover(2, 100.0);
+    mb.set_crossover(1, 30000.0);
+    assert_eq!(MIN_CROSSOVER, mb.frequencies[0]);
+    assert_eq!(2500.0, mb.frequencies[1]);
+    assert_eq!(2500.0f32.next_up(), mb.frequencies[2]);
+    assert!(mb.frequencies.windows(2).all(|f| f[0] < f[1]), "{:?}", mb.frequencies);
+    assert!(Multiband::new(SAMPLERATE, &mb.frequencies).is_ok());
+    assert!(mb.process(0.5).is_finite());
+  }
+}
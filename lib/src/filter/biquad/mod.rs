@@ -90,6 +90,11 @@ impl BiquadKind for Notch {
 }
 
 impl BiquadCoeffs {
+  /// Coefficients from an external design, normalized so that `a0 == 1.0`.
+  pub fn new(b: [f32; 3], a: [f32; 2]) -> Self {
+    Self{a1: a[0], a2: a[1], b0: b[0], b1: b[1], b2: b[2]}
+  }
+
 #[inline]
   pub fn lpf(omega: f32, q: f32) -> Self {
     let alpha = omega.sin() / (2.0 * q);
@@ -98,7 +103,7 @@ impl BiquadCoeffs {
     let a2 = (1.0 - alpha) / a0;
 
     let b1 = (1.0 - omega.cos()) / a0;
-    let b0 = b1 / 2.0 / a0;
+    let b0 = b1 / 2.0;
     let b2 = b0;
     Self{a1, a2, b0, b1, b2}
   }
@@ -122,7 +127,7 @@ impl BiquadCoeffs {
     let alpha = omega.sin() / (2.0 * q);
     let a0 = 1.0 + alpha;
     let a1 = -2.0 * omega.cos() / a0;
-    let a2 = 1.0 - alpha / a0;
+    let a2 = (1.0 - alpha) / a0;
 
     let b0 = (1.0 + omega.cos()) / 2.0 / a0;
     let b1 = -(b0 * 2.0);
@@ -192,7 +197,7 @@ pub mod calc {
       let a2 = (1.0 - alpha) / a0;
 
       let b1 = (1.0 - omega.cos()) / a0;
-      let b0 = b1 / 2.0 / a0;
+      let b0 = b1 / 2.0;
       let b2 = b0;
       BiquadCoeffs{a1, a2, b0, b1, b2}
   }
@@ -216,7 +221,7 @@ pub mod calc {
     let alpha = omega.sin() / (2.0 * q);
     let a0 = 1.0 + alpha;
     let a1 = -2.0 * omega.cos() / a0;
-    let a2 = 1.0 - alpha / a0;
+    let a2 = (1.0 - alpha) / a0;
 
     let b0 = (1.0 + omega.cos()) / 2.0 / a0;
     let b1 = -(b0 * 2.0);
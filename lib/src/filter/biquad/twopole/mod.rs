@@ -1,10 +1,12 @@
-use super::{BiquadCoeffs, BiquadTrait};
-use crate::filter::Filter;
+use super::{BiquadCoeffs, BiquadKind, BiquadSettings, BiquadTrait};
+use crate::filter::{Filter, ParamSmoother, Smoothing, smoothing_length};
 
 #[derive(Clone, Copy)]
 pub struct Biquad {
   x1: f32, x2: f32, y1: f32, y2: f32,
   bq: BiquadCoeffs,
+  smoothing: usize,
+  smoother: Smoothing<BiquadSettings, BiquadCoeffs>,
 }
 
 impl Biquad {
@@ -12,12 +14,43 @@ impl Biquad {
     Self {
       x1: 0.0, x2: 0.0, y1: 0.0, y2: 0.0, 
       bq: settings,
+      smoothing: 0,
+      smoother: None,
+    }
+  }
+
+  /// Time in seconds of the ramps made by [`Biquad::update_smoothed`],
+  /// `0.0` (default) is immediate.
+  pub fn set_smoothing(&mut self, seconds: f32, samplerate: u32) {
+    self.smoothing = smoothing_length(seconds, samplerate);
+    self.smoother = None;
+  }
+
+  /// Ramp to new settings of filter kind `K` over the smoothing length,
+  /// instead of jumping like [`BiquadTrait::update`]. The first call after
+  /// creating the filter or an `update` has nothing to ramp from, and sets
+  /// the settings directly.
+  pub fn update_smoothed<K: BiquadKind<Settings = BiquadSettings>>(&mut self, settings: &BiquadSettings) {
+    match self.smoother.as_mut() {
+      Some((smoother, calc)) => {
+        smoother.set_target(settings.omega, settings.q, settings.gain);
+        *calc = K::calc;
+      }
+      None => {
+        let smoother = ParamSmoother::new(settings.omega, settings.q, settings.gain, self.smoothing);
+        self.smoother = Some((smoother, K::calc));
+        self.bq = K::calc(settings);
+      }
     }
   }
 }
 
 impl Filter for Biquad {
   fn process(&mut self, sample: f32) -> f32 {
+    if let Some((smoother, calc)) = self.smoother.as_mut() && smoother.is_smoothing() {
+      let (omega, q, gain) = smoother.tick();
+      self.bq = calc(&BiquadSettings { omega, q, gain });
+    }
     let output = {
         self.bq.b0 * sample 
       + self.bq.b1 * self.x1 
@@ -36,6 +69,31 @@ impl Filter for Biquad {
 
 impl BiquadTrait for Biquad {
   fn update(&mut self, settings: &BiquadCoeffs) {
+      self.smoother = None;
       self.bq = *settings;
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::filter::Lpf;
+  use core::f32::consts::TAU;
+
+  fn settings(freq: f32) -> BiquadSettings {
+    BiquadSettings { omega: TAU * freq / 48000.0, q: 0.707, gain: 0.0 }
+  }
+
+  #[test]
+  fn smoothed_update_ramps_coefficients() {
+    let mut bq = Biquad::new(BiquadCoeffs::lpf(TAU * 100.0 / 48000.0, 0.707));
+    bq.set_smoothing(64.0 / 48000.0, 48000);
+    bq.update_smoothed::<Lpf>(&settings(100.0));
+    bq.update_smoothed::<Lpf>(&settings(10000.0));
+    bq.process(0.0);
+    let target = BiquadCoeffs::lpf(TAU * 10000.0 / 48000.0, 0.707);
+    assert!(bq.bq.b0 < target.b0 * 0.5);
+    for _ in 0..64 { bq.process(0.0); }
+    assert_eq!(target.b0, bq.bq.b0);
+  }
+}
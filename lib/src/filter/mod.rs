@@ -6,7 +6,7 @@ pub mod svf;
 
 #[cfg(not(feature="std"))]
 use alloc::{vec, vec::Vec};
-use crate::interpolation::Interpolation;
+use crate::{interpolation::Interpolation, smooth::{self, Multiplicative, SmoothedValue}};
 
 pub struct Lpf;
 pub struct Hpf;
@@ -20,6 +20,51 @@ pub trait Filter {
   fn process(&mut self, sample: f32) -> f32;
 }
 
+/// Ramps for the omega, q and gain settings of a filter, shared by the
+/// smoothed updates of [`svf::SVFilter`] and [`biquad::twopole::Biquad`].
+#[derive(Clone, Copy)]
+pub(crate) struct ParamSmoother {
+  omega: SmoothedValue<Multiplicative>,
+  q: SmoothedValue<Multiplicative>,
+  gain: SmoothedValue<smooth::Linear>,
+}
+
+impl ParamSmoother {
+  pub(crate) fn new(omega: f32, q: f32, gain: f32, samples: usize) -> Self {
+    let mut omega = SmoothedValue::new(0, omega);
+    let mut q = SmoothedValue::new(0, q);
+    let mut gain = SmoothedValue::new(0, gain);
+    omega.set_length(samples);
+    q.set_length(samples);
+    gain.set_length(samples);
+    Self { omega, q, gain }
+  }
+
+  pub(crate) fn set_target(&mut self, omega: f32, q: f32, gain: f32) {
+    self.omega.set_target(omega);
+    self.q.set_target(q);
+    self.gain.set_target(gain);
+  }
+
+  pub(crate) fn is_smoothing(&self) -> bool {
+    self.omega.is_smoothing() || self.q.is_smoothing() || self.gain.is_smoothing()
+  }
+
+  /// Next omega, q and gain
+  #[inline]
+  pub(crate) fn tick(&mut self) -> (f32, f32, f32) {
+    (self.omega.tick(), self.q.tick(), self.gain.tick())
+  }
+}
+
+/// Ramps of a filter with the function calculating its coefficients
+pub(crate) type Smoothing<S, C> = Option<(ParamSmoother, fn(&S) -> C)>;
+
+/// Length in samples of a smoothing time
+pub(crate) fn smoothing_length(seconds: f32, samplerate: u32) -> usize {
+  (f32::max(0.0, seconds) * samplerate as f32).round() as usize
+}
+
 pub trait FilterKind {
   type Settings;
   type Coefficients;
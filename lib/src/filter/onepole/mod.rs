@@ -1,10 +1,12 @@
 use super::Filter;
+use crate::smooth::{Multiplicative, SmoothedValue};
 use core::f32::consts::TAU;
 
 #[derive(Default)]
 pub struct Onepole {
   prev: f32,
   coeff: f32,
+  cutoff: SmoothedValue<Multiplicative>,
   samplerate: u32
 }
 
@@ -12,17 +14,35 @@ impl Onepole {
   pub fn new(samplerate: u32) -> Self {
     Self{
       samplerate,
+      cutoff: SmoothedValue::new(samplerate, 0.0),
       ..Default::default()
     }
   }
   /// `0.0 < coeff < 1.0 == lowpass`
   /// `|self.coeff| < 1 for stability`
+  ///
+  /// Cancels any cutoff change in progress.
   pub fn set_coeff(&mut self, coeff: f32) {
+    self.cutoff.set_value(self.cutoff.target());
     self.coeff = coeff;
   }
 
+  /// Cutoff in Hz, reached over the smoothing time.
   pub fn set_cutoff(&mut self, freq: f32) {
-    self.set_coeff((-TAU * freq / self.samplerate as f32).exp());
+    self.cutoff.set_target(freq);
+    if !self.cutoff.is_smoothing() {
+      self.coeff = self.calc_coeff(freq);
+    }
+  }
+
+  /// Time in seconds for cutoff changes, `0.0` (default) is immediate.
+  pub fn set_smoothing(&mut self, seconds: f32) {
+    self.cutoff.set_time(seconds);
+  }
+
+  #[inline]
+  fn calc_coeff(&self, freq: f32) -> f32 {
+    (-TAU * freq / self.samplerate as f32).exp()
   }
 }
 
@@ -30,6 +50,10 @@ impl Filter for Onepole {
   /// `0.0 < coeff < 1.0 == lowpass`
   /// `|self.coeff| < 1 for stability`
   fn process(&mut self, sample: f32) -> f32 {
+    if self.cutoff.is_smoothing() {
+      let freq = self.cutoff.tick();
+      self.coeff = self.calc_coeff(freq);
+    }
     self.prev = ((1.0 - self.coeff) * sample) + (self.coeff * self.prev);
     self.prev
   }
@@ -63,6 +87,22 @@ impl Filter for LagFilter {
   }
 }
 
+#[cfg(test)]
+mod tests {
+  use super::*;
 
-
-
+  #[test]
+  fn cutoff_is_smoothed() {
+    let mut instant = Onepole::new(48000);
+    instant.set_cutoff(100.0);
+    let mut smoothed = Onepole::new(48000);
+    smoothed.set_smoothing(0.01);
+    smoothed.set_cutoff(100.0);
+    smoothed.set_cutoff(10000.0);
+    instant.set_cutoff(10000.0);
+    // still close to the old cutoff on the first sample
+    assert!(smoothed.process(1.0) < instant.process(1.0) * 0.1);
+    for _ in 0..480 { smoothed.process(1.0); instant.process(1.0); }
+    assert_eq!(instant.coeff, smoothed.coeff);
+  }
+}
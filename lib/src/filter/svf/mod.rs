@@ -1,4 +1,4 @@
-use super::Filter;
+use super::{Filter, ParamSmoother, Smoothing, smoothing_length};
 use super::{Lpf, Bpf, Hpf, Notch};
 use core::marker::PhantomData;
 
@@ -74,6 +74,8 @@ pub struct SVFilter<T: SVFKind> {
   ic1eq: f32,
   ic2eq: f32,
   c: SVFCoeffs,
+  smoothing: usize,
+  smoother: Smoothing<SVFSettings, SVFCoeffs>,
   _marker: PhantomData<T>
 
 }
@@ -84,6 +86,8 @@ impl<T: SVFKind> SVFilter<T> {
       ic1eq: 0.0,
       ic2eq: 0.0,
       c: T::calc(&settings),
+      smoothing: 0,
+      smoother: None,
       _marker: PhantomData
     }
   }
@@ -93,8 +97,35 @@ impl<T: SVFKind> SVFilter<T> {
   }
 }
 
+impl<T: SVFKind<Settings = SVFSettings>> SVFilter<T> {
+  /// Time in seconds of the ramps made by [`SVFilter::update_smoothed`],
+  /// `0.0` (default) is immediate.
+  pub fn set_smoothing(&mut self, seconds: f32, samplerate: u32) {
+    self.smoothing = smoothing_length(seconds, samplerate);
+    self.smoother = None;
+  }
+
+  /// Ramp to new settings over the smoothing length, instead of jumping
+  /// like [`SVFTrait::update`]. The first call after creating the filter or
+  /// an `update` has nothing to ramp from, and sets the settings directly.
+  pub fn update_smoothed(&mut self, settings: &SVFSettings) {
+    match self.smoother.as_mut() {
+      Some((smoother, _)) => smoother.set_target(settings.omega, settings.q, settings.gain),
+      None => {
+        let smoother = ParamSmoother::new(settings.omega, settings.q, settings.gain, self.smoothing);
+        self.smoother = Some((smoother, T::calc));
+        self.c = T::calc(settings);
+      }
+    }
+  }
+}
+
 impl<T: SVFKind> Filter for SVFilter<T> {
   fn process(&mut self, sample: f32) -> f32 {
+    if let Some((smoother, calc)) = self.smoother.as_mut() && smoother.is_smoothing() {
+      let (omega, q, gain) = smoother.tick();
+      self.c = calc(&SVFSettings { omega, q, gain });
+    }
     // v0 is sample
     //
     // v3 = v0 - ic2eq
@@ -116,6 +147,7 @@ impl<T: SVFKind> Filter for SVFilter<T> {
 
 impl<T: SVFKind> SVFTrait<T> for SVFilter<T> {
   fn update(&mut self, settings: &T::Settings) {
+      self.smoother = None;
       self.c = T::calc(settings);
   }
 }
@@ -216,3 +248,26 @@ impl SVFCoeffs {
     Self{k, a1, a2, a3, m0, m1, m2}
   }
 }
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use core::f32::consts::TAU;
+
+  fn settings(freq: f32) -> SVFSettings {
+    SVFSettings { omega: TAU * freq / 48000.0, q: 0.707, gain: 0.0 }
+  }
+
+  #[test]
+  fn smoothed_update_ramps_coefficients() {
+    let mut svf = SVFilter::<Lpf>::new(settings(100.0));
+    svf.set_smoothing(64.0 / 48000.0, 48000);
+    svf.update_smoothed(&settings(100.0));
+    svf.update_smoothed(&settings(10000.0));
+    svf.process(0.0);
+    let target = SVFCoeffs::lpf(TAU * 10000.0 / 48000.0, 0.707);
+    assert!(svf.c.a2 < target.a2 * 0.5);
+    for _ in 0..64 { svf.process(0.0); }
+    assert_eq!(target.a2, svf.c.a2);
+  }
+}
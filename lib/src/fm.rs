@@ -0,0 +1,347 @@
+use core::{f32::consts::TAU, marker::PhantomData};
+
+use crate::{
+  adsr::ADSREnvelope,
+  interpolation::{Interpolation, Linear},
+  voice::Voice,
+};
+
+/// Preset routings of the modulation matrix. Operator `0` is always a
+/// carrier.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Algorithm {
+  /// Each operator modulates the one below it, `N-1 -> ... -> 1 -> 0`
+  Stack,
+  /// All operators are carriers, additive synthesis
+  Parallel,
+  /// Odd operators modulate the even operator below them, which are carriers
+  Pairs,
+  /// All other operators modulate operator `0`
+  Branch,
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Operator<'a> {
+  table: &'a [f32],
+  ratio: f32,
+  fixed: Option<f32>,
+  level: f32,
+  envelope: ADSREnvelope,
+  phase: f32,
+  /// Last two outputs, averaged for feedback
+  out: f32,
+  prev: f32,
+}
+
+/// Phase modulation voice with `OPS` operators.
+///
+/// Operators read any waveshape table, run at a ratio of the note frequency
+/// or at a fixed frequency, and are shaped by their own [`ADSREnvelope`].
+/// The modulation matrix holds the modulation index in radians from every
+/// operator to every other, the diagonal is feedback. Operators are
+/// computed from the highest to the lowest, modulation from a lower
+/// operator is delayed by a sample.
+///
+/// Implements [`Voice`], to be played by a
+/// [`VoiceManager`](crate::voice::VoiceManager).
+///
+/// ```
+/// use rust_dsp::{fm::{Algorithm, FmVoice}, voice::{Voice, VoiceManager}, waveshape::traits::Waveshape};
+///
+/// let sine = [0.0; 512].sine();
+/// let voices = core::array::from_fn(|_| {
+///   let mut fm = FmVoice::<4>::new(48000, &sine);
+///   fm.set_algorithm(Algorithm::Stack);
+///   fm.set_ratio(1, 2.0);
+///   fm.set_modulation(1, 0, 2.5);
+///   fm.set_feedback(3, 0.4);
+///   fm.envelope_mut(1).set_decay_dur(0.3);
+///   fm
+/// });
+/// let mut poly = VoiceManager::<_, 8>::new(voices);
+/// poly.note_on(60, 1.0);
+/// let out = poly.render();
+/// ```
+pub struct FmVoice<'a, const OPS: usize, T: Interpolation = Linear> {
+  operators: [Operator<'a>; OPS],
+  /// Modulation index from `[source]` into `[destination]`
+  matrix: [[f32; OPS]; OPS],
+  output: [f32; OPS],
+  frequency: f32,
+  velocity: f32,
+  gate: bool,
+  key_sync: bool,
+  sr_recip: f32,
+  _interpolation: PhantomData<T>,
+}
+
+impl<'a, const OPS: usize, T: Interpolation> FmVoice<'a, OPS, T> {
+  /// All operators read `table` at ratio `1.0`, routed as an
+  /// [`Algorithm::Stack`].
+  pub fn new(samplerate: u32, table: &'a [f32]) -> Self {
+    let operator = Operator {
+      table,
+      ratio: 1.0,
+      fixed: None,
+      level: 1.0,
+      envelope: ADSREnvelope::new(samplerate),
+      phase: 0.0,
+      out: 0.0,
+      prev: 0.0,
+    };
+    let mut fm = Self {
+      operators: [operator; OPS],
+      matrix: [[0.0; OPS]; OPS],
+      output: [0.0; OPS],
+      frequency: 0.0,
+      velocity: 0.0,
+      gate: false,
+      key_sync: true,
+      sr_recip: 1.0 / samplerate as f32,
+      _interpolation: PhantomData,
+    };
+    fm.set_algorithm(Algorithm::Stack);
+    fm
+  }
+
+  #[inline]
+  fn process(&mut self) -> f32 {
+    let mut sig = 0.0;
+    for dst in (0..OPS).rev() {
+      let mut modulation = 0.0;
+      for (src, op) in self.operators.iter().enumerate() {
+        let amount = self.matrix[src][dst];
+        if amount == 0.0 { continue; }
+        // higher operators are computed for this sample, lower ones still
+        // hold the previous sample
+        let input = if src == dst { 0.5 * (op.out + op.prev) } else { op.out };
+        modulation += amount * input;
+      }
+
+      let op = &mut self.operators[dst];
+      let freq = op.fixed.unwrap_or(self.frequency * op.ratio);
+      let env = op.envelope.play(self.gate);
+      let len = op.table.len();
+      let phase = (op.phase + modulation / TAU).rem_euclid(1.0);
+      let out = if len == 0 { 0.0 } else { T::interpolate(phase * len as f32, op.table, len) };
+      op.prev = op.out;
+      op.out = out * env * op.level;
+      op.phase = (op.phase + freq * self.sr_recip).rem_euclid(1.0);
+      sig += op.out * self.output[dst];
+    }
+    sig * self.velocity
+  }
+
+  /// Route the matrix as `algorithm`, with unit modulation indices and
+  /// carriers sharing the output equally. Feedback is kept.
+  pub fn set_algorithm(&mut self, algorithm: Algorithm) {
+    for (src, row) in self.matrix.iter_mut().enumerate() {
+      for (dst, amount) in row.iter_mut().enumerate() {
+        if src == dst { continue; }
+        let routed = match algorithm {
+          Algorithm::Stack => src == dst + 1,
+          Algorithm::Parallel => false,
+          Algorithm::Pairs => src % 2 == 1 && dst == src - 1,
+          Algorithm::Branch => dst == 0,
+        };
+        *amount = if routed { 1.0 } else { 0.0 };
+      }
+    }
+    let carrier = |op: usize| match algorithm {
+      Algorithm::Stack | Algorithm::Branch => op == 0,
+      Algorithm::Parallel => true,
+      Algorithm::Pairs => op.is_multiple_of(2),
+    };
+    let carriers = (0..OPS).filter(|op| carrier(*op)).count().max(1);
+    for (op, level) in self.output.iter_mut().enumerate() {
+      *level = if carrier(op) { 1.0 / carriers as f32 } else { 0.0 };
+    }
+  }
+
+  /// Modulation index in radians from operator `source` into
+  /// `destination`.
+  pub fn set_modulation(&mut self, source: usize, destination: usize, index: f32) {
+    if source < OPS && destination < OPS {
+      self.matrix[source][destination] = index;
+    }
+  }
+
+  /// Self modulation of `op` in radians
+  pub fn set_feedback(&mut self, op: usize, amount: f32) {
+    self.set_modulation(op, op, amount);
+  }
+
+  /// Level of `op` in the output mix, `0.0` for pure modulators.
+  pub fn set_output(&mut self, op: usize, level: f32) {
+    if let Some(l) = self.output.get_mut(op) { *l = level; }
+  }
+
+  /// Frequency of `op` relative to the note.
+  pub fn set_ratio(&mut self, op: usize, ratio: f32) {
+    if let Some(o) = self.operators.get_mut(op) { o.ratio = ratio; }
+  }
+
+  /// Run `op` at a fixed frequency in Hz regardless of the note, `None`
+  /// to follow the ratio.
+  pub fn set_fixed(&mut self, op: usize, frequency: Option<f32>) {
+    if let Some(o) = self.operators.get_mut(op) { o.fixed = frequency; }
+  }
+
+  /// Output level of `op`, scaling both its modulation and its output.
+  pub fn set_level(&mut self, op: usize, level: f32) {
+    if let Some(o) = self.operators.get_mut(op) { o.level = level; }
+  }
+
+  /// Waveform of `op`, one cycle of any length.
+  pub fn set_table(&mut self, op: usize, table: &'a [f32]) {
+    if let Some(o) = self.operators.get_mut(op) { o.table = table; }
+  }
+
+  /// Envelope of `op`, panics if `op` is out of range.
+  pub fn envelope_mut(&mut self, op: usize) -> &mut ADSREnvelope {
+    &mut self.operators[op].envelope
+  }
+
+  /// Restart the operator phases on every note, enabled by default.
+  pub fn set_key_sync(&mut self, key_sync: bool) {
+    self.key_sync = key_sync;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.sr_recip = 1.0 / samplerate as f32;
+    self.operators.iter_mut().for_each(|o| o.envelope.set_samplerate(samplerate));
+  }
+}
+
+impl<const OPS: usize, T: Interpolation> Voice for FmVoice<'_, OPS, T> {
+  fn note_on(&mut self, _note: u8, velocity: f32, frequency: f32) {
+    self.frequency = frequency;
+    self.velocity = velocity;
+    self.gate = true;
+    for op in self.operators.iter_mut() {
+      if self.key_sync {
+        op.phase = 0.0;
+      }
+      op.envelope.trig();
+    }
+  }
+
+  fn legato(&mut self, _note: u8, frequency: f32) {
+    self.frequency = frequency;
+  }
+
+  fn note_off(&mut self) {
+    self.gate = false;
+  }
+
+  fn is_active(&self) -> bool {
+    self.gate || self.operators.iter().zip(&self.output).any(|(op, out)| *out != 0.0 && op.envelope.is_playing())
+  }
+
+  #[inline]
+  fn render(&mut self) -> f32 {
+    self.process()
+  }
+
+  fn level(&self) -> f32 {
+    self.operators.iter().zip(&self.output).map(|(op, out)| (op.out * out).abs()).sum::<f32>() * self.velocity
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::waveshape::traits::Waveshape;
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn sine() -> [f32; 1024] {
+    [0.0; 1024].sine()
+  }
+
+  fn quick(fm: &mut FmVoice<'_, 2>) {
+    for op in 0..2 {
+      let env = fm.envelope_mut(op);
+      env.set_attack_dur(0.001);
+      env.set_decay_dur(0.001);
+      env.set_sustain_val(1.0);
+      env.set_attack_cur(1.0);
+      env.set_release_dur(0.01);
+    }
+  }
+
+  /// Zero crossings over a second
+  fn crossings(fm: &mut FmVoice<'_, 2>) -> usize {
+    let out: Vec<f32> = (0..SAMPLERATE).map(|_| fm.render()).collect();
+    out[480..].windows(2).filter(|w| w[0] <= 0.0 && w[1] > 0.0).count()
+  }
+
+  #[test]
+  fn carrier_without_modulation_is_sine() {
+    let table = sine();
+    let mut fm = FmVoice::<2>::new(SAMPLERATE, &table);
+    quick(&mut fm);
+    fm.set_modulation(1, 0, 0.0);
+    fm.note_on(69, 1.0, 100.0);
+    let n = crossings(&mut fm);
+    assert!((98..=100).contains(&n), "{n}");
+  }
+
+  #[test]
+  fn modulation_adds_partials() {
+    let table = sine();
+    let mut plain = FmVoice::<2>::new(SAMPLERATE, &table);
+    let mut modulated = FmVoice::<2>::new(SAMPLERATE, &table);
+    for fm in [&mut plain, &mut modulated] {
+      quick(fm);
+      fm.set_ratio(1, 1.0);
+      fm.note_on(69, 1.0, 100.0);
+    }
+    plain.set_modulation(1, 0, 0.0);
+    modulated.set_modulation(1, 0, 5.0);
+    // more crossings from the sidebands
+    assert!(crossings(&mut modulated) > crossings(&mut plain) + 50);
+  }
+
+  #[test]
+  fn fixed_frequency_ignores_note() {
+    let table = sine();
+    let mut fm = FmVoice::<2>::new(SAMPLERATE, &table);
+    quick(&mut fm);
+    fm.set_algorithm(Algorithm::Parallel);
+    fm.set_output(1, 0.0);
+    fm.set_fixed(0, Some(200.0));
+    fm.note_on(69, 1.0, 440.0);
+    let n = crossings(&mut fm);
+    assert!((198..=200).contains(&n), "{n}");
+  }
+
+  #[test]
+  fn algorithms() {
+    let table = sine();
+    let mut fm = FmVoice::<4>::new(SAMPLERATE, &table);
+    assert_eq!(1.0, fm.matrix[3][2]);
+    assert_eq!([1.0, 0.0, 0.0, 0.0], fm.output);
+    fm.set_feedback(3, 0.7);
+    fm.set_algorithm(Algorithm::Pairs);
+    assert_eq!((1.0, 0.0, 1.0), (fm.matrix[3][2], fm.matrix[2][1], fm.matrix[1][0]));
+    assert_eq!([0.5, 0.0, 0.5, 0.0], fm.output);
+    assert_eq!(0.7, fm.matrix[3][3]);
+    fm.set_algorithm(Algorithm::Branch);
+    assert!((1..4).all(|op| fm.matrix[op][0] == 1.0));
+  }
+
+  #[test]
+  fn release_frees_voice() {
+    let table = sine();
+    let mut fm = FmVoice::<2>::new(SAMPLERATE, &table);
+    quick(&mut fm);
+    assert!(!fm.is_active());
+    fm.note_on(60, 1.0, 261.6);
+    (0..480).for_each(|_| { fm.render(); });
+    fm.note_off();
+    assert!(fm.is_active());
+    (0..1000).for_each(|_| { fm.render(); });
+    assert!(!fm.is_active());
+  }
+}
@@ -0,0 +1,301 @@
+use crate::dsp::time::Division;
+use crate::noise::Prng;
+use core::f32::consts::{PI, TAU};
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum LfoShape {
+  #[default] Sine,
+  /// Starts at zero and rises, like the sine
+  Triangle,
+  /// Rising ramp
+  Saw,
+  Square,
+  /// New random value held for each cycle
+  SampleAndHold,
+  /// Cosine interpolated random values, one per cycle
+  SmoothRandom,
+}
+
+/// Low frequency oscillator for modulation.
+///
+/// Output is bipolar `-1.0..=1.0` by default, or unipolar `0.0..=1.0`. Each
+/// call to [`Lfo::play`] advances one sample, [`Lfo::play_control`] advances a
+/// whole control period at once. A trigger restarts the cycle at the phase
+/// offset, and the fade-in ramps the depth up from zero after each restart.
+///
+/// ```
+/// use rust_dsp::{lfo::{Lfo, LfoShape}, dsp::time::Division};
+///
+/// let mut lfo = Lfo::new(48000);
+/// lfo.set_shape(LfoShape::Triangle);
+/// lfo.set_tempo(120.0, Division::Dotted(8));
+/// lfo.set_fade_in(0.5);
+/// // restart the cycle on a trigger
+/// let value = lfo.play(1.0);
+/// ```
+pub struct Lfo {
+  shape: LfoShape,
+  phase: f32,
+  offset: f32,
+  frequency: f32,
+  bipolar: bool,
+  fade: f32,
+  fade_time: f32,
+  rng: Prng,
+  prev_random: f32,
+  next_random: f32,
+  samplerate: f32,
+}
+
+impl Lfo {
+  pub fn new(samplerate: u32) -> Self {
+    let mut rng = Prng::new(0);
+    let next_random = rng.frand_bipolar();
+    Self {
+      shape: LfoShape::Sine,
+      phase: 0.0,
+      offset: 0.0,
+      frequency: 1.0,
+      bipolar: true,
+      fade: 1.0,
+      fade_time: 0.0,
+      rng,
+      prev_random: next_random,
+      next_random,
+      samplerate: samplerate as f32,
+    }
+  }
+
+  /// Advance one sample. A `trig` above `0.0`, following the
+  /// [`TrigTrait`](crate::trig::TrigTrait) convention, restarts the cycle.
+  #[inline]
+  pub fn play(&mut self, trig: f32) -> f32 {
+    self.play_control(trig, 1)
+  }
+
+  /// Advance `period` samples, for updating modulation at control rate.
+  #[inline]
+  pub fn play_control(&mut self, trig: f32, period: usize) -> f32 {
+    if trig > 0.0 {
+      self.reset();
+    }
+    let out = self.value();
+    let step = period as f32 / self.samplerate;
+    self.phase += self.frequency * step;
+    // a long period or a high rate can pass several cycles, and a negative
+    // rate runs the cycles backwards
+    let wraps = self.phase.floor().abs() as usize;
+    self.phase = self.phase.rem_euclid(1.0);
+    if self.phase >= 1.0 {
+      // rounding of a tiny negative phase
+      self.phase = 0.0;
+    }
+    (0..wraps).for_each(|_| self.next_cycle());
+    if self.fade < 1.0 {
+      self.fade = if self.fade_time > 0.0 { f32::min(1.0, self.fade + step / self.fade_time) } else { 1.0 };
+    }
+    out
+  }
+
+  #[inline]
+  fn value(&self) -> f32 {
+    let p = self.phase;
+    let x = match self.shape {
+      LfoShape::Sine => f32::sin(TAU * p),
+      LfoShape::Triangle => 4.0 * f32::abs((p + 0.75).fract() - 0.5) - 1.0,
+      LfoShape::Saw => 2.0 * p - 1.0,
+      LfoShape::Square => if p < 0.5 { 1.0 } else { -1.0 },
+      LfoShape::SampleAndHold => self.next_random,
+      LfoShape::SmoothRandom => {
+        let t = 0.5 - 0.5 * f32::cos(PI * p);
+        self.prev_random + t * (self.next_random - self.prev_random)
+      }
+    };
+    let x = if self.bipolar { x } else { 0.5 * x + 0.5 };
+    x * self.fade
+  }
+
+  fn next_cycle(&mut self) {
+    self.prev_random = self.next_random;
+    self.next_random = self.rng.frand_bipolar();
+  }
+
+  /// Restart the cycle at the phase offset, and the fade-in from zero.
+  pub fn reset(&mut self) {
+    self.phase = self.offset;
+    self.fade = if self.fade_time > 0.0 { 0.0 } else { 1.0 };
+    self.next_cycle();
+  }
+
+  pub fn set_shape(&mut self, shape: LfoShape) {
+    self.shape = shape;
+  }
+
+  /// Free running rate in Hz
+  pub fn set_frequency(&mut self, hz: f32) {
+    self.frequency = hz;
+  }
+
+  /// Sync the rate to one cycle per `division` at `bpm`.
+  pub fn set_tempo(&mut self, bpm: f32, division: Division) {
+    self.frequency = division.hz(bpm);
+  }
+
+  /// Output in `-1.0..=1.0` when `true`, otherwise in `0.0..=1.0`.
+  pub fn set_bipolar(&mut self, bipolar: bool) {
+    self.bipolar = bipolar;
+  }
+
+  /// Phase in cycles, `0.0..1.0`, where the cycle restarts on a trigger.
+  /// The running phase is shifted by the change.
+  pub fn set_phase_offset(&mut self, offset: f32) {
+    let offset = offset.rem_euclid(1.0);
+    self.phase = (self.phase + offset - self.offset).rem_euclid(1.0);
+    self.offset = offset;
+  }
+
+  /// Time in seconds to reach full depth after a restart.
+  pub fn set_fade_in(&mut self, seconds: f32) {
+    self.fade_time = f32::max(0.0, seconds);
+  }
+
+  /// Seed of the random shapes
+  pub fn set_seed(&mut self, seed: u32) {
+    self.rng.reset(seed);
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn cycle(lfo: &mut Lfo, samples: u32) -> impl Iterator<Item = f32> + '_ {
+    (0..samples).map(|_| lfo.play(0.0))
+  }
+
+  #[test]
+  fn sine_frequency() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_frequency(4.0);
+    // the first cycle starts at zero, count the crossings up to one second
+    let values: Vec<f32> = cycle(&mut lfo, SAMPLERATE + SAMPLERATE / 10).collect();
+    let rising = values.windows(2).filter(|w| w[0] < 0.0 && w[1] >= 0.0).count();
+    assert_eq!(4, rising);
+  }
+
+  #[test]
+  fn shapes_stay_in_range() {
+    for shape in [LfoShape::Sine, LfoShape::Triangle, LfoShape::Saw, LfoShape::Square, LfoShape::SampleAndHold, LfoShape::SmoothRandom] {
+      let mut lfo = Lfo::new(SAMPLERATE);
+      lfo.set_shape(shape);
+      lfo.set_frequency(10.0);
+      assert!(cycle(&mut lfo, SAMPLERATE).all(|x| (-1.0..=1.0).contains(&x)));
+      lfo.set_bipolar(false);
+      assert!(cycle(&mut lfo, SAMPLERATE).all(|x| (0.0..=1.0).contains(&x)));
+    }
+  }
+
+  #[test]
+  fn triangle_starts_rising_from_zero() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_shape(LfoShape::Triangle);
+    lfo.set_frequency(1.0);
+    assert!(lfo.play(0.0).abs() < 1e-6);
+    let quarter = cycle(&mut lfo, SAMPLERATE / 4).last().unwrap();
+    assert!((quarter - 1.0).abs() < 1e-3);
+  }
+
+  #[test]
+  fn trigger_resets_phase() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_shape(LfoShape::Saw);
+    lfo.set_phase_offset(0.5);
+    cycle(&mut lfo, 1234).count();
+    assert!(lfo.play(1.0).abs() < 1e-6);
+  }
+
+  #[test]
+  fn tempo_sync() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_shape(LfoShape::Square);
+    // a quarter note at 120 bpm is 0.5 seconds
+    lfo.set_tempo(120.0, Division::Note(4));
+    let values: Vec<f32> = cycle(&mut lfo, SAMPLERATE).collect();
+    let rising = values.windows(2).filter(|w| w[0] < 0.0 && w[1] > 0.0).count();
+    assert_eq!(1, rising);
+    assert_eq!(-1.0, values[SAMPLERATE as usize / 4 + 1]);
+  }
+
+  #[test]
+  fn fade_in() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_shape(LfoShape::Square);
+    lfo.set_frequency(0.1);
+    lfo.set_fade_in(1.0);
+    assert_eq!(0.0, lfo.play(1.0));
+    let half = cycle(&mut lfo, SAMPLERATE / 2).last().unwrap();
+    assert!((half - 0.5).abs() < 1e-3);
+    let full = cycle(&mut lfo, SAMPLERATE).last().unwrap();
+    assert_eq!(1.0, full);
+  }
+
+  #[test]
+  fn sample_and_hold_steps_once_per_cycle() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_shape(LfoShape::SampleAndHold);
+    lfo.set_frequency(10.0);
+    let values: Vec<f32> = cycle(&mut lfo, SAMPLERATE).collect();
+    let steps = values.windows(2).filter(|w| w[0] != w[1]).count();
+    assert!((9..=10).contains(&steps), "{steps}");
+  }
+
+  #[test]
+  fn smooth_random_is_continuous() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_shape(LfoShape::SmoothRandom);
+    lfo.set_frequency(10.0);
+    let values: Vec<f32> = cycle(&mut lfo, SAMPLERATE).collect();
+    assert!(values.windows(2).all(|w| (w[0] - w[1]).abs() < 0.01));
+  }
+
+  #[test]
+  fn control_rate() {
+    let mut audio = Lfo::new(SAMPLERATE);
+    let mut control = Lfo::new(SAMPLERATE);
+    cycle(&mut audio, 64).count();
+    control.play_control(0.0, 64);
+    assert!((audio.play(0.0) - control.play(0.0)).abs() < 1e-5);
+  }
+
+  #[test]
+  fn control_period_counts_every_cycle() {
+    let mut audio = Lfo::new(SAMPLERATE);
+    let mut control = Lfo::new(SAMPLERATE);
+    for lfo in [&mut audio, &mut control] {
+      lfo.set_shape(LfoShape::SampleAndHold);
+      lfo.set_frequency(100.0);
+    }
+    // a second of audio passes 100 cycles
+    cycle(&mut audio, SAMPLERATE).count();
+    control.play_control(0.0, SAMPLERATE as usize);
+    assert_eq!(audio.play(0.0), control.play(0.0));
+  }
+
+  #[test]
+  fn negative_frequency_runs_backwards() {
+    let mut lfo = Lfo::new(SAMPLERATE);
+    lfo.set_shape(LfoShape::Saw);
+    lfo.set_frequency(-1.0);
+    let values: Vec<f32> = cycle(&mut lfo, SAMPLERATE).collect();
+    assert!(values.iter().all(|x| (-1.0..=1.0).contains(x)));
+    // falls from the top after the first sample
+    assert!(values[1] > 0.99, "{}", values[1]);
+    assert!(values[1..SAMPLERATE as usize / 2].windows(2).all(|w| w[1] < w[0]));
+  }
+}
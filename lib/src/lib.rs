@@ -5,6 +5,7 @@ extern crate alloc;
 pub mod dsp;
 pub mod grains;
 pub mod trig;
+pub mod clock;
 pub mod wavetable;
 pub mod interpolation;
 pub mod vector;
@@ -12,14 +13,29 @@ pub mod buffer;
 pub mod waveshape;
 pub mod envelope;
 pub mod adsr;
+pub mod lfo;
+pub mod modulation;
+pub mod smooth;
 pub mod polytable;
+pub mod unison;
+pub mod fm;
+pub mod sync;
+pub mod pd;
+pub mod vshape;
+pub mod additive;
+pub mod voice;
 pub mod delay;
 pub mod filter;
 // pub mod reverb;
 pub mod midibitfield;
+pub mod arp;
+pub mod midi;
+pub mod tuning;
 // pub mod karplus;
 pub mod noise;
 #[allow(non_snake_case)]
 pub mod vector2D;
 pub mod bindings;
 pub mod fold;
+pub mod analysis;
+pub mod dynamics;
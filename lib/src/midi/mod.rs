@@ -0,0 +1,418 @@
+//! MIDI 1.0 messages, parsed from and encoded to a byte stream.
+//!
+//! Works without allocation, SysEx payloads are collected in a fixed size
+//! buffer in the [`MidiParser`].
+
+pub mod mpe;
+pub mod smf;
+
+/// Centre value of a 14 bit pitch bend
+pub const PITCH_BEND_CENTER: u16 = 8192;
+
+/// A parsed MIDI message. Channels are `0..=15`, data values `0..=127`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MidiEvent {
+  NoteOff { channel: u8, note: u8, velocity: u8 },
+  /// A velocity of `0` is a note off by convention, see [`MidiEvent::is_note_off`].
+  NoteOn { channel: u8, note: u8, velocity: u8 },
+  /// Polyphonic aftertouch
+  PolyPressure { channel: u8, note: u8, pressure: u8 },
+  ControlChange { channel: u8, controller: u8, value: u8 },
+  ProgramChange { channel: u8, program: u8 },
+  /// Channel aftertouch
+  ChannelPressure { channel: u8, pressure: u8 },
+  /// 14 bit value, `8192` is centered
+  PitchBend { channel: u8, value: u16 },
+
+  /// A complete SysEx message, the payload without the `0xF0`/`0xF7`
+  /// framing is read with [`MidiParser::sysex`]. `truncated` is set when the
+  /// message did not fit the buffer.
+  SysEx { len: usize, truncated: bool },
+  TimeCode(u8),
+  SongPosition(u16),
+  SongSelect(u8),
+  TuneRequest,
+
+  Clock,
+  Start,
+  Continue,
+  Stop,
+  ActiveSensing,
+  Reset,
+}
+
+impl MidiEvent {
+  /// Channel of channel voice messages
+  pub fn channel(&self) -> Option<u8> {
+    match *self {
+      MidiEvent::NoteOff { channel, .. }
+      | MidiEvent::NoteOn { channel, .. }
+      | MidiEvent::PolyPressure { channel, .. }
+      | MidiEvent::ControlChange { channel, .. }
+      | MidiEvent::ProgramChange { channel, .. }
+      | MidiEvent::ChannelPressure { channel, .. }
+      | MidiEvent::PitchBend { channel, .. } => Some(channel),
+      _ => None,
+    }
+  }
+
+  /// Note off, or note on with velocity `0`.
+  pub fn is_note_off(&self) -> bool {
+    matches!(self, MidiEvent::NoteOff { .. } | MidiEvent::NoteOn { velocity: 0, .. })
+  }
+
+  /// Realtime messages may appear between the bytes of other messages.
+  pub fn is_realtime(&self) -> bool {
+    matches!(self,
+      MidiEvent::Clock | MidiEvent::Start | MidiEvent::Continue
+      | MidiEvent::Stop | MidiEvent::ActiveSensing | MidiEvent::Reset
+    )
+  }
+
+  /// Pitch bend in `-1.0..=1.0`, `None` for other messages.
+  pub fn bend(&self) -> Option<f32> {
+    match *self {
+      MidiEvent::PitchBend { value, .. } => {
+        let offset = value as f32 - PITCH_BEND_CENTER as f32;
+        Some(if offset < 0.0 { offset / 8192.0 } else { offset / 8191.0 })
+      }
+      _ => None,
+    }
+  }
+
+  /// Status byte and data bytes of the message, `None` for SysEx.
+  pub fn to_bytes(&self) -> Option<([u8; 3], usize)> {
+    let lsb = |v: u16| (v & 0x7F) as u8;
+    let msb = |v: u16| ((v >> 7) & 0x7F) as u8;
+    let status = |kind: u8, channel: u8| kind | channel & 0x0F;
+    let (bytes, len) = match *self {
+      MidiEvent::NoteOff { channel, note, velocity } => ([status(0x80, channel), note, velocity], 3),
+      MidiEvent::NoteOn { channel, note, velocity } => ([status(0x90, channel), note, velocity], 3),
+      MidiEvent::PolyPressure { channel, note, pressure } => ([status(0xA0, channel), note, pressure], 3),
+      MidiEvent::ControlChange { channel, controller, value } => ([status(0xB0, channel), controller, value], 3),
+      MidiEvent::ProgramChange { channel, program } => ([status(0xC0, channel), program, 0], 2),
+      MidiEvent::ChannelPressure { channel, pressure } => ([status(0xD0, channel), pressure, 0], 2),
+      MidiEvent::PitchBend { channel, value } => ([status(0xE0, channel), lsb(value), msb(value)], 3),
+      MidiEvent::SysEx { .. } => return None,
+      MidiEvent::TimeCode(v) => ([0xF1, v, 0], 2),
+      MidiEvent::SongPosition(v) => ([0xF2, lsb(v), msb(v)], 3),
+      MidiEvent::SongSelect(v) => ([0xF3, v, 0], 2),
+      MidiEvent::TuneRequest => ([0xF6, 0, 0], 1),
+      MidiEvent::Clock => ([0xF8, 0, 0], 1),
+      MidiEvent::Start => ([0xFA, 0, 0], 1),
+      MidiEvent::Continue => ([0xFB, 0, 0], 1),
+      MidiEvent::Stop => ([0xFC, 0, 0], 1),
+      MidiEvent::ActiveSensing => ([0xFE, 0, 0], 1),
+      MidiEvent::Reset => ([0xFF, 0, 0], 1),
+    };
+    // keep data within range
+    let mut bytes = bytes;
+    bytes[1] &= 0x7F;
+    bytes[2] &= 0x7F;
+    Some((bytes, len))
+  }
+}
+
+/// Number of data bytes following a status byte
+fn data_len(status: u8) -> usize {
+  match status & 0xF0 {
+    0xC0 | 0xD0 => 1,
+    0xF0 => match status {
+      0xF1 | 0xF3 => 1,
+      0xF2 => 2,
+      _ => 0,
+    },
+    _ => 2,
+  }
+}
+
+/// Byte stream parser with running status.
+///
+/// Realtime messages are reported as soon as they arrive, also in the middle
+/// of other messages. Up to `SYSEX` bytes of a SysEx payload are kept.
+///
+/// ```
+/// use rust_dsp::midi::{MidiEvent, MidiParser};
+///
+/// let mut parser = MidiParser::<64>::new();
+/// // note on, and a second one using running status
+/// for byte in [0x90, 60, 100, 64, 100] {
+///   if let Some(MidiEvent::NoteOn { note, velocity, .. }) = parser.parse(byte) {
+///     assert_eq!(100, velocity);
+///   }
+/// }
+/// ```
+pub struct MidiParser<const SYSEX: usize = 256> {
+  status: u8,
+  data: [u8; 2],
+  count: usize,
+  sysex: [u8; SYSEX],
+  sysex_len: usize,
+  in_sysex: bool,
+}
+
+impl<const SYSEX: usize> Default for MidiParser<SYSEX> {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl<const SYSEX: usize> MidiParser<SYSEX> {
+  pub fn new() -> Self {
+    Self { status: 0, data: [0; 2], count: 0, sysex: [0; SYSEX], sysex_len: 0, in_sysex: false }
+  }
+
+  /// Feed one byte, returns the event it completes.
+  pub fn parse(&mut self, byte: u8) -> Option<MidiEvent> {
+    if byte >= 0xF8 {
+      return match byte {
+        0xF8 => Some(MidiEvent::Clock),
+        0xFA => Some(MidiEvent::Start),
+        0xFB => Some(MidiEvent::Continue),
+        0xFC => Some(MidiEvent::Stop),
+        0xFE => Some(MidiEvent::ActiveSensing),
+        0xFF => Some(MidiEvent::Reset),
+        _ => None,
+      };
+    }
+
+    if byte & 0x80 != 0 {
+      // any status byte ends a SysEx, an unterminated one is dropped
+      let sysex = self.in_sysex;
+      self.in_sysex = false;
+      self.count = 0;
+      match byte {
+        0xF7 => {
+          self.status = 0;
+          return sysex.then_some(MidiEvent::SysEx {
+            len: self.sysex_len.min(SYSEX),
+            truncated: self.sysex_len > SYSEX,
+          });
+        }
+        0xF0 => {
+          self.status = 0;
+          self.in_sysex = true;
+          self.sysex_len = 0;
+        }
+        0xF6 => {
+          self.status = 0;
+          return Some(MidiEvent::TuneRequest);
+        }
+        // system common messages replace the running status, and clear it
+        // once complete
+        _ => self.status = byte,
+      }
+      return None;
+    }
+
+    if self.in_sysex {
+      if self.sysex_len < SYSEX {
+        self.sysex[self.sysex_len] = byte;
+      }
+      self.sysex_len = self.sysex_len.saturating_add(1);
+      return None;
+    }
+    if self.status == 0 {
+      return None;
+    }
+
+    self.data[self.count] = byte;
+    self.count += 1;
+    if self.count < data_len(self.status) {
+      return None;
+    }
+    self.count = 0;
+    let channel = self.status & 0x0F;
+    let [d0, d1] = self.data;
+    let event = match self.status & 0xF0 {
+      0x80 => MidiEvent::NoteOff { channel, note: d0, velocity: d1 },
+      0x90 => MidiEvent::NoteOn { channel, note: d0, velocity: d1 },
+      0xA0 => MidiEvent::PolyPressure { channel, note: d0, pressure: d1 },
+      0xB0 => MidiEvent::ControlChange { channel, controller: d0, value: d1 },
+      0xC0 => MidiEvent::ProgramChange { channel, program: d0 },
+      0xD0 => MidiEvent::ChannelPressure { channel, pressure: d0 },
+      0xE0 => MidiEvent::PitchBend { channel, value: d0 as u16 | (d1 as u16) << 7 },
+      _ => {
+        let event = match self.status {
+          0xF1 => Some(MidiEvent::TimeCode(d0)),
+          0xF2 => Some(MidiEvent::SongPosition(d0 as u16 | (d1 as u16) << 7)),
+          0xF3 => Some(MidiEvent::SongSelect(d0)),
+          _ => None,
+        };
+        self.status = 0;
+        return event;
+      }
+    };
+    Some(event)
+  }
+
+  /// Payload of the latest SysEx message.
+  pub fn sysex(&self) -> &[u8] {
+    &self.sysex[..self.sysex_len.min(SYSEX)]
+  }
+
+  /// Forget running status and any partial message.
+  pub fn reset(&mut self) {
+    self.status = 0;
+    self.count = 0;
+    self.in_sysex = false;
+  }
+}
+
+/// Encodes events to bytes, leaving out repeated status bytes.
+///
+/// ```
+/// use rust_dsp::midi::{MidiEncoder, MidiEvent};
+///
+/// let mut encoder = MidiEncoder::new();
+/// let mut bytes = Vec::new();
+/// encoder.encode(&MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 }, &mut |b| bytes.push(b));
+/// encoder.encode(&MidiEvent::NoteOn { channel: 0, note: 64, velocity: 100 }, &mut |b| bytes.push(b));
+/// assert_eq!(vec![0x90, 60, 100, 64, 100], bytes);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct MidiEncoder {
+  /// Leave out repeated status bytes
+  running_status: bool,
+  status: u8,
+}
+
+impl Default for MidiEncoder {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl MidiEncoder {
+  pub fn new() -> Self {
+    Self { running_status: true, status: 0 }
+  }
+
+  /// Use running status, enabled by default.
+  pub fn set_running_status(&mut self, running_status: bool) {
+    self.running_status = running_status;
+    self.status = 0;
+  }
+
+  /// Write the bytes of `event` to `out`. SysEx events carry no payload,
+  /// use [`MidiEncoder::encode_sysex`] for those.
+  pub fn encode(&mut self, event: &MidiEvent, out: &mut impl FnMut(u8)) {
+    let Some((bytes, len)) = event.to_bytes() else { return };
+    let status = bytes[0];
+    let skip_status = status < 0xF0 && status == self.status && self.running_status;
+    if status < 0xF0 {
+      self.status = status;
+    } else if status < 0xF8 {
+      self.status = 0;
+    }
+    let start = if skip_status { 1 } else { 0 };
+    bytes[start..len].iter().for_each(|b| out(*b));
+  }
+
+  /// Write a SysEx message framed by `0xF0` and `0xF7`. Payload bytes are
+  /// masked to 7 bits.
+  pub fn encode_sysex(&mut self, payload: &[u8], out: &mut impl FnMut(u8)) {
+    self.status = 0;
+    out(0xF0);
+    payload.iter().for_each(|b| out(b & 0x7F));
+    out(0xF7);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use alloc::{vec, vec::Vec};
+
+  fn parse_all(bytes: &[u8]) -> Vec<MidiEvent> {
+    let mut parser = MidiParser::<16>::new();
+    bytes.iter().filter_map(|b| parser.parse(*b)).collect()
+  }
+
+  #[test]
+  fn channel_messages() {
+    let events = parse_all(&[0x91, 60, 100, 0xB2, 7, 90, 0xC3, 5, 0xE0, 0x00, 0x40, 0xD4, 33, 0xA5, 60, 10]);
+    assert_eq!(vec![
+      MidiEvent::NoteOn { channel: 1, note: 60, velocity: 100 },
+      MidiEvent::ControlChange { channel: 2, controller: 7, value: 90 },
+      MidiEvent::ProgramChange { channel: 3, program: 5 },
+      MidiEvent::PitchBend { channel: 0, value: 8192 },
+      MidiEvent::ChannelPressure { channel: 4, pressure: 33 },
+      MidiEvent::PolyPressure { channel: 5, note: 60, pressure: 10 },
+    ], events);
+    assert_eq!(Some(0.0), events[3].bend());
+  }
+
+  #[test]
+  fn running_status() {
+    let events = parse_all(&[0x90, 60, 100, 64, 100, 60, 0, 0xC0, 1, 2]);
+    assert_eq!(5, events.len());
+    assert!(events[2].is_note_off());
+    assert_eq!(MidiEvent::ProgramChange { channel: 0, program: 2 }, events[4]);
+  }
+
+  #[test]
+  fn realtime_inside_messages() {
+    let events = parse_all(&[0x90, 0xF8, 60, 0xFA, 100, 62, 0xFE, 90]);
+    assert_eq!(vec![
+      MidiEvent::Clock,
+      MidiEvent::Start,
+      MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 },
+      MidiEvent::ActiveSensing,
+      MidiEvent::NoteOn { channel: 0, note: 62, velocity: 90 },
+    ], events);
+  }
+
+  #[test]
+  fn sysex() {
+    let mut parser = MidiParser::<4>::new();
+    let mut events = Vec::new();
+    for b in [0xF0, 1, 2, 0xF8, 3, 0xF7] {
+      if let Some(e) = parser.parse(b) { events.push(e); }
+    }
+    assert_eq!(vec![MidiEvent::Clock, MidiEvent::SysEx { len: 3, truncated: false }], events);
+    assert_eq!(&[1, 2, 3], parser.sysex());
+
+    // too long for the buffer, and the running status is cancelled
+    let events: Vec<MidiEvent> = [0x90, 60, 1, 0xF0, 1, 2, 3, 4, 5, 0xF7, 61, 1]
+      .iter().filter_map(|b| parser.parse(*b)).collect();
+    assert_eq!(MidiEvent::SysEx { len: 4, truncated: true }, events[1]);
+    assert_eq!(2, events.len());
+  }
+
+  #[test]
+  fn system_common() {
+    let events = parse_all(&[0xF2, 0x10, 0x01, 0xF3, 4, 0xF6, 0xF1, 0x23]);
+    assert_eq!(vec![
+      MidiEvent::SongPosition(0x90),
+      MidiEvent::SongSelect(4),
+      MidiEvent::TuneRequest,
+      MidiEvent::TimeCode(0x23),
+    ], events);
+  }
+
+  #[test]
+  fn encode_roundtrip() {
+    let events = vec![
+      MidiEvent::NoteOn { channel: 3, note: 60, velocity: 100 },
+      MidiEvent::NoteOn { channel: 3, note: 67, velocity: 80 },
+      MidiEvent::Clock,
+      MidiEvent::NoteOff { channel: 3, note: 60, velocity: 0 },
+      MidiEvent::PitchBend { channel: 15, value: 16383 },
+      MidiEvent::SongPosition(1000),
+      MidiEvent::ControlChange { channel: 0, controller: 1, value: 127 },
+      MidiEvent::ControlChange { channel: 0, controller: 1, value: 0 },
+    ];
+    let mut encoder = MidiEncoder::new();
+    let mut bytes = Vec::new();
+    events.iter().for_each(|e| encoder.encode(e, &mut |b| bytes.push(b)));
+    assert_eq!(&[0x93, 60, 100, 67, 80, 0xF8, 0x83, 60, 0], &bytes[..9]);
+    assert_eq!(events, parse_all(&bytes));
+    assert_eq!(Some(1.0), events[4].bend());
+
+    let mut bytes = Vec::new();
+    encoder.set_running_status(false);
+    events[..2].iter().for_each(|e| encoder.encode(e, &mut |b| bytes.push(b)));
+    assert_eq!(6, bytes.len());
+  }
+}
@@ -0,0 +1,351 @@
+//! MIDI Polyphonic Expression.
+//!
+//! Each note of an MPE controller gets its own member channel, so pitch
+//! bend, pressure and timbre (CC74) on that channel apply to that note
+//! alone. Pitch bend on the master channel of the zone bends all notes.
+
+use super::MidiEvent;
+use crate::tuning::Tuning;
+
+/// Default bend range of member channels, in semitones
+pub const MEMBER_BEND_RANGE: f32 = 48.0;
+/// Default bend range of the master channel, in semitones
+pub const MASTER_BEND_RANGE: f32 = 2.0;
+
+/// Timbre controller
+const CC_TIMBRE: u8 = 74;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Zone {
+  /// Master channel 1, members counting up from channel 2
+  #[default] Lower,
+  /// Master channel 16, members counting down from channel 15
+  Upper,
+}
+
+/// Expression of a note.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Expression {
+  pub note: u8,
+  /// Note on velocity, `0.0..=1.0`
+  pub velocity: f32,
+  /// Pitch bend in semitones, member and master bend combined
+  pub bend: f32,
+  /// Channel or polyphonic pressure, `0.0..=1.0`
+  pub pressure: f32,
+  /// CC74, `0.0..=1.0`, centered by default
+  pub timbre: f32,
+}
+
+impl Default for Expression {
+  fn default() -> Self {
+    Self { note: 0, velocity: 0.0, bend: 0.0, pressure: 0.0, timbre: 0.5 }
+  }
+}
+
+impl Expression {
+  /// Bent pitch as a fractional midi note
+  pub fn pitch(&self) -> f32 {
+    self.note as f32 + self.bend
+  }
+
+  /// Frequency of the note in `tuning`, bent in equal tempered semitones.
+  pub fn frequency(&self, tuning: &Tuning) -> Option<f32> {
+    tuning.frequency(self.note).map(|f| f * f32::powf(2.0, self.bend / 12.0))
+  }
+}
+
+/// Note events, with the channel that carries their expression
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum MpeEvent {
+  NoteOn { channel: u8, note: u8, velocity: f32 },
+  NoteOff { channel: u8, note: u8 },
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Channel {
+  note: Option<u8>,
+  velocity: f32,
+  /// Normalized pitch bend, `-1.0..=1.0`
+  bend: f32,
+  pressure: f32,
+  timbre: f32,
+  /// Selected registered parameter, MSB and LSB
+  rpn: (u8, u8),
+}
+
+impl Channel {
+  /// No note, centered controllers and no parameter selected
+  const IDLE: Self = Self { note: None, velocity: 0.0, bend: 0.0, pressure: 0.0, timbre: 0.5, rpn: (127, 127) };
+}
+
+/// Per note expression from an MPE zone.
+///
+/// ```
+/// use rust_dsp::{midi::{MidiParser, mpe::{Mpe, MpeEvent}}, polytable::PolyTable, tuning::Tuning};
+///
+/// let mut parser = MidiParser::<0>::new();
+/// let mut mpe = Mpe::new();
+/// let mut poly = PolyTable::<8>::new();
+/// let tuning = Tuning::default();
+///
+/// for byte in [0x91, 60, 100, 0xE1, 0x00, 0x50, 0xD1, 90] {
+///   if let Some(event) = parser.parse(byte).and_then(|e| mpe.process(&e)) {
+///     poly.trigger_mpe(&event);
+///   }
+/// }
+/// poly.update_mpe(&mpe, &tuning);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Mpe {
+  channels: [Channel; 16],
+  zone: Zone,
+  members: u8,
+  member_range: f32,
+  master_range: f32,
+}
+
+impl Default for Mpe {
+  fn default() -> Self {
+    Self::new()
+  }
+}
+
+impl Mpe {
+  /// Lower zone with all 15 member channels.
+  pub fn new() -> Self {
+    Self {
+      channels: [Channel::IDLE; 16],
+      zone: Zone::Lower,
+      members: 15,
+      member_range: MEMBER_BEND_RANGE,
+      master_range: MASTER_BEND_RANGE,
+    }
+  }
+
+  /// Apply `event`, returns the note event it carries. Messages outside
+  /// the zone are ignored.
+  pub fn process(&mut self, event: &MidiEvent) -> Option<MpeEvent> {
+    let channel = event.channel()?;
+    // configuration messages arrive on the master channel of either zone,
+    // so they can switch the zone
+    if let MidiEvent::ControlChange { controller: controller @ (101 | 100 | 6), value, .. } = *event
+      && (channel == 0 || channel == 15 || self.in_zone(channel))
+    {
+      let rpn = &mut self.channels[channel as usize].rpn;
+      match controller {
+        101 => rpn.0 = value,
+        100 => rpn.1 = value,
+        _ => self.data_entry(channel, value),
+      }
+      return None;
+    }
+    if !self.in_zone(channel) { return None; }
+    let state = &mut self.channels[channel as usize];
+    match *event {
+      MidiEvent::NoteOn { note, velocity, .. } if velocity > 0 => {
+        state.note = Some(note);
+        state.velocity = velocity as f32 / 127.0;
+        return Some(MpeEvent::NoteOn { channel, note, velocity: state.velocity });
+      }
+      MidiEvent::NoteOn { note, .. } | MidiEvent::NoteOff { note, .. } => {
+        if state.note == Some(note) { state.note = None; }
+        return Some(MpeEvent::NoteOff { channel, note });
+      }
+      MidiEvent::PitchBend { .. } => state.bend = event.bend().unwrap_or(0.0),
+      MidiEvent::ChannelPressure { pressure, .. } => state.pressure = pressure as f32 / 127.0,
+      MidiEvent::PolyPressure { note, pressure, .. } if state.note == Some(note) => {
+        state.pressure = pressure as f32 / 127.0;
+      }
+      MidiEvent::ControlChange { controller: CC_TIMBRE, value, .. } => state.timbre = value as f32 / 127.0,
+      _ => (),
+    }
+    None
+  }
+
+  /// Data entry for the selected registered parameter
+  fn data_entry(&mut self, channel: u8, value: u8) {
+    let master = channel == self.master();
+    match self.channels[channel as usize].rpn {
+      (0, 0) if !self.in_zone(channel) => (),
+      // pitch bend sensitivity, applies to all member channels
+      (0, 0) if master => self.master_range = value as f32,
+      (0, 0) => self.member_range = value as f32,
+      // MPE configuration message
+      (0, 6) if channel == 0 => self.set_zone(Zone::Lower, value),
+      (0, 6) if channel == 15 => self.set_zone(Zone::Upper, value),
+      _ => (),
+    }
+  }
+
+  fn master(&self) -> u8 {
+    match self.zone {
+      Zone::Lower => 0,
+      Zone::Upper => 15,
+    }
+  }
+
+  fn in_zone(&self, channel: u8) -> bool {
+    match self.zone {
+      Zone::Lower => channel <= self.members,
+      Zone::Upper => channel >= 15 - self.members,
+    }
+  }
+
+  /// Expression of the note on `channel`, `None` without a note.
+  pub fn expression(&self, channel: u8) -> Option<Expression> {
+    let state = self.channels.get(channel as usize)?;
+    let note = state.note?;
+    let master = self.channels[self.master() as usize].bend * self.master_range;
+    let bend = if channel == self.master() { master } else { state.bend * self.member_range + master };
+    Some(Expression { note, velocity: state.velocity, bend, pressure: state.pressure, timbre: state.timbre })
+  }
+
+  /// Use `zone` with `members` member channels, resets the bend ranges
+  /// to their defaults.
+  pub fn set_zone(&mut self, zone: Zone, members: u8) {
+    self.zone = zone;
+    self.members = members.min(15);
+    self.member_range = MEMBER_BEND_RANGE;
+    self.master_range = MASTER_BEND_RANGE;
+  }
+
+  /// Pitch bend range of the member channels, in semitones.
+  pub fn set_bend_range(&mut self, semitones: f32) {
+    self.member_range = semitones;
+  }
+
+  /// Pitch bend range of the master channel, in semitones.
+  pub fn set_master_bend_range(&mut self, semitones: f32) {
+    self.master_range = semitones;
+  }
+
+  /// Release all notes and center all controllers.
+  pub fn reset(&mut self) {
+    self.channels = [Channel::IDLE; 16];
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  fn bend(channel: u8, value: u16) -> MidiEvent {
+    MidiEvent::PitchBend { channel, value }
+  }
+
+  fn cc(channel: u8, controller: u8, value: u8) -> MidiEvent {
+    MidiEvent::ControlChange { channel, controller, value }
+  }
+
+  #[test]
+  fn per_note_expression() {
+    let mut mpe = Mpe::new();
+    let on = mpe.process(&MidiEvent::NoteOn { channel: 2, note: 60, velocity: 127 });
+    assert_eq!(Some(MpeEvent::NoteOn { channel: 2, note: 60, velocity: 1.0 }), on);
+    mpe.process(&MidiEvent::NoteOn { channel: 3, note: 64, velocity: 64 });
+    mpe.process(&bend(2, 16383));
+    mpe.process(&MidiEvent::ChannelPressure { channel: 3, pressure: 127 });
+    mpe.process(&cc(3, CC_TIMBRE, 0));
+
+    let a = mpe.expression(2).unwrap();
+    assert_eq!(48.0, a.bend);
+    assert_eq!(0.0, a.pressure);
+    assert_eq!(0.5, a.timbre);
+    let b = mpe.expression(3).unwrap();
+    assert_eq!(0.0, b.bend);
+    assert_eq!(1.0, b.pressure);
+    assert_eq!(0.0, b.timbre);
+
+    mpe.process(&MidiEvent::NoteOff { channel: 2, note: 60, velocity: 0 });
+    assert_eq!(None, mpe.expression(2));
+  }
+
+  #[test]
+  fn master_bend_applies_to_all() {
+    let mut mpe = Mpe::new();
+    mpe.process(&MidiEvent::NoteOn { channel: 1, note: 60, velocity: 100 });
+    mpe.process(&bend(0, 16383));
+    mpe.process(&bend(1, 0));
+    assert_eq!(2.0 - 48.0, mpe.expression(1).unwrap().bend);
+    let freq = mpe.expression(1).unwrap().frequency(&Tuning::default()).unwrap();
+    assert!((freq - crate::dsp::math::midi_to_freq(14, 440.0)).abs() < 1e-3);
+  }
+
+  #[test]
+  fn bend_range_rpn() {
+    let mut mpe = Mpe::new();
+    for event in [cc(1, 101, 0), cc(1, 100, 0), cc(1, 6, 12)] {
+      mpe.process(&event);
+    }
+    mpe.process(&MidiEvent::NoteOn { channel: 5, note: 60, velocity: 100 });
+    mpe.process(&bend(5, 16383));
+    assert_eq!(12.0, mpe.expression(5).unwrap().bend);
+  }
+
+  #[test]
+  fn zone_configuration() {
+    let mut mpe = Mpe::new();
+    // upper zone with 4 members by MCM
+    for event in [cc(15, 101, 0), cc(15, 100, 6), cc(15, 6, 4)] {
+      mpe.process(&event);
+    }
+    assert_eq!(None, mpe.process(&MidiEvent::NoteOn { channel: 2, note: 60, velocity: 100 }));
+    assert!(mpe.process(&MidiEvent::NoteOn { channel: 11, note: 60, velocity: 100 }).is_some());
+    mpe.process(&bend(15, 0));
+    assert_eq!(-2.0, mpe.expression(11).unwrap().bend);
+  }
+
+  #[test]
+  fn zone_switches_back() {
+    let mut mpe = Mpe::new();
+    mpe.set_zone(Zone::Upper, 3);
+    // channel 0 lies outside the upper zone, but still configures it
+    for event in [cc(0, 101, 0), cc(0, 100, 6), cc(0, 6, 5)] {
+      mpe.process(&event);
+    }
+    assert!(mpe.process(&MidiEvent::NoteOn { channel: 2, note: 60, velocity: 100 }).is_some());
+    assert_eq!(None, mpe.process(&MidiEvent::NoteOn { channel: 13, note: 60, velocity: 100 }));
+    // and back to upper from outside the lower zone
+    for event in [cc(15, 101, 0), cc(15, 100, 6), cc(15, 6, 5)] {
+      mpe.process(&event);
+    }
+    assert!(mpe.process(&MidiEvent::NoteOn { channel: 13, note: 62, velocity: 100 }).is_some());
+    // a bend range outside the zone is ignored
+    for event in [cc(0, 101, 0), cc(0, 100, 0), cc(0, 6, 24)] {
+      mpe.process(&event);
+    }
+    mpe.process(&bend(13, 16383));
+    assert_eq!(48.0, mpe.expression(13).unwrap().bend);
+  }
+
+  #[test]
+  fn routes_to_voices() {
+    use crate::polytable::PolyTable;
+    let mut mpe = Mpe::new();
+    let mut poly = PolyTable::<2>::new();
+    for event in [
+      MidiEvent::NoteOn { channel: 1, note: 60, velocity: 100 },
+      MidiEvent::NoteOn { channel: 2, note: 67, velocity: 100 },
+      MidiEvent::ChannelPressure { channel: 2, pressure: 127 },
+    ] {
+      if let Some(e) = mpe.process(&event) { poly.trigger_mpe(&e); }
+    }
+    poly.update_mpe(&mpe, &Tuning::default());
+    let [a, b] = poly.expressions();
+    assert_eq!((60, 0.0), (a.note, a.pressure));
+    assert_eq!((67, 1.0), (b.note, b.pressure));
+
+    // released voices keep their last expression
+    poly.trigger_mpe(&mpe.process(&MidiEvent::NoteOff { channel: 2, note: 67, velocity: 0 }).unwrap());
+    mpe.process(&MidiEvent::ChannelPressure { channel: 2, pressure: 0 });
+    poly.update_mpe(&mpe, &Tuning::default());
+    assert_eq!(1.0, poly.expressions()[1].pressure);
+
+    // a plain trigger takes the voice off its channel
+    poly.trigger(Some(220.0));
+    mpe.process(&MidiEvent::ChannelPressure { channel: 1, pressure: 127 });
+    poly.update_mpe(&mpe, &Tuning::default());
+    assert_eq!(0.0, poly.expressions()[0].pressure);
+  }
+}
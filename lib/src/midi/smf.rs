@@ -0,0 +1,420 @@
+//! Standard MIDI File reader and player.
+//!
+//! Reads format 0 and 1 files into one list of timed events, with the tempo
+//! map used to place them in time. SysEx and meta events other than tempo
+//! changes are skipped.
+
+use super::MidiEvent;
+use alloc::vec::Vec;
+
+/// Channel message at a position in ticks
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct TimedEvent {
+  pub tick: u64,
+  /// Index of the track in the file
+  pub track: u16,
+  pub event: MidiEvent,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Timing {
+  /// Ticks per quarter note, follows the tempo map
+  Metrical(u16),
+  /// Frames per second and ticks per frame, independent of tempo
+  Timecode(u8, u8),
+}
+
+/// Tempo change, with the time it happens at
+#[derive(Clone, Copy, Debug, PartialEq)]
+struct Tempo {
+  tick: u64,
+  seconds: f64,
+  seconds_per_tick: f64,
+}
+
+/// A parsed Standard MIDI File.
+///
+/// ```no_run
+/// use rust_dsp::midi::smf::{Smf, SmfPlayer};
+///
+/// let bytes = std::fs::read("song.mid").unwrap();
+/// let smf = Smf::parse(&bytes).unwrap();
+/// let mut player = SmfPlayer::new(&smf, 48000);
+/// let mut block = [0.0; 128];
+/// while !player.is_finished() {
+///   player.process(block.len(), &mut |offset, event| {
+///     // start or stop a voice `offset` samples into the block
+///   });
+/// }
+/// ```
+#[derive(Clone, Debug)]
+pub struct Smf {
+  format: u16,
+  tracks: u16,
+  timing: Timing,
+  events: Vec<TimedEvent>,
+  tempo_map: Vec<Tempo>,
+  length: u64,
+}
+
+/// Bounds checked reading of big endian values
+struct Reader<'a> {
+  data: &'a [u8],
+  pos: usize,
+}
+
+impl<'a> Reader<'a> {
+  fn new(data: &'a [u8]) -> Self {
+    Self { data, pos: 0 }
+  }
+
+  fn is_empty(&self) -> bool {
+    self.pos >= self.data.len()
+  }
+
+  fn bytes(&mut self, len: usize) -> Result<&'a [u8], &'static str> {
+    let end = self.pos.checked_add(len).filter(|end| *end <= self.data.len()).ok_or("unexpected end of data")?;
+    let bytes = &self.data[self.pos..end];
+    self.pos = end;
+    Ok(bytes)
+  }
+
+  fn u8(&mut self) -> Result<u8, &'static str> {
+    Ok(self.bytes(1)?[0])
+  }
+
+  fn peek(&self) -> Result<u8, &'static str> {
+    self.data.get(self.pos).copied().ok_or("unexpected end of data")
+  }
+
+  fn u16(&mut self) -> Result<u16, &'static str> {
+    let b = self.bytes(2)?;
+    Ok(u16::from_be_bytes([b[0], b[1]]))
+  }
+
+  fn u32(&mut self) -> Result<u32, &'static str> {
+    let b = self.bytes(4)?;
+    Ok(u32::from_be_bytes([b[0], b[1], b[2], b[3]]))
+  }
+
+  /// Variable length quantity, at most four bytes
+  fn vlq(&mut self) -> Result<u32, &'static str> {
+    let mut value = 0;
+    for _ in 0..4 {
+      let byte = self.u8()?;
+      value = value << 7 | (byte & 0x7F) as u32;
+      if byte & 0x80 == 0 { return Ok(value); }
+    }
+    Err("variable length quantity too long")
+  }
+}
+
+impl Smf {
+  pub fn parse(bytes: &[u8]) -> Result<Self, &'static str> {
+    let mut reader = Reader::new(bytes);
+    if reader.bytes(4)? != b"MThd" { return Err("not a MIDI file"); }
+    let header_len = reader.u32()? as usize;
+    let mut header = Reader::new(reader.bytes(header_len)?);
+    let format = header.u16()?;
+    let tracks = header.u16()?;
+    let division = header.u16()?;
+    if format > 1 { return Err("only format 0 and 1 are supported"); }
+
+    let timing = if division & 0x8000 == 0 {
+      if division == 0 { return Err("zero ticks per quarter note"); }
+      Timing::Metrical(division)
+    } else {
+      let fps = (division >> 8) as u8 as i8;
+      let ticks = (division & 0xFF) as u8;
+      if ticks == 0 { return Err("zero ticks per frame"); }
+      Timing::Timecode(fps.unsigned_abs(), ticks)
+    };
+
+    let mut events = Vec::new();
+    let mut tempos = Vec::new();
+    let mut length = 0;
+    let mut track = 0;
+    while !reader.is_empty() && track < tracks {
+      let id = reader.bytes(4)?;
+      let len = reader.u32()? as usize;
+      let chunk = reader.bytes(len)?;
+      // unknown chunks are skipped
+      if id != b"MTrk" { continue; }
+      let end = Self::parse_track(chunk, track, &mut events, &mut tempos)?;
+      length = length.max(end);
+      track += 1;
+    }
+
+    // stable, so simultaneous events keep their file order
+    events.sort_by_key(|e| e.tick);
+    tempos.sort_by_key(|t: &(u64, u32)| t.0);
+    let mut smf = Self { format, tracks: track, timing, events, tempo_map: Vec::new(), length };
+    smf.build_tempo_map(&tempos);
+    Ok(smf)
+  }
+
+  /// Reads the events of one track, returns its length in ticks.
+  fn parse_track(data: &[u8], track: u16, events: &mut Vec<TimedEvent>, tempos: &mut Vec<(u64, u32)>) -> Result<u64, &'static str> {
+    let mut reader = Reader::new(data);
+    let mut tick = 0u64;
+    let mut status = 0u8;
+    while !reader.is_empty() {
+      tick += reader.vlq()? as u64;
+      if reader.peek()? & 0x80 != 0 {
+        status = reader.u8()?;
+      }
+      match status {
+        0xFF => {
+          let kind = reader.u8()?;
+          let len = reader.vlq()? as usize;
+          let data = reader.bytes(len)?;
+          status = 0;
+          match kind {
+            0x2F => break,
+            0x51 if len == 3 => tempos.push((tick, u32::from_be_bytes([0, data[0], data[1], data[2]]))),
+            _ => (),
+          }
+        }
+        0xF0 | 0xF7 => {
+          let len = reader.vlq()? as usize;
+          reader.bytes(len)?;
+          status = 0;
+        }
+        0x80..=0xEF => {
+          let channel = status & 0x0F;
+          let d0 = reader.u8()? & 0x7F;
+          let mut d1 = || Ok::<u8, &'static str>(reader.u8()? & 0x7F);
+          let event = match status & 0xF0 {
+            0x80 => MidiEvent::NoteOff { channel, note: d0, velocity: d1()? },
+            0x90 => MidiEvent::NoteOn { channel, note: d0, velocity: d1()? },
+            0xA0 => MidiEvent::PolyPressure { channel, note: d0, pressure: d1()? },
+            0xB0 => MidiEvent::ControlChange { channel, controller: d0, value: d1()? },
+            0xC0 => MidiEvent::ProgramChange { channel, program: d0 },
+            0xD0 => MidiEvent::ChannelPressure { channel, pressure: d0 },
+            _ => MidiEvent::PitchBend { channel, value: d0 as u16 | (d1()? as u16) << 7 },
+          };
+          events.push(TimedEvent { tick, track, event });
+        }
+        _ => return Err("invalid status byte"),
+      }
+    }
+    Ok(tick)
+  }
+
+  fn build_tempo_map(&mut self, tempos: &[(u64, u32)]) {
+    let seconds_per_tick = |micros: u32| match self.timing {
+      Timing::Metrical(ppq) => micros as f64 / (1e6 * ppq as f64),
+      // 29 frames per second means 29.97 drop frame
+      Timing::Timecode(fps, ticks) => {
+        let fps = if fps == 29 { 29.97 } else { fps as f64 };
+        1.0 / (fps * ticks as f64)
+      }
+    };
+    // 120 BPM until the first tempo event
+    let mut map = alloc::vec![Tempo { tick: 0, seconds: 0.0, seconds_per_tick: seconds_per_tick(500_000) }];
+    if matches!(self.timing, Timing::Metrical(_)) {
+      for &(tick, micros) in tempos {
+        let last = map[map.len() - 1];
+        let tempo = Tempo {
+          tick,
+          seconds: last.seconds + (tick - last.tick) as f64 * last.seconds_per_tick,
+          seconds_per_tick: seconds_per_tick(micros),
+        };
+        if tick == last.tick {
+          let len = map.len();
+          map[len - 1] = tempo;
+        } else {
+          map.push(tempo);
+        }
+      }
+    }
+    self.tempo_map = map;
+  }
+
+  /// Time of `tick` in seconds, following the tempo map.
+  pub fn tick_to_seconds(&self, tick: u64) -> f64 {
+    let i = self.tempo_map.partition_point(|t| t.tick <= tick).max(1) - 1;
+    let tempo = &self.tempo_map[i];
+    tempo.seconds + (tick - tempo.tick) as f64 * tempo.seconds_per_tick
+  }
+
+  /// Length of the longest track in seconds
+  pub fn duration(&self) -> f64 {
+    self.tick_to_seconds(self.length)
+  }
+
+  pub fn format(&self) -> u16 {
+    self.format
+  }
+
+  /// Number of tracks read
+  pub fn tracks(&self) -> u16 {
+    self.tracks
+  }
+
+  pub fn timing(&self) -> Timing {
+    self.timing
+  }
+
+  /// Events of all tracks, in order of time
+  pub fn events(&self) -> &[TimedEvent] {
+    &self.events
+  }
+}
+
+/// Plays a [`Smf`] block by block, delivering each event at its sample
+/// offset within the block.
+pub struct SmfPlayer {
+  /// Sample position and event, in order
+  events: Vec<(u64, MidiEvent)>,
+  times: Vec<f64>,
+  next: usize,
+  position: u64,
+  length: u64,
+  duration: f64,
+  samplerate: f32,
+}
+
+impl SmfPlayer {
+  pub fn new(smf: &Smf, samplerate: u32) -> Self {
+    let times = smf.events.iter().map(|e| smf.tick_to_seconds(e.tick)).collect();
+    let mut player = Self {
+      events: smf.events.iter().map(|e| (0, e.event)).collect(),
+      times,
+      next: 0,
+      position: 0,
+      length: 0,
+      duration: smf.duration(),
+      samplerate: samplerate as f32,
+    };
+    player.set_samplerate(samplerate);
+    player
+  }
+
+  /// Advance `samples` samples, calling `func` with the offset into the
+  /// block and the event, for each event in the block.
+  pub fn process(&mut self, samples: usize, func: &mut impl FnMut(usize, &MidiEvent)) {
+    let end = self.position + samples as u64;
+    while let Some((sample, event)) = self.events.get(self.next) && *sample < end {
+      func((sample - self.position) as usize, event);
+      self.next += 1;
+    }
+    self.position = end;
+  }
+
+  /// Playing position in samples
+  pub fn position(&self) -> u64 {
+    self.position
+  }
+
+  /// All events are played, and the end of the longest track is reached.
+  pub fn is_finished(&self) -> bool {
+    self.next >= self.events.len() && self.position >= self.length
+  }
+
+  /// Jump to `seconds`, events before it are skipped.
+  pub fn locate(&mut self, seconds: f64) {
+    self.position = (seconds.max(0.0) * self.samplerate as f64).round() as u64;
+    self.next = self.events.partition_point(|(sample, _)| *sample < self.position);
+  }
+
+  pub fn reset(&mut self) {
+    self.locate(0.0);
+  }
+
+  /// Recomputes the event positions, keeping the playing time.
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    let seconds = self.position as f64 / self.samplerate as f64;
+    self.samplerate = samplerate as f32;
+    let sr = samplerate as f64;
+    self.events.iter_mut().zip(&self.times).for_each(|(e, t)| e.0 = (t * sr).round() as u64);
+    self.length = (self.duration * sr).round() as u64;
+    self.locate(seconds);
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use alloc::{vec, vec::Vec};
+
+  fn chunk(id: &[u8], data: &[u8]) -> Vec<u8> {
+    let mut out = id.to_vec();
+    out.extend((data.len() as u32).to_be_bytes());
+    out.extend(data);
+    out
+  }
+
+  /// Format 1 at 96 ticks per quarter, 120 BPM then 240 BPM from tick 192.
+  fn file() -> Vec<u8> {
+    let mut out = chunk(b"MThd", &[0, 1, 0, 2, 0, 96]);
+    out.extend(chunk(b"MTrk", &[
+      0x00, 0xFF, 0x51, 0x03, 0x07, 0xA1, 0x20,
+      0x81, 0x40, 0xFF, 0x51, 0x03, 0x03, 0xD0, 0x90,
+      0x00, 0xFF, 0x2F, 0x00,
+    ]));
+    out.extend(chunk(b"MTrk", &[
+      0x00, 0x90, 60, 100,
+      // running status, velocity 0
+      0x60, 60, 0,
+      0x00, 0xFF, 0x03, 0x01, b'a',
+      0x81, 0x40, 0x91, 64, 90,
+      0x00, 0xE1, 0x00, 0x40,
+      0x60, 0xFF, 0x2F, 0x00,
+    ]));
+    out
+  }
+
+  #[test]
+  fn parses_tracks() {
+    let smf = Smf::parse(&file()).unwrap();
+    assert_eq!(1, smf.format());
+    assert_eq!(2, smf.tracks());
+    assert_eq!(Timing::Metrical(96), smf.timing());
+    let ticks: Vec<u64> = smf.events().iter().map(|e| e.tick).collect();
+    assert_eq!(vec![0, 96, 288, 288], ticks);
+    assert!(smf.events()[1].event.is_note_off());
+    assert_eq!(MidiEvent::PitchBend { channel: 1, value: 8192 }, smf.events()[3].event);
+  }
+
+  #[test]
+  fn tempo_map() {
+    let smf = Smf::parse(&file()).unwrap();
+    assert_eq!(0.5, smf.tick_to_seconds(96));
+    assert_eq!(1.0, smf.tick_to_seconds(192));
+    assert_eq!(1.25, smf.tick_to_seconds(288));
+    assert_eq!(1.5, smf.duration());
+  }
+
+  #[test]
+  fn sample_accurate_blocks() {
+    let smf = Smf::parse(&file()).unwrap();
+    let mut player = SmfPlayer::new(&smf, 48000);
+    let mut hits = Vec::new();
+    let mut blocks = 0;
+    while !player.is_finished() {
+      let start = player.position();
+      player.process(64, &mut |offset, _| hits.push(start + offset as u64));
+      blocks += 1;
+    }
+    assert_eq!(vec![0, 24000, 60000, 60000], hits);
+    assert_eq!(72000usize.div_ceil(64), blocks);
+
+    player.set_samplerate(44100);
+    player.locate(0.4);
+    hits.clear();
+    player.process(44100, &mut |offset, _| hits.push(offset as u64));
+    assert_eq!(vec![22050 - 17640, 55125 - 17640, 55125 - 17640], hits);
+  }
+
+  #[test]
+  fn rejects_bad_data() {
+    assert!(Smf::parse(b"RIFF").is_err());
+    let mut bytes = file();
+    bytes.truncate(bytes.len() - 3);
+    assert!(Smf::parse(&bytes).is_err());
+    let mut format2 = file();
+    format2[9] = 2;
+    assert!(Smf::parse(&format2).is_err());
+  }
+}
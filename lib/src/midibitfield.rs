@@ -1,5 +1,6 @@
 #[cfg(not(feature="std"))]
 use alloc::{format, string::String};
+use crate::midi::MidiEvent;
 
 
 #[derive(Default)]
@@ -15,7 +16,7 @@ impl MidiBitField {
   // note: `0` sets the 0th bit, making the binary representation `0b001`
   // `0` sets the 1st bit, making the binary representation `0b010` etc.
   pub fn add(&mut self, value: u8) -> Result<(), &'static str> {
-    if value == 128 { return Err("128 is not a valid MIDI note input") }
+    if value >= 128 { return Err("128 is not a valid MIDI note input") }
     self.data |= 1<<value;
     Ok(())
   }
@@ -23,7 +24,7 @@ impl MidiBitField {
   /// Resets note on-data, effectively `note off`
   // Resets a bit if it is set high.
   pub fn remove(&mut self, value: u8) -> Result<(), &'static str> {
-    if value == 128 { return Err("128 is not a valid MIDI note input") }
+    if value >= 128 { return Err("128 is not a valid MIDI note input") }
     self.data &= !(1<<value);
     Ok(())
   }
@@ -43,8 +44,14 @@ impl MidiBitField {
   /// Check if value is encoded as 1 or 0.
   /// returns [`true`] if bit is set. 
   pub fn check(&self, value: u8) -> bool {
+    if value >= 128 { return false }
     let data = self.data;
-    ((1 << value) & data) == 1
+    ((1 << value) & data) != 0
+  }
+
+  /// Returns [`true`] if no notes are active
+  pub fn is_empty(&self) -> bool {
+    self.data == 0
   }
 
   #[allow(unused)]
@@ -58,6 +65,16 @@ impl MidiBitField {
     self.data = 0;
   }
 
+  /// Follow note on and off events, all notes off (CC 123) clears the field.
+  pub fn process(&mut self, event: &MidiEvent) {
+    match *event {
+      MidiEvent::NoteOn { note, velocity, .. } if velocity > 0 => { let _ = self.add(note); }
+      MidiEvent::NoteOn { note, .. } | MidiEvent::NoteOff { note, .. } => { let _ = self.remove(note); }
+      MidiEvent::ControlChange { controller: 123, .. } => self.reset(),
+      _ => (),
+    }
+  }
+
   /// Prints the binary encoding of the underlying structure, a u128
   pub fn repr(&self) -> String {
     format!("{:b}", self.data)
@@ -114,6 +131,16 @@ use super::*;
     assert!(x.is_err())
   }
 
+  #[test]
+  fn check_bits() {
+    let mut bm = MidiBitField::default();
+    bm.add(64).unwrap();
+    assert!(bm.check(64));
+    assert!(!bm.check(63));
+    assert!(!bm.check(200));
+    assert!(bm.add(200).is_err());
+  }
+
   #[test]
   fn representation() {
     let mut bm = MidiBitField::default();
@@ -122,4 +149,21 @@ use super::*;
     assert_eq!("100", bm.repr());
   }
 
+  #[test]
+  fn follows_notes() {
+    let mut bm = MidiBitField::new();
+    for event in [
+      MidiEvent::NoteOn { channel: 0, note: 60, velocity: 100 },
+      MidiEvent::NoteOn { channel: 0, note: 64, velocity: 100 },
+      MidiEvent::NoteOn { channel: 0, note: 60, velocity: 0 },
+      MidiEvent::NoteOff { channel: 0, note: 70, velocity: 0 },
+    ] {
+      bm.process(&event);
+    }
+    assert!(bm.check(64));
+    assert!(!bm.check(60));
+    bm.process(&MidiEvent::ControlChange { channel: 0, controller: 123, value: 0 });
+    assert!(bm.is_empty());
+  }
+
 }
@@ -0,0 +1,313 @@
+/// Samples the input on each rising edge of a trigger and holds it.
+///
+/// Works with the `0.0`/`1.0` output of [`TrigTrait`](crate::trig::TrigTrait),
+/// a trigger held high for several samples only samples once.
+///
+/// ```
+/// use rust_dsp::{modulation::SampleAndHold, noise::Prng, trig::{Dust, TrigTrait}};
+///
+/// let mut sh = SampleAndHold::new();
+/// let mut dust = Dust::new(48000, 1);
+/// let mut rng = Prng::new(2);
+/// let value = sh.process(rng.frand_bipolar(), dust.play(0.25));
+/// ```
+#[derive(Clone, Copy, Debug, Default)]
+pub struct SampleAndHold {
+  held: f32,
+  prev_trig: f32,
+}
+
+impl SampleAndHold {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[inline]
+  pub fn process(&mut self, input: f32, trig: f32) -> f32 {
+    if trig > 0.0 && self.prev_trig <= 0.0 {
+      self.held = input;
+    }
+    self.prev_trig = trig;
+    self.held
+  }
+
+  pub fn value(&self) -> f32 {
+    self.held
+  }
+}
+
+/// Follows the input while the gate is high, and holds the last value
+/// while it is low.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct TrackAndHold {
+  held: f32,
+}
+
+impl TrackAndHold {
+  pub fn new() -> Self {
+    Self::default()
+  }
+
+  #[inline]
+  pub fn process(&mut self, input: f32, gate: f32) -> f32 {
+    if gate > 0.0 {
+      self.held = input;
+    }
+    self.held
+  }
+
+  pub fn value(&self) -> f32 {
+    self.held
+  }
+}
+
+/// Limits how fast the output can follow the input, with separate rates
+/// for rising and falling in units per second.
+#[derive(Clone, Copy, Debug)]
+pub struct SlewLimiter {
+  value: f32,
+  rise: f32,
+  fall: f32,
+  rise_step: f32,
+  fall_step: f32,
+  samplerate: f32,
+}
+
+impl SlewLimiter {
+  /// Starts without limiting, until the rates are set.
+  pub fn new(samplerate: u32) -> Self {
+    Self {
+      value: 0.0,
+      rise: f32::INFINITY,
+      fall: f32::INFINITY,
+      rise_step: f32::INFINITY,
+      fall_step: f32::INFINITY,
+      samplerate: samplerate as f32,
+    }
+  }
+
+  #[inline]
+  pub fn process(&mut self, input: f32) -> f32 {
+    let diff = input - self.value;
+    self.value += diff.clamp(-self.fall_step, self.rise_step);
+    self.value
+  }
+
+  /// Maximum rise in units per second
+  pub fn set_rise(&mut self, rate: f32) {
+    self.rise = rate.abs();
+    self.rise_step = self.rise / self.samplerate;
+  }
+
+  /// Maximum fall in units per second
+  pub fn set_fall(&mut self, rate: f32) {
+    self.fall = rate.abs();
+    self.fall_step = self.fall / self.samplerate;
+  }
+
+  /// Jump to `value` without slewing.
+  pub fn reset(&mut self, value: f32) {
+    self.value = value;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    self.set_rise(self.rise);
+    self.set_fall(self.fall);
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum GlideMode {
+  /// Every change takes the glide time, regardless of distance.
+  #[default] Time,
+  /// Changes move at a fixed rate in units per second.
+  Rate,
+}
+
+/// Portamento between successive target values.
+///
+/// The glide is linear in the units of the input, glide on midi notes
+/// rather than frequency for an even sweep in pitch.
+///
+/// ```
+/// use rust_dsp::modulation::{Glide, GlideMode};
+///
+/// let mut glide = Glide::new(48000);
+/// glide.set_mode(GlideMode::Time);
+/// glide.set_time(0.2);
+/// let note = glide.process(72.0);
+/// let freq = 440.0 * f32::powf(2.0, (note - 69.0) / 12.0);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct Glide {
+  mode: GlideMode,
+  value: f32,
+  start: f32,
+  target: f32,
+  position: f32,
+  length: f32,
+  time: f32,
+  rate: f32,
+  samplerate: f32,
+  active: bool,
+}
+
+impl Glide {
+  pub fn new(samplerate: u32) -> Self {
+    Self {
+      mode: GlideMode::Time,
+      value: 0.0,
+      start: 0.0,
+      target: 0.0,
+      position: 0.0,
+      length: 0.0,
+      time: 0.0,
+      rate: f32::INFINITY,
+      samplerate: samplerate as f32,
+      active: false,
+    }
+  }
+
+  #[inline]
+  pub fn process(&mut self, target: f32) -> f32 {
+    if !self.active {
+      // the first target is reached immediately
+      self.active = true;
+      self.value = target;
+      self.target = target;
+    }
+    if target != self.target {
+      // glides from wherever the previous glide got to
+      self.start = self.value;
+      self.target = target;
+      self.position = 0.0;
+      self.length = match self.mode {
+        GlideMode::Time => self.time * self.samplerate,
+        GlideMode::Rate if self.rate == 0.0 => 0.0,
+        GlideMode::Rate => (target - self.value).abs() / self.rate * self.samplerate,
+      };
+    }
+    self.position += 1.0;
+    self.value = if self.position >= self.length {
+      self.target
+    } else {
+      self.start + (self.target - self.start) * self.position / self.length
+    };
+    self.value
+  }
+
+  pub fn is_gliding(&self) -> bool {
+    self.value != self.target
+  }
+
+  pub fn set_mode(&mut self, mode: GlideMode) {
+    self.mode = mode;
+  }
+
+  /// Glide time in seconds, used in [`GlideMode::Time`]. `0.0` jumps to
+  /// each target.
+  pub fn set_time(&mut self, seconds: f32) {
+    self.time = f32::max(0.0, seconds);
+  }
+
+  /// Glide rate in units per second, used in [`GlideMode::Rate`]. `0.0`
+  /// jumps to each target like a glide time of `0.0`.
+  pub fn set_rate(&mut self, rate: f32) {
+    self.rate = rate.abs();
+  }
+
+  /// Jump to `value` without gliding.
+  pub fn reset(&mut self, value: f32) {
+    self.value = value;
+    self.target = value;
+    self.position = self.length;
+    self.active = true;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::trig::{Impulse, TrigTrait};
+
+  const SAMPLERATE: u32 = 48000;
+
+  #[test]
+  fn sample_and_hold_on_rising_edge() {
+    let mut sh = SampleAndHold::new();
+    assert_eq!(0.0, sh.process(0.3, 0.0));
+    assert_eq!(0.5, sh.process(0.5, 1.0));
+    // held high, no new sample
+    assert_eq!(0.5, sh.process(0.7, 1.0));
+    assert_eq!(0.5, sh.process(0.9, 0.0));
+    assert_eq!(0.2, sh.process(0.2, 1.0));
+  }
+
+  #[test]
+  fn sample_and_hold_with_impulse() {
+    let mut sh = SampleAndHold::new();
+    let mut imp = Impulse::new(SAMPLERATE);
+    let values: Vec<f32> = (0..SAMPLERATE).map(|n| sh.process(n as f32, imp.play(0.1))).collect();
+    let steps = values.windows(2).filter(|w| w[0] != w[1]).count();
+    assert!((9..=10).contains(&steps), "{steps}");
+  }
+
+  #[test]
+  fn track_and_hold() {
+    let mut th = TrackAndHold::new();
+    assert_eq!(0.1, th.process(0.1, 1.0));
+    assert_eq!(0.2, th.process(0.2, 1.0));
+    assert_eq!(0.2, th.process(0.3, 0.0));
+  }
+
+  #[test]
+  fn slew_rates() {
+    let mut slew = SlewLimiter::new(SAMPLERATE);
+    slew.set_rise(10.0);
+    slew.set_fall(1.0);
+    // 0.1 seconds to rise by 1
+    let risen = (0..SAMPLERATE / 10).map(|_| slew.process(1.0)).last().unwrap();
+    assert!((risen - 1.0).abs() < 1e-3, "{risen}");
+    let fallen = (0..SAMPLERATE / 10).map(|_| slew.process(0.0)).last().unwrap();
+    assert!((fallen - 0.9).abs() < 1e-3, "{fallen}");
+  }
+
+  #[test]
+  fn glide_constant_time() {
+    for distance in [1.0, 12.0] {
+      let mut glide = Glide::new(SAMPLERATE);
+      glide.set_time(0.1);
+      glide.process(60.0);
+      let samples = (0..SAMPLERATE).take_while(|_| { glide.process(60.0 + distance); glide.is_gliding() }).count();
+      assert!((samples as i32 - 4800).abs() <= 2, "{samples}");
+    }
+  }
+
+  #[test]
+  fn glide_constant_rate() {
+    let mut glide = Glide::new(SAMPLERATE);
+    glide.set_mode(GlideMode::Rate);
+    glide.set_rate(100.0);
+    glide.reset(0.0);
+    let samples = (0..SAMPLERATE).take_while(|_| { glide.process(50.0); glide.is_gliding() }).count();
+    assert!((samples as i32 - 24000).abs() <= 2, "{samples}");
+  }
+
+  #[test]
+  fn glide_zero_jumps() {
+    for mode in [GlideMode::Time, GlideMode::Rate] {
+      let mut glide = Glide::new(SAMPLERATE);
+      glide.set_mode(mode);
+      glide.set_time(0.0);
+      glide.set_rate(0.0);
+      glide.reset(0.0);
+      assert_eq!(12.0, glide.process(12.0), "{mode:?}");
+      assert!(!glide.is_gliding());
+    }
+  }
+}
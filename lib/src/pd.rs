@@ -0,0 +1,161 @@
+use crate::interpolation::Interpolation;
+
+/// Phase transfer functions of the Casio CZ series.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum PdShape {
+  #[default] Saw,
+  Square,
+  /// Narrowing pulse
+  Pulse,
+  /// Resonance over a falling saw window
+  ResonantSaw,
+  /// Resonance over a triangle window
+  ResonantTriangle,
+  /// Resonance over a trapezoid window
+  ResonantTrapezoid,
+}
+
+/// Phase distortion oscillator.
+///
+/// Reads `table`, normally a sine, as a cosine through a bent phase. At an
+/// amount of `0.0` every shape plays the plain cosine, raising the amount
+/// sharpens the waveform like closing and opening a filter. The resonant
+/// shapes sweep a windowed resonance from the fundamental up to the 15th
+/// harmonic.
+///
+/// ```
+/// use rust_dsp::{interpolation::linear::Linear, pd::{PdOscillator, PdShape}, waveshape::traits::Waveshape};
+///
+/// let sine = [0.0; 1024].sine();
+/// let mut pd = PdOscillator::new(48000);
+/// pd.set_shape(PdShape::ResonantTrapezoid);
+/// pd.set_amount(0.6);
+/// let out = pd.play::<Linear>(&sine, 110.0, 0.0);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct PdOscillator {
+  position: f32,
+  shape: PdShape,
+  amount: f32,
+  sr_recip: f32,
+}
+
+impl PdOscillator {
+  pub fn new(samplerate: u32) -> Self {
+    Self {
+      position: 0.0,
+      shape: PdShape::Saw,
+      amount: 0.0,
+      sr_recip: 1.0 / samplerate as f32,
+    }
+  }
+
+  #[inline]
+  pub fn play<T: Interpolation>(&mut self, table: &[f32], frequency: f32, phase: f32) -> f32 {
+    self.position = (self.position + frequency * self.sr_recip).rem_euclid(1.0);
+    let p = (self.position + phase).rem_euclid(1.0);
+    let len = table.len();
+    // cosine from a sine table
+    let cos = |x: f32| T::interpolate((x + 0.25).rem_euclid(1.0) * len as f32, table, len);
+    let a = self.amount;
+
+    match self.shape {
+      PdShape::Saw => {
+        let knee = 0.5 - 0.49 * a;
+        cos(if p < knee { 0.5 * p / knee } else { 0.5 + 0.5 * (p - knee) / (1.0 - knee) })
+      }
+      PdShape::Square => {
+        let knee = 0.5 - 0.49 * a;
+        let half = p.rem_euclid(0.5);
+        let bent = if half < knee { 0.5 * half / knee } else { 0.5 };
+        cos(if p < 0.5 { bent } else { 0.5 + bent })
+      }
+      PdShape::Pulse => {
+        let width = 1.0 - 0.9 * a;
+        cos(if p < width { p / width } else { 0.0 })
+      }
+      PdShape::ResonantSaw | PdShape::ResonantTriangle | PdShape::ResonantTrapezoid => {
+        let window = match self.shape {
+          PdShape::ResonantSaw => 1.0 - p,
+          PdShape::ResonantTriangle => 1.0 - f32::abs(2.0 * p - 1.0),
+          _ => f32::min(1.0, 2.0 - 2.0 * p),
+        };
+        let resonance = 1.0 + 14.0 * a;
+        // starts and ends each cycle at 1.0, so the window hides the
+        // discontinuity of the resonance
+        1.0 - window * (1.0 - cos((p * resonance).fract()))
+      }
+    }
+  }
+
+  pub fn set_shape(&mut self, shape: PdShape) {
+    self.shape = shape;
+  }
+
+  /// Distortion depth, `0.0..=1.0`.
+  pub fn set_amount(&mut self, amount: f32) {
+    self.amount = amount.clamp(0.0, 1.0);
+  }
+
+  /// Restart at `phase`, `0.0..1.0`.
+  pub fn reset(&mut self, phase: f32) {
+    self.position = phase.rem_euclid(1.0);
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.sr_recip = 1.0 / samplerate as f32;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{interpolation::Linear, waveshape::traits::Waveshape};
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn cycle(shape: PdShape, amount: f32) -> Vec<f32> {
+    let sine = [0.0; 4096].sine();
+    let mut pd = PdOscillator::new(SAMPLERATE);
+    pd.set_shape(shape);
+    pd.set_amount(amount);
+    (0..480).map(|_| pd.play::<Linear>(&sine, 100.0, 0.0)).collect()
+  }
+
+  #[test]
+  fn zero_amount_is_cosine() {
+    for shape in [PdShape::Saw, PdShape::Square, PdShape::Pulse] {
+      let out = cycle(shape, 0.0);
+      for (n, s) in out.iter().enumerate() {
+        let expected = f32::cos(core::f32::consts::TAU * (n + 1) as f32 / 480.0);
+        assert!((s - expected).abs() < 1e-3, "{shape:?} {n}");
+      }
+    }
+  }
+
+  #[test]
+  fn saw_falls_quickly() {
+    let out = cycle(PdShape::Saw, 1.0);
+    // reaches the bottom within the first few percent of the cycle
+    let bottom = out.iter().position(|s| *s < -0.99).unwrap();
+    assert!(bottom < 10, "{bottom}");
+  }
+
+  #[test]
+  fn square_holds() {
+    let out = cycle(PdShape::Square, 1.0);
+    assert!(out[100..230].iter().all(|s| *s < -0.99));
+    assert!(out[340..470].iter().all(|s| *s > 0.99));
+  }
+
+  #[test]
+  fn resonance_is_continuous() {
+    for shape in [PdShape::ResonantSaw, PdShape::ResonantTriangle, PdShape::ResonantTrapezoid] {
+      let mut out = cycle(shape, 0.7);
+      out.extend(cycle(shape, 0.7));
+      assert!(out.iter().all(|s| (-1.0..=1.0).contains(s)));
+      // no jumps at the cycle boundary
+      assert!(out.windows(2).all(|w| (w[1] - w[0]).abs() < 0.5), "{shape:?}");
+    }
+  }
+}
@@ -3,12 +3,57 @@ use core::array;
 use crate::{
   vector::VectorOscillator,
   wavetable::shared::Wavetable,
-  interpolation::Interpolation
+  interpolation::Interpolation,
+  tuning::Tuning,
+  midi::mpe::{Expression, Mpe, MpeEvent},
 };
 
 struct Token<T> {
   voice: T,
   freq: f32,
+  /// Member channel of the sounding MPE note
+  channel: Option<u8>,
+  expression: Expression,
+}
+
+impl<T> Token<T> {
+  fn new(voice: T) -> Self {
+    Self { voice, freq: 0.0, channel: None, expression: Expression::default() }
+  }
+
+  /// Follow the expression of the channel, keeping the last values after
+  /// the note is released.
+  fn update_mpe(&mut self, mpe: &Mpe, tuning: &Tuning) {
+    let Some(expression) = self.channel.and_then(|c| mpe.expression(c)) else { return };
+    if let Some(freq) = expression.frequency(tuning) {
+      self.freq = freq;
+    }
+    self.expression = expression;
+  }
+}
+
+/// Give `freq` to the next voice, which stops following an MPE channel.
+fn trigger<T>(voices: &mut [Token<T>], next: &mut usize, freq: f32) {
+  let v = &mut voices[*next];
+  v.freq = freq;
+  v.channel = None;
+  *next = (*next + 1) % voices.len();
+}
+
+fn trigger_mpe<T>(voices: &mut [Token<T>], next: &mut usize, event: &MpeEvent) {
+  match *event {
+    MpeEvent::NoteOn { channel, note, velocity } => {
+      let v = &mut voices[*next];
+      v.channel = Some(channel);
+      v.expression = Expression { note, velocity, ..Default::default() };
+      *next = (*next + 1) % voices.len();
+    }
+    MpeEvent::NoteOff { channel, note } => {
+      voices.iter_mut()
+        .filter(|v| v.channel == Some(channel) && v.expression.note == note)
+        .for_each(|v| v.channel = None);
+    }
+  }
 }
 
 pub struct PolyTable<const VOICES: usize> {
@@ -18,12 +63,7 @@ pub struct PolyTable<const VOICES: usize> {
 
 impl<const VOICES: usize> Default for PolyTable<VOICES> {
   fn default() -> Self {
-    let voices = array::from_fn(|_| {
-      Token{
-        voice: Wavetable::new(),
-        freq: 0.0,
-      }
-    });
+    let voices = array::from_fn(|_| Token::new(Wavetable::new()));
     Self {
       voices,
       next: 0,
@@ -33,12 +73,7 @@ impl<const VOICES: usize> Default for PolyTable<VOICES> {
 
 impl<const VOICES: usize> PolyTable<VOICES> {
   pub fn new() -> Self {
-    let voices = array::from_fn(|_| {
-      Token{
-        voice: Wavetable::new(),
-        freq: 0.0,
-      }
-    });
+    let voices = array::from_fn(|_| Token::new(Wavetable::new()));
     Self {
       voices,
       next: 0,
@@ -47,14 +82,32 @@ impl<const VOICES: usize> PolyTable<VOICES> {
 
   pub fn trigger(&mut self, note: Option<f32>) {
     if let Some(freq) = note {
-      unsafe {
-        let v = self.voices.get_unchecked_mut(self.next);
-        v.freq = freq;
-      }
-      self.next = (self.next + 1) % VOICES;
+      trigger(&mut self.voices, &mut self.next, freq);
     }
   }
 
+  /// Trigger a midi note, unmapped notes are ignored.
+  pub fn trigger_note(&mut self, note: Option<u8>, tuning: &Tuning) {
+    self.trigger(note.and_then(|n| tuning.frequency(n)));
+  }
+
+  /// Assign MPE notes to voices, the pitch and expression follow with
+  /// [`update_mpe`](Self::update_mpe).
+  pub fn trigger_mpe(&mut self, event: &MpeEvent) {
+    trigger_mpe(&mut self.voices, &mut self.next, event);
+  }
+
+  /// Update the frequency and expression of the voices playing MPE notes.
+  pub fn update_mpe(&mut self, mpe: &Mpe, tuning: &Tuning) {
+    self.voices.iter_mut().for_each(|v| v.update_mpe(mpe, tuning));
+  }
+
+  /// Expression of each voice, to route pressure and timbre to per voice
+  /// parameters in `env_func`.
+  pub fn expressions(&self) -> [Expression; VOICES] {
+    self.voices.each_ref().map(|v| v.expression)
+  }
+
   #[inline]
   pub fn play<T: Interpolation, const N: usize>(
     &mut self,
@@ -83,25 +136,38 @@ pub struct PolyVector<const VOICES: usize> {
 
 impl<const VOICES: usize> PolyVector<VOICES> {
   pub fn new(samplerate: usize) -> Self {
-    let voices = array::from_fn(|_| {
-      Token{
-        voice: VectorOscillator::new(samplerate),
-        freq: 0.0,
-      }
-    });
+    let voices = array::from_fn(|_| Token::new(VectorOscillator::new(samplerate)));
     Self { voices, next: 0 }
   }
   
   pub fn trigger(&mut self, note: Option<f32>) {
     if let Some(freq) = note {
-      unsafe {
-        let v = self.voices.get_unchecked_mut(self.next);
-        v.freq = freq;
-      }
-      self.next = (self.next + 1) % VOICES;
+      trigger(&mut self.voices, &mut self.next, freq);
     }
   }
 
+  /// Trigger a midi note, unmapped notes are ignored.
+  pub fn trigger_note(&mut self, note: Option<u8>, tuning: &Tuning) {
+    self.trigger(note.and_then(|n| tuning.frequency(n)));
+  }
+
+  /// Assign MPE notes to voices, the pitch and expression follow with
+  /// [`update_mpe`](Self::update_mpe).
+  pub fn trigger_mpe(&mut self, event: &MpeEvent) {
+    trigger_mpe(&mut self.voices, &mut self.next, event);
+  }
+
+  /// Update the frequency and expression of the voices playing MPE notes.
+  pub fn update_mpe(&mut self, mpe: &Mpe, tuning: &Tuning) {
+    self.voices.iter_mut().for_each(|v| v.update_mpe(mpe, tuning));
+  }
+
+  /// Expression of each voice, to route pressure and timbre to per voice
+  /// parameters in `env_func`.
+  pub fn expressions(&self) -> [Expression; VOICES] {
+    self.voices.each_ref().map(|v| v.expression)
+  }
+
   pub fn play<const LENGTH: usize, OscInterpolation>(
     &mut self,
     tables: &[[f32; LENGTH]],
@@ -0,0 +1,292 @@
+use core::marker::PhantomData;
+
+/// Curve a [`SmoothedValue`] follows towards its target.
+pub trait SmoothingKind {
+  /// Per sample step of a ramp from `current` to `target` over `samples`,
+  /// `NaN` when the ramp is impossible and the value should jump.
+  fn step(current: f32, target: f32, samples: f32) -> f32;
+  fn apply(current: f32, target: f32, step: f32) -> f32;
+}
+
+/// Straight line ramp, for parameters that are already perceptually linear.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Linear;
+
+/// One-pole lowpass, fast at first and slowing down towards the target.
+/// Gets within -60 dB of the step during the smoothing time, and is then set
+/// to the target.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Exponential;
+
+/// Constant ratio per sample, a straight line in dB or octaves. Meant for
+/// gains and frequencies, which need to stay above zero. Ramps from or to
+/// values `<= 0.0` jump to the target.
+#[derive(Clone, Copy, Debug, Default)]
+pub struct Multiplicative;
+
+impl SmoothingKind for Linear {
+  #[inline]
+  fn step(current: f32, target: f32, samples: f32) -> f32 {
+    (target - current) / samples
+  }
+
+  #[inline]
+  fn apply(current: f32, _target: f32, step: f32) -> f32 {
+    current + step
+  }
+}
+
+impl SmoothingKind for Exponential {
+  #[inline]
+  fn step(_current: f32, _target: f32, samples: f32) -> f32 {
+    // ln(0.001)
+    f32::exp(-6.907755 / samples)
+  }
+
+  #[inline]
+  fn apply(current: f32, target: f32, step: f32) -> f32 {
+    target + step * (current - target)
+  }
+}
+
+impl SmoothingKind for Multiplicative {
+  #[inline]
+  fn step(current: f32, target: f32, samples: f32) -> f32 {
+    if current <= 0.0 || target <= 0.0 { return f32::NAN; }
+    f32::powf(target / current, 1.0 / samples)
+  }
+
+  #[inline]
+  fn apply(current: f32, _target: f32, step: f32) -> f32 {
+    current * step
+  }
+}
+
+/// Parameter that moves to a new target over a fixed time instead of
+/// jumping, to avoid zipper noise on control changes.
+///
+/// Every ramp takes the smoothing time regardless of distance, and lands
+/// exactly on the target. A smoothing time of `0.0` (default) makes every
+/// change immediate.
+///
+/// ```
+/// use rust_dsp::smooth::{SmoothedValue, Multiplicative};
+///
+/// let mut gain = SmoothedValue::<Multiplicative>::new(48000, 1.0);
+/// gain.set_time(0.05);
+/// gain.set_target(0.25);
+/// let input = [0.5; 64];
+/// let mut block = [0.0; 64];
+/// gain.fill(&mut block);
+/// let out = input.iter().zip(block).map(|(x, g)| x * g);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SmoothedValue<T: SmoothingKind> {
+  value: f32,
+  target: f32,
+  step: f32,
+  remaining: usize,
+  length: usize,
+  /// `None` while the length was set in samples without a samplerate
+  time: Option<f32>,
+  samplerate: f32,
+  _marker: PhantomData<T>,
+}
+
+impl<T: SmoothingKind> Default for SmoothedValue<T> {
+  fn default() -> Self {
+    Self::new(0, 0.0)
+  }
+}
+
+impl<T: SmoothingKind> SmoothedValue<T> {
+  pub fn new(samplerate: u32, value: f32) -> Self {
+    Self {
+      value,
+      target: value,
+      step: 0.0,
+      remaining: 0,
+      length: 0,
+      time: Some(0.0),
+      samplerate: samplerate as f32,
+      _marker: PhantomData,
+    }
+  }
+
+  /// Advance one sample.
+  #[inline]
+  pub fn tick(&mut self) -> f32 {
+    if self.remaining > 0 {
+      self.remaining -= 1;
+      self.value = if self.remaining == 0 {
+        self.target
+      } else {
+        T::apply(self.value, self.target, self.step)
+      };
+    }
+    self.value
+  }
+
+  /// Set the target and advance one sample, for parameters passed on
+  /// every call.
+  #[inline]
+  pub fn process(&mut self, target: f32) -> f32 {
+    self.set_target(target);
+    self.tick()
+  }
+
+  /// Fill `out` with the next values.
+  pub fn fill(&mut self, out: &mut [f32]) {
+    if self.remaining == 0 {
+      out.fill(self.value);
+      return;
+    }
+    out.iter_mut().for_each(|o| *o = self.tick());
+  }
+
+  /// Start a ramp from the current value to `target`.
+  #[inline]
+  pub fn set_target(&mut self, target: f32) {
+    if target == self.target { return; }
+    self.target = target;
+    if self.length == 0 {
+      self.value = target;
+      self.remaining = 0;
+      return;
+    }
+    self.step = T::step(self.value, target, self.length as f32);
+    if self.step.is_nan() {
+      self.value = target;
+      self.remaining = 0;
+      return;
+    }
+    self.remaining = self.length;
+  }
+
+  /// Jump to `value`, cancelling any ramp.
+  pub fn set_value(&mut self, value: f32) {
+    self.value = value;
+    self.target = value;
+    self.remaining = 0;
+  }
+
+  pub fn value(&self) -> f32 {
+    self.value
+  }
+
+  pub fn target(&self) -> f32 {
+    self.target
+  }
+
+  pub fn is_smoothing(&self) -> bool {
+    self.remaining > 0
+  }
+
+  /// Smoothing time in seconds, applies from the next target.
+  pub fn set_time(&mut self, seconds: f32) {
+    let seconds = f32::max(0.0, seconds);
+    self.time = Some(seconds);
+    self.length = (seconds * self.samplerate) as usize;
+  }
+
+  /// Smoothing time in samples, for use without a samplerate.
+  pub fn set_length(&mut self, samples: usize) {
+    self.length = samples;
+    self.time = (self.samplerate > 0.0).then(|| samples as f32 / self.samplerate);
+  }
+
+  /// Keeps the smoothing time, or the length in samples when it was set
+  /// without a samplerate.
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate as f32;
+    match self.time {
+      Some(seconds) => self.set_time(seconds),
+      None => self.set_length(self.length),
+    }
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn ramp<T: SmoothingKind>(from: f32, to: f32) -> Vec<f32> {
+    let mut sv = SmoothedValue::<T>::new(SAMPLERATE, from);
+    sv.set_length(100);
+    sv.set_target(to);
+    let mut out = vec![0.0; 110];
+    sv.fill(&mut out);
+    out
+  }
+
+  #[test]
+  fn length_survives_samplerate() {
+    let mut sv = SmoothedValue::<Linear>::new(0, 0.0);
+    sv.set_length(100);
+    sv.set_samplerate(SAMPLERATE);
+    sv.set_target(1.0);
+    let mut out = vec![0.0; 100];
+    sv.fill(&mut out);
+    assert!(out[98] < 1.0);
+    assert_eq!(1.0, out[99]);
+    // from here on the time is known and scales with the samplerate
+    sv.set_samplerate(2 * SAMPLERATE);
+    sv.set_target(0.0);
+    assert_eq!(199, (0..).take_while(|_| { sv.tick(); sv.is_smoothing() }).count());
+  }
+
+  #[test]
+  fn immediate_by_default() {
+    let mut sv = SmoothedValue::<Linear>::new(SAMPLERATE, 0.0);
+    sv.set_target(1.0);
+    assert!(!sv.is_smoothing());
+    assert_eq!(1.0, sv.tick());
+  }
+
+  #[test]
+  fn lands_on_target() {
+    for out in [ramp::<Linear>(0.0, 1.0), ramp::<Exponential>(0.0, 1.0), ramp::<Multiplicative>(0.1, 1.0)] {
+      assert_eq!(1.0, out[99]);
+      assert!(out[98] < 1.0);
+      assert!(out.windows(2).all(|w| w[0] <= w[1]));
+    }
+  }
+
+  #[test]
+  fn linear_is_straight() {
+    let out = ramp::<Linear>(0.0, 1.0);
+    assert!((out[49] - 0.5).abs() < 1e-5);
+  }
+
+  #[test]
+  fn multiplicative_is_straight_in_octaves() {
+    let out = ramp::<Multiplicative>(100.0, 400.0);
+    assert!((out[49] - 200.0).abs() < 1e-2, "{}", out[49]);
+  }
+
+  #[test]
+  fn multiplicative_from_zero_jumps() {
+    let out = ramp::<Multiplicative>(0.0, 1.0);
+    assert_eq!(1.0, out[0]);
+  }
+
+  #[test]
+  fn exponential_is_fast_first() {
+    let out = ramp::<Exponential>(0.0, 1.0);
+    assert!(out[49] > 0.9);
+  }
+
+  #[test]
+  fn retarget_mid_ramp() {
+    let mut sv = SmoothedValue::<Linear>::new(SAMPLERATE, 0.0);
+    sv.set_length(10);
+    sv.set_target(1.0);
+    (0..5).for_each(|_| { sv.tick(); });
+    sv.set_target(0.0);
+    let values: Vec<f32> = (0..10).map(|_| sv.tick()).collect();
+    assert!((values[0] - 0.45).abs() < 1e-5);
+    assert_eq!(0.0, values[9]);
+  }
+}
@@ -0,0 +1,264 @@
+use crate::interpolation::Interpolation;
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum SyncMode {
+  /// Restart the cycle on every sync
+  #[default] Hard,
+  /// Reverse direction on every sync
+  Soft,
+  /// Restart the cycle only when past this phase, `0.0..1.0`
+  Threshold(f32),
+}
+
+/// Oscillator following the phase wraps of a master oscillator.
+///
+/// Masters report their wraps with the sub-sample offset through `sync()`,
+/// e.g. [`Wavetable::sync`](crate::wavetable::shared::Wavetable::sync) or
+/// [`VectorOscillator::sync`](crate::vector::VectorOscillator::sync). The
+/// step a hard reset makes in the waveform is smoothed with a polyBLEP,
+/// which delays the output by one sample.
+///
+/// ```
+/// use rust_dsp::{
+///   interpolation::linear::Linear,
+///   sync::{SyncMode, SyncOscillator},
+///   waveshape::traits::Waveshape,
+///   wavetable::shared::Wavetable,
+/// };
+///
+/// let table = [0.0; 1024].sawtooth();
+/// let mut master = Wavetable::new();
+/// master.set_samplerate(48000);
+/// let mut slave = SyncOscillator::new(48000);
+/// slave.set_mode(SyncMode::Hard);
+///
+/// master.play::<Linear>(&table, 110.0, 0.0);
+/// let out = slave.play::<Linear>(&table, 297.0, master.sync());
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct SyncOscillator {
+  /// Position in the cycle, `0.0..1.0`
+  phase: f32,
+  direction: f32,
+  mode: SyncMode,
+  /// Last sample, waiting for the correction of a step after it
+  held: f32,
+  wrap: Option<f32>,
+  sr_recip: f32,
+}
+
+impl SyncOscillator {
+  pub fn new(samplerate: u32) -> Self {
+    Self {
+      phase: 0.0,
+      direction: 1.0,
+      mode: SyncMode::Hard,
+      held: 0.0,
+      wrap: None,
+      sr_recip: 1.0 / samplerate as f32,
+    }
+  }
+
+  /// Play a single cycle `table`, `sync` is the wrap of the master.
+  #[inline]
+  pub fn play<T: Interpolation>(&mut self, table: &[f32], frequency: f32, sync: Option<f32>) -> f32 {
+    let len = table.len();
+    self.tick(frequency, sync, |phase| T::interpolate(phase * len as f32, table, len))
+  }
+
+  /// Play through `tables` like [`VectorOscillator`](crate::vector::VectorOscillator),
+  /// `position` crossfades between them.
+  #[inline]
+  pub fn play_vector<const LENGTH: usize, T: Interpolation>(
+    &mut self,
+    tables: &[[f32; LENGTH]],
+    frequency: f32,
+    position: f32,
+    sync: Option<f32>
+  ) -> f32 {
+    let width = tables.len();
+    let position = position.clamp(0.0, 1.0) * (width as f32 - 1.0);
+    let t1 = (position.floor() as usize).min(width - 1);
+    let t2 = (t1 + 1).min(width - 1);
+    let x = position - t1 as f32;
+    self.tick(frequency, sync, |phase| {
+      let pos = phase * LENGTH as f32;
+      T::interpolate(pos, &tables[t1], LENGTH) * (1.0 - x) + T::interpolate(pos, &tables[t2], LENGTH) * x
+    })
+  }
+
+  #[inline]
+  fn tick(&mut self, frequency: f32, sync: Option<f32>, read: impl Fn(f32) -> f32) -> f32 {
+    let increment = frequency * self.sr_recip;
+    let next = self.phase + self.direction * increment;
+    self.wrap = None;
+    // step in the waveform and the samples since it
+    let mut step = None;
+
+    match sync {
+      Some(since) => {
+        let since = since.clamp(0.0, 1.0);
+        let at_sync = (self.phase + self.direction * increment * (1.0 - since)).rem_euclid(1.0);
+        if self.mode == SyncMode::Soft {
+          self.direction = -self.direction;
+          self.phase = at_sync + self.direction * increment * since;
+        } else if self.past_threshold(at_sync) {
+          step = Some((read(0.0) - read(at_sync), since));
+          self.direction = 1.0;
+          self.phase = increment * since;
+          self.wrap = Some(since);
+        } else {
+          self.phase = next;
+        }
+      }
+      None => self.phase = next,
+    }
+    if self.phase >= 1.0 || self.phase < 0.0 {
+      if self.phase >= 1.0 && increment > 0.0 {
+        self.wrap = Some(f32::min((self.phase - 1.0) / increment, 1.0 - f32::EPSILON));
+      }
+      self.phase = self.phase.rem_euclid(1.0);
+    }
+
+    let mut current = read(self.phase);
+    let mut out = self.held;
+    if let Some((height, since)) = step {
+      // polyBLEP residuals either side of the step
+      out += 0.5 * height * since * since;
+      current -= 0.5 * height * (1.0 - since) * (1.0 - since);
+    }
+    self.held = current;
+    out
+  }
+
+  fn past_threshold(&self, phase: f32) -> bool {
+    match self.mode {
+      SyncMode::Threshold(threshold) => phase >= threshold,
+      _ => true,
+    }
+  }
+
+  /// Samples since the phase wrapped or was reset, before the last sample,
+  /// to chain further slaves.
+  #[inline]
+  pub fn sync(&self) -> Option<f32> {
+    self.wrap
+  }
+
+  pub fn set_mode(&mut self, mode: SyncMode) {
+    self.mode = mode;
+    if mode != SyncMode::Soft { self.direction = 1.0; }
+  }
+
+  /// Restart at `phase`, `0.0..1.0`.
+  pub fn reset(&mut self, phase: f32) {
+    self.phase = phase.rem_euclid(1.0);
+    self.direction = 1.0;
+    self.held = 0.0;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.sr_recip = 1.0 / samplerate as f32;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::{interpolation::Linear, vector::VectorOscillator, wavetable::shared::Wavetable};
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn saw() -> [f32; 1024] {
+    core::array::from_fn(|i| 2.0 * i as f32 / 1024.0 - 1.0)
+  }
+
+  fn master() -> Wavetable {
+    let mut wt = Wavetable::new();
+    wt.set_samplerate(SAMPLERATE);
+    wt
+  }
+
+  #[test]
+  fn master_reports_wraps() {
+    let table = saw();
+    let mut wt = master();
+    // 0.3 cycles per sample, the first wrap lands 0.2 samples before the
+    // fourth sample
+    let freq = 0.3 * SAMPLERATE as f32;
+    let wraps: Vec<Option<f32>> = (0..4).map(|_| { wt.play::<Linear>(&table, freq, 0.0); wt.sync() }).collect();
+    assert_eq!(None, wraps[2]);
+    assert!((wraps[3].unwrap() - 0.2 / 0.3).abs() < 1e-3, "{wraps:?}");
+
+    let mut vector = VectorOscillator::new(SAMPLERATE as usize);
+    let tables = [saw()];
+    let count = (0..SAMPLERATE).filter(|_| { vector.play::<1024, Linear>(&tables, 100.0, 0.0, 0.0); vector.sync().is_some() }).count();
+    assert!((99..=100).contains(&count), "{count}");
+  }
+
+  #[test]
+  fn hard_sync_follows_master_period() {
+    let table = saw();
+    let mut wt = master();
+    let mut slave = SyncOscillator::new(SAMPLERATE);
+    let out: Vec<f32> = (0..4800).map(|_| {
+      wt.play::<Linear>(&table, 100.0, 0.0);
+      slave.play::<Linear>(&table, 330.0, wt.sync())
+    }).collect();
+    // periodic at the master frequency, 480 samples
+    for n in 1000..1480 {
+      assert!((out[n] - out[n + 480]).abs() < 1e-2, "{n}");
+    }
+    assert!(out.iter().all(|s| s.abs() <= 1.0));
+  }
+
+  #[test]
+  fn reset_step_is_smoothed() {
+    let table = saw();
+    let mut slave = SyncOscillator::new(SAMPLERATE);
+    let freq = 0.01 * SAMPLERATE as f32;
+    // run to the middle of the ramp, then reset halfway between samples
+    (0..50).for_each(|_| { slave.play::<Linear>(&table, freq, None); });
+    let before = slave.play::<Linear>(&table, freq, Some(0.5));
+    let after = slave.play::<Linear>(&table, freq, None);
+    // the naive output would drop from about 0.0 to -1.0 in one sample
+    assert!(before < -0.05 && before > -0.95, "{before}");
+    assert!(after > -1.0 && after < before, "{after}");
+  }
+
+  #[test]
+  fn soft_sync_reverses() {
+    let table = saw();
+    let mut slave = SyncOscillator::new(SAMPLERATE);
+    slave.set_mode(SyncMode::Soft);
+    let freq = 0.01 * SAMPLERATE as f32;
+    let mut out: Vec<f32> = (0..20).map(|_| slave.play::<Linear>(&table, freq, None)).collect();
+    out.extend((0..20).map(|n| slave.play::<Linear>(&table, freq, if n == 0 { Some(0.0) } else { None })));
+    // rising, then falling without a step, after the first delayed sample
+    assert!(out[5] < out[15]);
+    assert!(out[35] < out[25]);
+    assert!(out[1..].windows(2).all(|w| (w[1] - w[0]).abs() < 0.03));
+  }
+
+  #[test]
+  fn threshold_sync() {
+    let table = saw();
+    let freq = 0.01 * SAMPLERATE as f32;
+    let mut slave = SyncOscillator::new(SAMPLERATE);
+    slave.set_mode(SyncMode::Threshold(0.5));
+    (0..10).for_each(|_| { slave.play::<Linear>(&table, freq, None); });
+    slave.play::<Linear>(&table, freq, Some(0.0));
+    assert_eq!(None, slave.sync());
+    (0..50).for_each(|_| { slave.play::<Linear>(&table, freq, None); });
+    slave.play::<Linear>(&table, freq, Some(0.0));
+    assert_eq!(Some(0.0), slave.sync());
+  }
+
+  #[test]
+  fn vector_tables() {
+    let tables = [saw(), [0.0; 1024]];
+    let mut slave = SyncOscillator::new(SAMPLERATE);
+    let out: Vec<f32> = (0..100).map(|n| slave.play_vector::<1024, Linear>(&tables, 440.0, 0.5, if n == 50 { Some(0.3) } else { None })).collect();
+    assert!(out.iter().all(|s| s.abs() <= 0.5));
+  }
+}
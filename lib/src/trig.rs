@@ -1,4 +1,5 @@
 use crate::noise::Prng;
+use alloc::{vec, vec::Vec};
 
 
 pub struct Impulse { 
@@ -144,10 +145,328 @@ impl Trigger {
   }
 }
 
+/// Rising edge detector for clock inputs, so a clock held high for several
+/// samples only counts once.
+#[derive(Clone, Copy, Debug, Default)]
+struct Edge {
+  prev: f32,
+}
+
+impl Edge {
+  #[inline]
+  fn rising(&mut self, clock: f32) -> bool {
+    let rising = clock > 0.0 && self.prev <= 0.0;
+    self.prev = clock;
+    rising
+  }
+}
+
+/// Euclidean rhythm, spreading `pulses` as evenly as possible over `steps`.
+///
+/// Advances one step on each rising edge of the clock, and outputs `1.0`
+/// on that sample when the step is active.
+///
+/// ```
+/// use rust_dsp::trig::{Euclid, Impulse, TrigTrait};
+///
+/// let mut clock = Impulse::new(48000);
+/// // tresillo, x..x..x.
+/// let mut euclid = Euclid::new(8, 3);
+/// let trig = euclid.play(clock.play(0.125));
+/// ```
+pub struct Euclid {
+  pattern: Vec<bool>,
+  pulses: usize,
+  rotation: usize,
+  position: usize,
+  edge: Edge,
+}
 
+impl Euclid {
+  pub fn new(steps: usize, pulses: usize) -> Self {
+    let mut euclid = Self { pattern: Vec::new(), pulses, rotation: 0, position: 0, edge: Edge::default() };
+    euclid.set_steps(steps);
+    euclid
+  }
+
+  #[inline]
+  pub fn play(&mut self, clock: f32) -> f32 {
+    if !self.edge.rising(clock) || self.pattern.is_empty() {
+      return 0.0;
+    }
+    let active = self.pattern[self.position];
+    self.position = (self.position + 1) % self.pattern.len();
+    if active { 1.0 } else { 0.0 }
+  }
 
+  fn update(&mut self) {
+    let steps = self.pattern.len();
+    let pulses = self.pulses.min(steps);
+    for (i, step) in self.pattern.iter_mut().enumerate() {
+      let n = (i + self.rotation) % steps;
+      *step = (n * pulses) % steps < pulses;
+    }
+  }
+
+  pub fn pattern(&self) -> &[bool] {
+    &self.pattern
+  }
+
+  pub fn set_steps(&mut self, steps: usize) {
+    self.pattern.resize(steps, false);
+    self.position = if steps > 0 { self.position % steps } else { 0 };
+    self.update();
+  }
+
+  /// Active steps, limited to the number of steps.
+  pub fn set_pulses(&mut self, pulses: usize) {
+    self.pulses = pulses;
+    self.update();
+  }
+
+  /// Rotate the pattern `rotation` steps to the left.
+  pub fn set_rotation(&mut self, rotation: usize) {
+    self.rotation = rotation;
+    self.update();
+  }
 
-// #[cfg(test)]
-// mod tests {
-//     use super::*;
-// }
+  /// Start from the first step on the next clock.
+  pub fn reset(&mut self) {
+    self.position = 0;
+  }
+}
+
+/// A step of a [`Sequencer`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Step {
+  /// Value sent with the trigger, e.g. a frequency
+  pub value: f32,
+  pub gate: bool,
+  /// Chance of the gate to fire, `0.0..=1.0`
+  pub probability: f32,
+  /// Number of evenly spaced triggers within the step
+  pub ratchets: usize,
+}
+
+impl Default for Step {
+  fn default() -> Self {
+    Self { value: 0.0, gate: true, probability: 1.0, ratchets: 1 }
+  }
+}
+
+/// Step sequencer with per-step values, gates, probability and ratchets.
+///
+/// Advances one step on each rising edge of the clock. The step length is
+/// measured between clock edges, which spaces the ratchets and swing, so
+/// these take effect from the second clock.
+///
+/// ```
+/// use rust_dsp::{adsr::ADSREnvelope, trig::{Sequencer, Step}};
+///
+/// let mut seq = Sequencer::new(4);
+/// seq.set_step(1, Step { value: 220.0, ratchets: 2, ..Default::default() });
+/// seq.set_swing(0.5);
+/// let mut adsr = ADSREnvelope::new(48000);
+/// let trig = seq.play(1.0);
+/// let env = adsr.play(trig > 0.0);
+/// if let Some(freq) = seq.note() {
+///   // poly.trigger(Some(freq))
+/// }
+/// ```
+pub struct Sequencer {
+  steps: Vec<Step>,
+  next: usize,
+  position: usize,
+  value: f32,
+  note: Option<f32>,
+  swing: f32,
+  rng: Prng,
+  edge: Edge,
+
+  period: usize,
+  counter: usize,
+  started: bool,
+  next_fire: usize,
+  interval: usize,
+  ratchets: usize,
+}
+
+impl Sequencer {
+  pub fn new(length: usize) -> Self {
+    Self {
+      steps: vec![Step::default(); length],
+      next: 0,
+      position: 0,
+      value: 0.0,
+      note: None,
+      swing: 0.0,
+      rng: Prng::new(0),
+      edge: Edge::default(),
+      period: 0,
+      counter: 0,
+      started: false,
+      next_fire: 0,
+      interval: 0,
+      ratchets: 0,
+    }
+  }
+
+  /// Returns `1.0` on the samples the current step fires.
+  #[inline]
+  pub fn play(&mut self, clock: f32) -> f32 {
+    self.note = None;
+    if self.edge.rising(clock) && !self.steps.is_empty() {
+      if self.started { self.period = self.counter; }
+      self.started = true;
+      self.counter = 0;
+      self.next_step();
+    }
+
+    let mut out = 0.0;
+    if self.ratchets > 0 && self.counter == self.next_fire {
+      self.ratchets -= 1;
+      self.next_fire += self.interval;
+      self.note = Some(self.value);
+      out = 1.0;
+    }
+    self.counter += 1;
+    out
+  }
+
+  fn next_step(&mut self) {
+    self.position = self.next;
+    self.next = (self.next + 1) % self.steps.len();
+    let step = self.steps[self.position];
+    self.value = step.value;
+
+    let fire = step.gate && (step.probability >= 1.0 || self.rng.frand_unipolar() < step.probability);
+    // every second step is pushed late, by up to half a step
+    let delay = if self.position % 2 == 1 { (self.swing * 0.5 * self.period as f32) as usize } else { 0 };
+    self.next_fire = delay;
+    if !fire || self.period == 0 {
+      self.ratchets = fire as usize;
+      self.interval = 0;
+    } else {
+      self.ratchets = step.ratchets.max(1);
+      self.interval = usize::max(1, (self.period - delay) / self.ratchets);
+    }
+  }
+
+  /// Value of the step that fired on this sample, for
+  /// [`PolyTable::trigger`](crate::polytable::PolyTable::trigger).
+  pub fn note(&self) -> Option<f32> {
+    self.note
+  }
+
+  /// Value of the current step, held between steps.
+  pub fn value(&self) -> f32 {
+    self.value
+  }
+
+  /// Index of the current step.
+  pub fn position(&self) -> usize {
+    self.position
+  }
+
+  pub fn set_step(&mut self, index: usize, step: Step) {
+    self.steps[index] = step;
+  }
+
+  pub fn step_mut(&mut self, index: usize) -> &mut Step {
+    &mut self.steps[index]
+  }
+
+  /// Change the number of steps, new steps are default.
+  pub fn set_length(&mut self, length: usize) {
+    self.steps.resize(length, Step::default());
+    self.next = if length > 0 { self.next % length } else { 0 };
+  }
+
+  /// Delay of every second step, `0.0` is straight and `1.0` delays by
+  /// half a step. `2/3` gives a triplet feel.
+  pub fn set_swing(&mut self, swing: f32) {
+    self.swing = swing.clamp(0.0, 1.0);
+  }
+
+  /// Seed of the step probability
+  pub fn set_seed(&mut self, seed: u32) {
+    self.rng.reset(seed);
+  }
+
+  /// Start from the first step on the next clock.
+  pub fn reset(&mut self) {
+    self.next = 0;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  /// Clock pulse every `period` samples, starting on the first.
+  fn clock(period: usize, samples: usize) -> impl Iterator<Item = f32> {
+    (0..samples).map(move |n| if n % period == 0 { 1.0 } else { 0.0 })
+  }
+
+  #[test]
+  fn euclid_patterns() {
+    let x = true;
+    let o = false;
+    assert_eq!(&[x, o, o, x, o, o, x, o], Euclid::new(8, 3).pattern());
+    assert_eq!(&[x, o, x, o, x, o, x, o], Euclid::new(8, 4).pattern());
+    assert_eq!(5, Euclid::new(13, 5).pattern().iter().filter(|s| **s).count());
+    assert!(Euclid::new(4, 9).pattern().iter().all(|s| *s));
+    let mut euclid = Euclid::new(8, 3);
+    euclid.set_rotation(1);
+    assert_eq!(&[o, o, x, o, o, x, o, x], euclid.pattern());
+  }
+
+  #[test]
+  fn euclid_follows_clock() {
+    let mut euclid = Euclid::new(8, 3);
+    let hits: Vec<usize> = clock(10, 80).enumerate()
+      .filter_map(|(n, c)| (euclid.play(c) > 0.0).then_some(n))
+      .collect();
+    assert_eq!(vec![0, 30, 60], hits);
+  }
+
+  #[test]
+  fn gates_and_values() {
+    let mut seq = Sequencer::new(3);
+    seq.set_step(0, Step { value: 100.0, ..Default::default() });
+    seq.set_step(1, Step { value: 200.0, gate: false, ..Default::default() });
+    seq.set_step(2, Step { value: 300.0, ..Default::default() });
+    let notes: Vec<f32> = clock(10, 60).filter_map(|c| { seq.play(c); seq.note() }).collect();
+    assert_eq!(vec![100.0, 300.0, 100.0, 300.0], notes);
+  }
+
+  #[test]
+  fn probability() {
+    let mut seq = Sequencer::new(1);
+    seq.step_mut(0).probability = 0.0;
+    assert_eq!(0.0, clock(10, 1000).map(|c| seq.play(c)).sum::<f32>());
+    seq.step_mut(0).probability = 0.5;
+    let hits = clock(10, 10000).map(|c| seq.play(c)).sum::<f32>();
+    assert!((400.0..600.0).contains(&hits), "{hits}");
+  }
+
+  #[test]
+  fn ratchets() {
+    let mut seq = Sequencer::new(2);
+    seq.step_mut(1).ratchets = 4;
+    let hits: Vec<usize> = clock(100, 400).enumerate()
+      .filter_map(|(n, c)| (seq.play(c) > 0.0).then_some(n))
+      .collect();
+    assert_eq!(vec![0, 100, 125, 150, 175, 200, 300, 325, 350, 375], hits);
+  }
+
+  #[test]
+  fn swing() {
+    let mut seq = Sequencer::new(2);
+    seq.set_swing(0.5);
+    let hits: Vec<usize> = clock(100, 400).enumerate()
+      .filter_map(|(n, c)| (seq.play(c) > 0.0).then_some(n))
+      .collect();
+    assert_eq!(vec![0, 125, 200, 325], hits);
+  }
+}
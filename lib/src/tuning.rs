@@ -0,0 +1,326 @@
+//! Microtuning with Scala scales and keyboard mappings.
+//!
+//! A [`Scale`] holds the pitches of one period in cents, a [`KeyboardMap`]
+//! places the scale on the keys, and a [`Tuning`] combines the two into a
+//! table of frequencies for the 128 midi notes.
+//!
+//! ```
+//! use rust_dsp::{polytable::PolyTable, tuning::{KeyboardMap, Scale, Tuning}};
+//!
+//! let scl = "! meantone.scl
+//! Quarter comma meantone, 5 notes for the example
+//! 5
+//! 193.157
+//! 386.314
+//! 3/2
+//! 889.735
+//! 2/1
+//! ";
+//! let scale = Scale::parse_scl(scl).unwrap();
+//! let tuning = Tuning::new(&scale, &KeyboardMap::default()).unwrap();
+//! let mut poly = PolyTable::<4>::new();
+//! poly.trigger_note(Some(62), &tuning);
+//! ```
+
+use alloc::{string::String, vec::Vec};
+
+/// Cents of a frequency ratio
+fn ratio_to_cents(ratio: f64) -> f64 {
+  1200.0 * ratio.log2()
+}
+
+/// Meaningful lines of a Scala file, without comments
+fn lines(text: &str) -> impl Iterator<Item = &str> {
+  text.lines().filter(|l| !l.starts_with('!'))
+}
+
+/// First value on a line, ignoring trailing text
+fn first_token(line: &str) -> Result<&str, &'static str> {
+  line.split_whitespace().next().ok_or("missing value")
+}
+
+fn parse_value<T: core::str::FromStr>(line: Option<&str>) -> Result<T, &'static str> {
+  first_token(line.ok_or("unexpected end of file")?)?.parse().map_err(|_| "invalid number")
+}
+
+/// Pitches of a scale, in cents above the tonic.
+///
+/// The tonic at `0.0` cents is implied, the last pitch is the period the
+/// scale repeats at, usually an octave.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Scale {
+  description: String,
+  cents: Vec<f64>,
+}
+
+impl Default for Scale {
+  fn default() -> Self {
+    Self::edo(12).unwrap()
+  }
+}
+
+impl Scale {
+  /// Scale from pitches in cents, the last one being the period.
+  pub fn from_cents(cents: &[f64]) -> Result<Self, &'static str> {
+    if cents.is_empty() { return Err("scale has no pitches"); }
+    if cents.iter().any(|c| !c.is_finite()) { return Err("invalid pitch"); }
+    if cents[cents.len() - 1] <= 0.0 { return Err("period must be above the tonic"); }
+    Ok(Self { description: String::new(), cents: cents.to_vec() })
+  }
+
+  /// Just intonation scale from frequency ratios, the last one being the
+  /// period.
+  pub fn from_ratios(ratios: &[f64]) -> Result<Self, &'static str> {
+    if ratios.iter().any(|r| *r <= 0.0) { return Err("ratios must be positive"); }
+    let cents: Vec<f64> = ratios.iter().map(|r| ratio_to_cents(*r)).collect();
+    Self::from_cents(&cents)
+  }
+
+  /// Equal division of the octave in `steps` steps.
+  pub fn edo(steps: u32) -> Result<Self, &'static str> {
+    Self::equal(steps, 1200.0)
+  }
+
+  /// Equal division of `period` cents in `steps` steps, e.g. Bohlen-Pierce
+  /// with 13 steps of a tritave (1901.955 cents).
+  pub fn equal(steps: u32, period: f64) -> Result<Self, &'static str> {
+    if steps == 0 { return Err("zero steps"); }
+    let cents: Vec<f64> = (1..=steps).map(|n| period * n as f64 / steps as f64).collect();
+    Self::from_cents(&cents)
+  }
+
+  /// Parse the contents of a Scala `.scl` file.
+  pub fn parse_scl(text: &str) -> Result<Self, &'static str> {
+    let mut lines = lines(text);
+    let description = lines.next().ok_or("missing description")?.trim();
+    let count: usize = parse_value(lines.next())?;
+    let mut cents = Vec::with_capacity(count);
+    for _ in 0..count {
+      let token = first_token(lines.next().ok_or("fewer pitches than declared")?)?;
+      let pitch = if token.contains('.') {
+        token.parse().map_err(|_| "invalid cents value")?
+      } else {
+        let (num, den) = token.split_once('/').unwrap_or((token, "1"));
+        let num: f64 = num.parse().map_err(|_| "invalid ratio")?;
+        let den: f64 = den.parse().map_err(|_| "invalid ratio")?;
+        if num <= 0.0 || den <= 0.0 { return Err("ratios must be positive"); }
+        ratio_to_cents(num / den)
+      };
+      cents.push(pitch);
+    }
+    let mut scale = Self::from_cents(&cents)?;
+    scale.description = String::from(description);
+    Ok(scale)
+  }
+
+  pub fn description(&self) -> &str {
+    &self.description
+  }
+
+  /// Number of pitches in a period
+  pub fn len(&self) -> usize {
+    self.cents.len()
+  }
+
+  pub fn is_empty(&self) -> bool {
+    self.cents.is_empty()
+  }
+
+  /// Period of the scale in cents
+  pub fn period(&self) -> f64 {
+    self.cents[self.cents.len() - 1]
+  }
+
+  /// Cents of `degree` above the tonic, repeating at the period in both
+  /// directions.
+  pub fn cents(&self, degree: i32) -> f64 {
+    let len = self.cents.len() as i32;
+    let periods = degree.div_euclid(len);
+    let step = degree.rem_euclid(len);
+    let cents = if step == 0 { 0.0 } else { self.cents[step as usize - 1] };
+    periods as f64 * self.period() + cents
+  }
+}
+
+/// Placement of a scale on the keyboard, as in a Scala `.kbm` file.
+#[derive(Clone, Debug, PartialEq)]
+pub struct KeyboardMap {
+  /// Scale degree of each key in a repetition of the map, `None` for
+  /// unmapped keys. Empty for a linear mapping of consecutive degrees.
+  mapping: Vec<Option<i32>>,
+  first: u8,
+  last: u8,
+  /// Key of the tonic
+  middle: u8,
+  reference_note: u8,
+  reference_freq: f64,
+  /// Scale degree the map repeats at
+  octave_degree: i32,
+}
+
+impl Default for KeyboardMap {
+  /// Tonic on middle C, with A4 at 440 Hz.
+  fn default() -> Self {
+    Self::linear(60, 69, 440.0)
+  }
+}
+
+impl KeyboardMap {
+  /// Consecutive scale degrees on consecutive keys, with the tonic on
+  /// `middle` and `reference_note` tuned to `reference_freq`.
+  pub fn linear(middle: u8, reference_note: u8, reference_freq: f64) -> Self {
+    Self {
+      mapping: Vec::new(),
+      first: 0,
+      last: 127,
+      middle: middle.min(127),
+      reference_note: reference_note.min(127),
+      reference_freq,
+      octave_degree: 0,
+    }
+  }
+
+  /// Parse the contents of a Scala `.kbm` file.
+  pub fn parse_kbm(text: &str) -> Result<Self, &'static str> {
+    let mut lines = lines(text).filter(|l| !l.trim().is_empty());
+    let size: usize = parse_value(lines.next())?;
+    let note = |n: i32| u8::try_from(n).ok().filter(|n| *n < 128).ok_or("note out of range");
+    let first = note(parse_value(lines.next())?)?;
+    let last = note(parse_value(lines.next())?)?;
+    let middle = note(parse_value(lines.next())?)?;
+    let reference_note = note(parse_value(lines.next())?)?;
+    let reference_freq: f64 = parse_value(lines.next())?;
+    if reference_freq <= 0.0 { return Err("reference frequency must be positive"); }
+    let octave_degree = parse_value(lines.next())?;
+    // missing entries at the end are unmapped
+    let mut mapping = alloc::vec![None; size];
+    for (entry, line) in mapping.iter_mut().zip(lines) {
+      let token = first_token(line)?;
+      *entry = if token == "x" { None } else { Some(token.parse().map_err(|_| "invalid mapping")?) };
+    }
+    Ok(Self { mapping, first, last, middle, reference_note, reference_freq, octave_degree })
+  }
+
+  /// Offset of `note` from the tonic in scale degrees, `None` if unmapped.
+  fn degree(&self, note: u8, scale: &Scale) -> Option<i32> {
+    let offset = note as i32 - self.middle as i32;
+    if self.mapping.is_empty() { return Some(offset); }
+    let size = self.mapping.len() as i32;
+    let repeats = offset.div_euclid(size);
+    let degree = self.mapping[offset.rem_euclid(size) as usize]?;
+    let octave = if self.octave_degree > 0 { self.octave_degree } else { scale.len() as i32 };
+    Some(repeats * octave + degree)
+  }
+}
+
+/// Frequencies of the 128 midi notes.
+///
+/// Replaces [`midi_to_freq`](crate::dsp::math::midi_to_freq) where the
+/// tuning should be configurable, the default is 12 tone equal temperament
+/// with A4 at 440 Hz.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Tuning {
+  table: [Option<f32>; 128],
+}
+
+impl Default for Tuning {
+  fn default() -> Self {
+    Self::new(&Scale::default(), &KeyboardMap::default()).unwrap()
+  }
+}
+
+impl Tuning {
+  pub fn new(scale: &Scale, map: &KeyboardMap) -> Result<Self, &'static str> {
+    let reference = map.degree(map.reference_note, scale).ok_or("reference note is not mapped")?;
+    let reference = scale.cents(reference);
+    let mut table = [None; 128];
+    for (note, freq) in table.iter_mut().enumerate().take(map.last as usize + 1).skip(map.first as usize) {
+      *freq = map.degree(note as u8, scale).map(|degree| {
+        let cents = scale.cents(degree) - reference;
+        (map.reference_freq * f64::powf(2.0, cents / 1200.0)) as f32
+      });
+    }
+    Ok(Self { table })
+  }
+
+  /// Equal temperament with `steps` per octave, tonic on middle C and A4
+  /// at 440 Hz.
+  pub fn edo(steps: u32) -> Result<Self, &'static str> {
+    Self::new(&Scale::edo(steps)?, &KeyboardMap::default())
+  }
+
+  /// Frequency of `note`, `None` for notes out of range or unmapped.
+  #[inline]
+  pub fn frequency(&self, note: u8) -> Option<f32> {
+    self.table.get(note as usize).copied().flatten()
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::dsp::math::midi_to_freq;
+
+  fn close(a: f32, b: f32) -> bool {
+    (a - b).abs() < 1e-3 * b
+  }
+
+  #[test]
+  fn default_is_12_tet() {
+    let tuning = Tuning::default();
+    for note in 0..128 {
+      assert!(close(midi_to_freq(note, 440.0), tuning.frequency(note).unwrap()), "{note}");
+    }
+    assert_eq!(None, tuning.frequency(128));
+  }
+
+  #[test]
+  fn edo() {
+    let tuning = Tuning::edo(19).unwrap();
+    // 19 steps above middle C is an octave
+    let c4 = tuning.frequency(60).unwrap();
+    assert!(close(2.0 * c4, tuning.frequency(79).unwrap()));
+    assert!(close(440.0, tuning.frequency(69).unwrap()));
+  }
+
+  #[test]
+  fn scl() {
+    let text = "! test.scl\n!\nJust major\n 7\n!\n 9/8\n 5/4\n 4/3\n 3/2 fifth\n 5/3\n 15/8\n 2\n";
+    let scale = Scale::parse_scl(text).unwrap();
+    assert_eq!("Just major", scale.description());
+    assert_eq!(7, scale.len());
+    assert!((scale.cents(4) - 701.955).abs() < 1e-3);
+    assert!((scale.cents(-3) + 1200.0 - 701.955).abs() < 1e-3);
+    assert_eq!(Scale::from_ratios(&[9.0 / 8.0, 5.0 / 4.0, 4.0 / 3.0, 1.5, 5.0 / 3.0, 15.0 / 8.0, 2.0]).unwrap().cents, scale.cents);
+    assert!(Scale::parse_scl("desc\n3\n100.0\n200.0\n").is_err());
+    assert!(Scale::parse_scl("desc\n1\n-3/2\n").is_err());
+  }
+
+  #[test]
+  fn kbm() {
+    // 7 note scale on the white keys, A4 at 432 Hz
+    let kbm = "! white.kbm\n12\n0\n127\n60\n69\n432.0\n7\n0\nx\n1\nx\n2\n3\nx\n4\nx\n5\nx\n6\n";
+    let map = KeyboardMap::parse_kbm(kbm).unwrap();
+    let scale = Scale::parse_scl("Just major\n7\n9/8\n5/4\n4/3\n3/2\n5/3\n15/8\n2/1\n").unwrap();
+    let tuning = Tuning::new(&scale, &map).unwrap();
+    assert_eq!(None, tuning.frequency(61));
+    assert!(close(432.0, tuning.frequency(69).unwrap()));
+    let c4 = tuning.frequency(60).unwrap();
+    assert!(close(c4 * 1.5, tuning.frequency(67).unwrap()));
+    assert!(close(c4 * 2.0, tuning.frequency(72).unwrap()));
+    assert!(close(c4 / 2.0 * 15.0 / 8.0, tuning.frequency(59).unwrap()));
+
+    // the reference has to be mapped
+    let kbm = "12\n0\n127\n60\n61\n440.0\n7\n0\nx\n";
+    assert!(Tuning::new(&scale, &KeyboardMap::parse_kbm(kbm).unwrap()).is_err());
+  }
+
+  #[test]
+  fn key_range() {
+    let kbm = "0\n48\n72\n60\n69\n440.0\n0\n";
+    let tuning = Tuning::new(&Scale::default(), &KeyboardMap::parse_kbm(kbm).unwrap()).unwrap();
+    assert_eq!(None, tuning.frequency(47));
+    assert!(tuning.frequency(48).is_some());
+    assert_eq!(None, tuning.frequency(73));
+  }
+}
@@ -0,0 +1,379 @@
+use core::array;
+
+use crate::{
+  dsp::signal::pan_exp2,
+  interpolation::Interpolation,
+  noise::Prng,
+  vector::VectorOscillator,
+  wavetable::shared::Wavetable,
+};
+
+/// Most voices of a [`Unison`]
+pub const MAX_VOICES: usize = 16;
+
+/// How the detune is spread from the center to the outer voices.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum DetuneCurve {
+  /// Evenly spaced
+  #[default] Linear,
+  /// Close to the center, spreading out towards the outer voices
+  Quadratic,
+  /// Random offsets, fixed per seed
+  Random,
+}
+
+/// Phase the voices start at on `Unison::reset`.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum StartPhase {
+  /// A new random phase per voice
+  Random,
+  /// The same phase for all voices, `0.0..1.0`
+  Fixed(f32),
+}
+
+/// Stack of detuned oscillators playing the same note, panned across the
+/// stereo field.
+///
+/// Wraps [`Wavetable`] or [`VectorOscillator`]. Voices are placed from
+/// `-1.0` to `1.0`, the detune in cents and the stereo spread follow that
+/// placement. With an even number of voices the two innermost voices make
+/// up the center.
+///
+/// ```
+/// use rust_dsp::{
+///   interpolation::linear::Linear,
+///   unison::{Unison, DetuneCurve},
+///   waveshape::traits::Waveshape,
+///   wavetable::shared::Wavetable,
+/// };
+///
+/// let table = [0.0; 1024].sawtooth();
+/// let mut unison = Unison::<Wavetable>::new(48000);
+/// unison.set_voices(7);
+/// unison.set_detune(25.0);
+/// unison.set_curve(DetuneCurve::Quadratic);
+/// unison.set_spread(0.8);
+/// let (left, right) = unison.play::<Linear>(&table, 110.0, 0.0);
+/// ```
+pub struct Unison<O> {
+  oscillators: [O; MAX_VOICES],
+  voices: usize,
+  ratios: [f32; MAX_VOICES],
+  gains: [(f32, f32); MAX_VOICES],
+  phases: [f32; MAX_VOICES],
+  /// Random offsets of [`DetuneCurve::Random`], `-1.0..=1.0`
+  offsets: [f32; MAX_VOICES],
+  detune: f32,
+  curve: DetuneCurve,
+  spread: f32,
+  blend: f32,
+  start: StartPhase,
+  rng: Prng,
+}
+
+impl Unison<Wavetable> {
+  pub fn new(samplerate: u32) -> Self {
+    let mut unison = Self::with_oscillators(array::from_fn(|_| {
+      let mut osc = Wavetable::new();
+      osc.set_samplerate(samplerate);
+      osc
+    }));
+    unison.reset();
+    unison
+  }
+
+  /// Stereo output, `phase` modulates all voices.
+  #[inline]
+  pub fn play<T: Interpolation>(&mut self, table: &[f32], frequency: f32, phase: f32) -> (f32, f32) {
+    let (mut left, mut right) = (0.0, 0.0);
+    let voices = self.oscillators.iter_mut().zip(&self.ratios).zip(&self.gains);
+    for ((osc, ratio), gain) in voices.take(self.voices) {
+      let sig = osc.play::<T>(table, frequency * ratio, phase);
+      left += sig * gain.0;
+      right += sig * gain.1;
+    }
+    (left, right)
+  }
+
+  /// Restart the voices at their start phases.
+  pub fn reset(&mut self) {
+    self.draw_phases();
+    self.oscillators.iter_mut().zip(&self.phases).for_each(|(o, p)| o.reset(*p));
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.oscillators.iter_mut().for_each(|o| o.set_samplerate(samplerate));
+  }
+}
+
+impl Unison<VectorOscillator> {
+  pub fn new(samplerate: usize) -> Self {
+    let mut unison = Self::with_oscillators(array::from_fn(|_| VectorOscillator::new(samplerate)));
+    unison.reset();
+    unison
+  }
+
+  /// Stereo output, `position` and `phase` apply to all voices.
+  #[inline]
+  pub fn play<const LENGTH: usize, T: Interpolation>(
+    &mut self,
+    tables: &[[f32; LENGTH]],
+    frequency: f32,
+    position: f32,
+    phase: f32
+  ) -> (f32, f32) {
+    let (mut left, mut right) = (0.0, 0.0);
+    let voices = self.oscillators.iter_mut().zip(&self.ratios).zip(&self.gains);
+    for ((osc, ratio), gain) in voices.take(self.voices) {
+      let sig = osc.play::<LENGTH, T>(tables, frequency * ratio, position, phase);
+      left += sig * gain.0;
+      right += sig * gain.1;
+    }
+    (left, right)
+  }
+
+  /// Restart the voices at their start phases.
+  pub fn reset(&mut self) {
+    self.draw_phases();
+    self.oscillators.iter_mut().zip(&self.phases).for_each(|(o, p)| o.reset(*p));
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: usize) {
+    self.oscillators.iter_mut().for_each(|o| o.set_samplerate(samplerate));
+  }
+}
+
+impl<O> Unison<O> {
+  fn with_oscillators(oscillators: [O; MAX_VOICES]) -> Self {
+    let mut unison = Self {
+      oscillators,
+      voices: 1,
+      ratios: [1.0; MAX_VOICES],
+      gains: [(0.0, 0.0); MAX_VOICES],
+      phases: [0.0; MAX_VOICES],
+      offsets: [0.0; MAX_VOICES],
+      detune: 0.0,
+      curve: DetuneCurve::Linear,
+      spread: 0.0,
+      blend: 0.5,
+      start: StartPhase::Random,
+      rng: Prng::new(1),
+    };
+    unison.randomize_offsets();
+    unison.update();
+    unison
+  }
+
+  /// Placement of voice `i` from `-1.0` to `1.0`
+  fn placement(&self, i: usize) -> f32 {
+    if self.voices == 1 { return 0.0; }
+    2.0 * i as f32 / (self.voices - 1) as f32 - 1.0
+  }
+
+  fn is_center(&self, i: usize) -> bool {
+    let n = self.voices;
+    if n % 2 == 1 { i == n / 2 } else { i == n / 2 || i + 1 == n / 2 }
+  }
+
+  fn update(&mut self) {
+    let n = self.voices;
+    let centers = if n % 2 == 1 { 1 } else { 2 };
+    let sides = n - centers.min(n);
+    // equal power between the center and the sides
+    let (center_gain, side_gain) = if sides == 0 {
+      (1.0 / f32::sqrt(centers as f32), 0.0)
+    } else {
+      ((1.0 - self.blend) / f32::sqrt(centers as f32), self.blend / f32::sqrt(sides as f32))
+    };
+    for i in 0..n {
+      let x = self.placement(i);
+      let detune = match self.curve {
+        DetuneCurve::Linear => x,
+        DetuneCurve::Quadratic => x * x.abs(),
+        // an even number has no voice at zero detune to begin with
+        DetuneCurve::Random if x == 0.0 => 0.0,
+        DetuneCurve::Random => self.offsets[i],
+      };
+      self.ratios[i] = f32::powf(2.0, detune * self.detune / 1200.0);
+      let gain = if self.is_center(i) { center_gain } else { side_gain };
+      let (l, r) = pan_exp2(-x * self.spread);
+      self.gains[i] = (gain * l, gain * r);
+    }
+  }
+
+  fn randomize_offsets(&mut self) {
+    for i in 0..MAX_VOICES {
+      self.offsets[i] = self.rng.frand_bipolar();
+    }
+  }
+
+  fn draw_phases(&mut self) {
+    for i in 0..MAX_VOICES {
+      self.phases[i] = match self.start {
+        StartPhase::Random => self.rng.frand_unipolar(),
+        StartPhase::Fixed(phase) => phase,
+      };
+    }
+  }
+
+  /// Number of voices, `1..=16`.
+  pub fn set_voices(&mut self, voices: usize) {
+    self.voices = voices.clamp(1, MAX_VOICES);
+    self.update();
+  }
+
+  /// Detune of the outer voices in cents.
+  pub fn set_detune(&mut self, cents: f32) {
+    self.detune = cents.abs();
+    self.update();
+  }
+
+  pub fn set_curve(&mut self, curve: DetuneCurve) {
+    self.curve = curve;
+    self.update();
+  }
+
+  /// Stereo width, `0.0` is mono and `1.0` pans the outer voices hard
+  /// left and right.
+  pub fn set_spread(&mut self, spread: f32) {
+    self.spread = spread.clamp(0.0, 1.0);
+    self.update();
+  }
+
+  /// Balance between the center and the side voices, `0.0` is only the
+  /// center, `1.0` only the sides.
+  pub fn set_blend(&mut self, blend: f32) {
+    self.blend = blend.clamp(0.0, 1.0);
+    self.update();
+  }
+
+  /// Applies from the next `reset`.
+  pub fn set_start_phase(&mut self, start: StartPhase) {
+    self.start = start;
+  }
+
+  /// Seed of the random phases and detune.
+  pub fn set_seed(&mut self, seed: u32) {
+    self.rng.reset(seed);
+    self.randomize_offsets();
+    self.update();
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::interpolation::linear::Linear;
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn saw() -> [f32; 512] {
+    array::from_fn(|i| 2.0 * i as f32 / 512.0 - 1.0)
+  }
+
+  #[test]
+  fn single_voice_is_centered() {
+    let mut unison = Unison::<Wavetable>::new(SAMPLERATE);
+    unison.set_start_phase(StartPhase::Fixed(0.0));
+    unison.reset();
+    unison.set_spread(1.0);
+    unison.set_detune(50.0);
+    let mut osc = Wavetable::new();
+    osc.set_samplerate(SAMPLERATE);
+    let table = saw();
+    for _ in 0..100 {
+      let (l, r) = unison.play::<Linear>(&table, 100.0, 0.0);
+      let expected = osc.play::<Linear>(&table, 100.0, 0.0) * core::f32::consts::FRAC_1_SQRT_2;
+      assert!((l - expected).abs() < 1e-6 && (r - expected).abs() < 1e-6);
+    }
+  }
+
+  #[test]
+  fn detune_curves() {
+    let mut unison = Unison::<Wavetable>::new(SAMPLERATE);
+    unison.set_voices(5);
+    unison.set_detune(100.0);
+    let semitone = f32::powf(2.0, 1.0 / 12.0);
+    assert!((unison.ratios[4] - semitone).abs() < 1e-6);
+    assert!((unison.ratios[0] - 1.0 / semitone).abs() < 1e-6);
+    assert_eq!(1.0, unison.ratios[2]);
+    let linear = unison.ratios[3];
+    unison.set_curve(DetuneCurve::Quadratic);
+    assert!(unison.ratios[3] < linear);
+    assert!((unison.ratios[4] - semitone).abs() < 1e-6);
+    unison.set_curve(DetuneCurve::Random);
+    assert_eq!(1.0, unison.ratios[2]);
+    assert!(unison.ratios[..5].iter().all(|r| (1.0 / semitone..=semitone).contains(r)));
+  }
+
+  #[test]
+  fn stereo_spread_and_blend() {
+    let mut unison = Unison::<Wavetable>::new(SAMPLERATE);
+    unison.set_voices(3);
+    unison.set_spread(1.0);
+    // first voice hard left, last hard right
+    assert!(unison.gains[0].1.abs() < 1e-6 && unison.gains[0].0 > 0.0);
+    assert!(unison.gains[2].0.abs() < 1e-6 && unison.gains[2].1 > 0.0);
+    unison.set_blend(0.0);
+    assert_eq!((0.0, 0.0), (unison.gains[0].0, unison.gains[2].1));
+    assert!(unison.gains[1].0 > 0.0);
+    unison.set_blend(1.0);
+    assert_eq!((0.0, 0.0), unison.gains[1]);
+  }
+
+  #[test]
+  fn start_phases() {
+    let mut unison = Unison::<Wavetable>::new(SAMPLERATE);
+    unison.set_start_phase(StartPhase::Fixed(0.25));
+    unison.reset();
+    assert!(unison.phases.iter().all(|p| *p == 0.25));
+    unison.set_start_phase(StartPhase::Random);
+    unison.reset();
+    assert!(unison.phases.windows(2).any(|w| w[0] != w[1]));
+  }
+
+  #[test]
+  fn vector_voices() {
+    let mut unison = Unison::<VectorOscillator>::new(SAMPLERATE as usize);
+    unison.set_voices(4);
+    unison.set_detune(10.0);
+    let tables = [saw(), saw()];
+    let out: Vec<(f32, f32)> = (0..256).map(|_| unison.play::<512, Linear>(&tables, 100.0, 0.5, 0.0)).collect();
+    assert!(out.iter().all(|(l, r)| l.is_finite() && r.is_finite() && l.abs() <= 4.0 && r.abs() <= 4.0));
+    assert!(out.iter().any(|(l, _)| *l != 0.0));
+  }
+
+  #[test]
+  fn voices_align_after_reset() {
+    let table = saw();
+    let tables = [saw()];
+    let mut wt = Unison::<Wavetable>::new(SAMPLERATE);
+    let mut vector = Unison::<VectorOscillator>::new(SAMPLERATE as usize);
+    for _ in 0..100 {
+      wt.play::<Linear>(&table, 100.0, 0.0);
+      vector.play::<512, Linear>(&tables, 100.0, 0.0, 0.0);
+    }
+    // restarting twice must not add up the start phases
+    for _ in 0..2 {
+      wt.set_voices(4);
+      wt.set_start_phase(StartPhase::Fixed(0.25));
+      wt.reset();
+      vector.set_voices(4);
+      vector.set_start_phase(StartPhase::Fixed(0.25));
+      vector.reset();
+    }
+    let mut single = Wavetable::new();
+    single.set_samplerate(SAMPLERATE);
+    single.reset(0.25);
+    let mut single_vector = VectorOscillator::new(SAMPLERATE as usize);
+    single_vector.reset(0.25);
+    // without detune all voices play the same samples
+    let gain: f32 = wt.gains[..4].iter().map(|g| g.0).sum();
+    for n in 0..100 {
+      let (l, _) = wt.play::<Linear>(&table, 100.0, 0.0);
+      assert!((l - single.play::<Linear>(&table, 100.0, 0.0) * gain).abs() < 1e-5, "{n}");
+      let (l, _) = vector.play::<512, Linear>(&tables, 100.0, 0.0, 0.0);
+      assert!((l - single_vector.play::<512, Linear>(&tables, 100.0, 0.0, 0.0) * gain).abs() < 1e-5, "{n}");
+    }
+  }
+}
@@ -1,4 +1,4 @@
-use crate::interpolation::Interpolation;
+use crate::{interpolation::Interpolation, smooth::{Multiplicative, SmoothedValue}};
 
 /// Interpolating oscillator
 ///
@@ -7,22 +7,35 @@ use crate::interpolation::Interpolation;
 /// of type `trait Interpolation`
 pub struct VectorOscillator {
   table_pos: f32,
+  frequency: SmoothedValue<Multiplicative>,
   samplerate: usize,
   sr_recip: f32,
+  wrap: Option<f32>,
+  /// Wrap after the last read, reported with the next sample
+  next_wrap: Option<f32>,
+  /// Phase to restart at on the next `play`, once the table size is known
+  reset: Option<f32>,
 }
 
 impl VectorOscillator {
   pub fn new(samplerate: usize) -> Self {
     Self {
       table_pos: 0.0,
+      frequency: SmoothedValue::new(samplerate as u32, 0.0),
       samplerate,
       sr_recip: 1.0 / samplerate as f32,
+      wrap: None,
+      next_wrap: None,
+      reset: None,
     }
   }
 
   pub fn play<const LENGTH: usize, T: Interpolation>(&mut self, tables: &[[f32; LENGTH]], frequency: f32, position: f32, phase: f32) -> f32 {
+    self.wrap = self.next_wrap.take();
+    let frequency = self.frequency.process(frequency);
     if frequency as usize > (self.samplerate >> 1) {return 0.0}
     let len = LENGTH as f32;
+    if let Some(phase) = self.reset.take() { self.table_pos = phase * len; }
     let width = tables.len();
     let position = if position >= 1.0 {0.99999999999999} else {position};
     let position = position * (width as f32 - 1.0);
@@ -34,20 +47,51 @@ impl VectorOscillator {
       T::interpolate(self.table_pos, &tables[t2], LENGTH) * x
     };
 
-    self.table_pos += (len * self.sr_recip * frequency) + (phase * len);
-    while self.table_pos as usize > LENGTH { self.table_pos -= len; }
-    while self.table_pos < 0.0 { self.table_pos += len; }
+    self.advance((len * self.sr_recip * frequency) + (phase * len), LENGTH);
     sig
   }
 
+  fn advance(&mut self, increment: f32, length: usize) {
+    let len = length as f32;
+    self.table_pos += increment;
+    if self.table_pos >= len && increment > 0.0 {
+      self.next_wrap = Some(f32::min((self.table_pos - len) / increment, 1.0 - f32::EPSILON));
+    }
+    while self.table_pos >= len { self.table_pos -= len; }
+    while self.table_pos < 0.0 { self.table_pos += len; }
+  }
+
+  /// Samples since the phase wrapped before the last sample, `0.0..1.0`,
+  /// to sync a [`SyncOscillator`](crate::sync::SyncOscillator).
+  #[inline]
+  pub fn sync(&self) -> Option<f32> {
+    self.wrap
+  }
+
+  /// Restart at `phase`, `0.0..1.0`, from the next `play`.
+  pub fn reset(&mut self, phase: f32) {
+    self.reset = Some(phase.rem_euclid(1.0));
+    self.next_wrap = None;
+  }
+
+  /// Time in seconds to glide between frequencies, `0.0` (default) is
+  /// immediate. Frequencies need to be above zero to glide.
+  pub fn set_smoothing(&mut self, seconds: f32) {
+    self.frequency.set_time(seconds);
+  }
+
   pub fn set_samplerate(&mut self, samplerate: usize) {
     self.samplerate = samplerate;
     self.sr_recip = 1.0 / samplerate as f32;
+    self.frequency.set_samplerate(samplerate as u32);
   }
 
   pub fn play_linear<const LENGTH: usize>(&mut self, tables: &[[f32; LENGTH]], frequency: f32, position: f32, phase: f32) -> f32 {
+    self.wrap = self.next_wrap.take();
+    let frequency = self.frequency.process(frequency);
     if frequency as usize > (self.samplerate >> 1) {return 0.0}
     let len = LENGTH as f32;
+    if let Some(phase) = self.reset.take() { self.table_pos = phase * len; }
     let width = tables.len();
 
     let position = if position >= 1.0 {0.99999999999999} else {position};
@@ -66,9 +110,7 @@ impl VectorOscillator {
     let diff1 = b - a;
     let diff2 = x*(diff1 - d + c);
     let sig = a + x * diff1 + y * (c - a * diff2);
-    self.table_pos += (len * self.sr_recip * frequency) + (phase * len);
-    while self.table_pos as usize > LENGTH { self.table_pos -= len; }
-    while self.table_pos < 0.0 { self.table_pos += len; }
+    self.advance((len * self.sr_recip * frequency) + (phase * len), LENGTH);
     sig
   }
 }
@@ -92,4 +134,20 @@ mod tests {
 
     assert_eq!(16, shape.len())
   }
+
+  #[test]
+  fn frequency_glides() {
+    const SIZE: usize = 512;
+    let tables = [core::array::from_fn::<f32, SIZE, _>(|i| i as f32 / SIZE as f32)];
+    let mut vc = VectorOscillator::new(48000);
+    vc.set_smoothing(0.01);
+    vc.play::<SIZE, Linear>(&tables, 100.0, 0.0, 0.0);
+    let start = vc.table_pos;
+    vc.play::<SIZE, Linear>(&tables, 1000.0, 0.0, 0.0);
+    // still moving at about the old rate
+    let step = vc.table_pos - start;
+    assert!(step < SIZE as f32 * 200.0 / 48000.0, "{step}");
+    for _ in 0..480 { vc.play::<SIZE, Linear>(&tables, 1000.0, 0.0, 0.0); }
+    assert_eq!(1000.0, vc.frequency.value());
+  }
 }
@@ -1,11 +1,23 @@
 use crate::interpolation::Interpolation;
-use core::{f32::consts::SQRT_2, ops::{Add, AddAssign}};
+use core::ops::{Add, AddAssign, Mul, Sub};
 
 
-#[derive(Clone, Copy)]
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
 pub struct Coord {
-  x: f32,
-  y: f32
+  pub x: f32,
+  pub y: f32
+}
+
+impl Coord {
+  pub fn new(x: f32, y: f32) -> Self {
+    Self { x, y }
+  }
+
+  /// Euclidean distance to `other`
+  #[inline]
+  pub fn distance(&self, other: &Coord) -> f32 {
+    f32::hypot(self.x - other.x, self.y - other.y)
+  }
 }
 
 impl Add for Coord {
@@ -25,25 +37,97 @@ impl AddAssign for Coord {
   }
 }
 
+impl Sub for Coord {
+  type Output = Coord;
+  fn sub(self, rhs: Self) -> Self::Output {
+    Self::Output{
+      x: self.x - rhs.x,
+      y: self.y - rhs.y
+    }
+  }
+}
+
+impl Mul<f32> for Coord {
+  type Output = Coord;
+  fn mul(self, rhs: f32) -> Self::Output {
+    Self::Output{
+      x: self.x * rhs,
+      y: self.y * rhs
+    }
+  }
+}
+
 impl From<(f32, f32)> for Coord {
   fn from(value: (f32, f32)) -> Self {
     Self { x: value.0, y: value.1 }
   }
 }
 
-type Vector = Coord;
+/// Direction and speed of movement, in units per second
+pub type Vector = Coord;
 
+/// Single cycle table placed in the plane
 pub struct Table2D<const LENGTH: usize> {
   table: [f32; LENGTH],
   coords: Coord,
 }
 
+impl<const LENGTH: usize> Table2D<LENGTH> {
+  pub fn new(table: [f32; LENGTH], coords: Coord) -> Self {
+    Self { table, coords }
+  }
+
+  pub fn table(&self) -> &[f32; LENGTH] {
+    &self.table
+  }
+
+  pub fn table_mut(&mut self) -> &mut [f32; LENGTH] {
+    &mut self.table
+  }
+
+  pub fn coords(&self) -> Coord {
+    self.coords
+  }
+
+  pub fn set_coords(&mut self, coords: Coord) {
+    self.coords = coords;
+  }
+}
+
+/// Weight of a table by its distance from the oscillator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Weighting {
+  /// Falls linearly from 1.0 on the table to 0.0 at the radius
+  Linear,
+  /// `1 / distance^power`, a table under the oscillator plays alone
+  InverseDistance(f32),
+}
+
+/// What happens when the oscillator moves past the bounds
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Boundary {
+  /// Reappear at the opposite edge
+  #[default] Wrap,
+  /// Reflect off the edge, reversing direction
+  Bounce,
+  /// Stop at the edge
+  Clamp,
+}
+
+/// Oscillator moving through a plane of tables.
+///
+/// Tables within `radius` of the position are weighted by their distance
+/// and blended, the weights are normalised to sum to 1.0.
 pub struct VectorOscillator2D {
   samplerate: f32,
   sr_recip: f32,
   table_pos: f32,
   coords: Coord,
-  direction: Vector
+  direction: Vector,
+  weighting: Weighting,
+  boundary: Boundary,
+  min: Coord,
+  max: Coord,
 }
 
 impl VectorOscillator2D {
@@ -53,8 +137,12 @@ impl VectorOscillator2D {
       sr_recip: 1.0/samplerate,
       table_pos: 0.0,
       coords: start_position,
-      direction: Vector{x: 0.0, y: 0.0} 
-    } 
+      direction: Vector{x: 0.0, y: 0.0},
+      weighting: Weighting::Linear,
+      boundary: Boundary::Wrap,
+      min: Coord::new(f32::NEG_INFINITY, f32::NEG_INFINITY),
+      max: Coord::new(f32::INFINITY, f32::INFINITY),
+    }
   }
 
   /// Produce the next sample from the VectorOscillator2D
@@ -63,59 +151,209 @@ impl VectorOscillator2D {
   /// - radius: the cutoff radius from [`VectorOscillator2D`] interpolating between [`Table2D`] structs.
   ///
   /// ```
-  /// // ex:
-  // // setup
-  // const SIZE: usize = 512;
-  // let osc = VectorOscillator2D::new(48000.0, (0.0,0.0).into());
-  // let tables = [Table2D{(1.0, 3.5).into(), [0.0, 512].sine()}];
-  // // constant size of table, &impl Interpolation
-  // let sample = osc.play::<SIZE, Linear>(&tables, 100.0, 2.5, 0.0);
-  // ```
-  //
+  /// use rust_dsp::{interpolation::linear::Linear, vector2D::{Table2D, VectorOscillator2D}, waveshape::traits::Waveshape};
+  ///
+  /// const SIZE: usize = 512;
+  /// let mut osc = VectorOscillator2D::new(48000.0, (0.5, 0.5).into());
+  /// let tables = [
+  ///   Table2D::new([0.0; SIZE].sine(), (0.0, 0.0).into()),
+  ///   Table2D::new([0.0; SIZE].sawtooth(), (1.0, 1.0).into()),
+  /// ];
+  /// osc.set_vector((0.1, 0.05).into());
+  /// let sample = osc.play::<SIZE, Linear>(&tables, 100.0, 1.0, 0.0);
+  /// ```
   pub fn play<const LENGTH: usize, T: Interpolation>(&mut self,
     tables: &[Table2D<LENGTH>],
-    frequency: f32, 
+    frequency: f32,
     radius: f32,
     phase: f32
-  ) -> f32 { 
+  ) -> f32 {
     if frequency > self.samplerate * 0.5 {return 0.0}
     let len = LENGTH as f32;
 
-    let mut sig = 0.0;
+    // Increment frequency
+    self.table_pos = (self.table_pos + len * self.sr_recip * frequency).rem_euclid(len);
+    let pos = (self.table_pos + phase * len).rem_euclid(len);
 
+    let mut sig = 0.0;
+    let mut total = 0.0;
     for t in tables.iter() {
-      let distance = self.convert_manhattan(&t.coords);
-      if distance <= radius {
-        // Simple addition of nearby tables. 
-        // Instead of linear ratio between radius and distance, perhaps 
-        sig += {
-          T::interpolate(self.table_pos, &t.table, LENGTH)
-          * (1.0 - (distance / radius))
+      let distance = self.coords.distance(&t.coords);
+      if distance > radius { continue; }
+      let weight = match self.weighting {
+        Weighting::Linear => if radius > 0.0 { 1.0 - distance / radius } else { 1.0 },
+        Weighting::InverseDistance(power) => {
+          if distance <= f32::EPSILON {
+            sig = T::interpolate(pos, &t.table, LENGTH);
+            total = 1.0;
+            break;
+          }
+          distance.powf(-power)
         }
-      }
+      };
+      sig += T::interpolate(pos, &t.table, LENGTH) * weight;
+      total += weight;
     }
 
-    // Increment frequency and 2D position
-    self.table_pos += (len * self.sr_recip * frequency) + (phase * len);
-    while self.table_pos as usize > LENGTH { self.table_pos -= len; }
-    while self.table_pos < 0.0 { self.table_pos += len; }
-    self.coords += self.direction;
-
-    sig
+    self.advance();
+    if total > 0.0 { sig / total } else { 0.0 }
   }
 
-  /// calculate the hypotenuse from [`Coord`] in [`VectorOscillator2D`] and [`Table2D`]
-  #[inline]
-  fn convert_manhattan(&self, table_pos: &Coord) -> f32 {
-    let x_diff = f32::abs(self.coords.x - table_pos.x);
-    let y_diff = f32::abs(self.coords.y - table_pos.y);
-    // tiny edge case
-    if x_diff - y_diff <= f32::EPSILON { return x_diff * SQRT_2; }
-    f32::sqrt(x_diff * x_diff * y_diff * y_diff)
+  /// Move the position by one sample of the direction vector
+  fn advance(&mut self) {
+    if self.direction == Vector::default() { return; }
+    self.coords += self.direction * self.sr_recip;
+    let (min, max) = (self.min, self.max);
+    let (coords, direction) = (&mut self.coords, &mut self.direction);
+    for (c, d, lo, hi) in [
+      (&mut coords.x, &mut direction.x, min.x, max.x),
+      (&mut coords.y, &mut direction.y, min.y, max.y),
+    ] {
+      if *c >= lo && *c <= hi { continue; }
+      let size = hi - lo;
+      if size <= 0.0 { *c = lo; continue; }
+      match self.boundary {
+        Boundary::Wrap => *c = lo + (*c - lo).rem_euclid(size),
+        Boundary::Clamp => *c = c.clamp(lo, hi),
+        Boundary::Bounce => {
+          // an odd number of reflections reverses the direction
+          let reflections = ((*c - lo) / size).floor() as i32;
+          if reflections % 2 != 0 { *d = -*d; }
+          // fold into a period of twice the size
+          let folded = (*c - lo).rem_euclid(2.0 * size);
+          *c = if folded > size { lo + 2.0 * size - folded } else { lo + folded };
+        }
+      }
+    }
   }
 
-  /// Change direction vector
+  /// Change direction vector, in units per second
   pub fn set_vector(&mut self, vector: Vector) {
     self.direction = vector;
   }
+
+  pub fn vector(&self) -> Vector {
+    self.direction
+  }
+
+  pub fn position(&self) -> Coord {
+    self.coords
+  }
+
+  pub fn set_position(&mut self, position: Coord) {
+    self.coords = position;
+  }
+
+  pub fn set_weighting(&mut self, weighting: Weighting) {
+    self.weighting = weighting;
+  }
+
+  pub fn set_boundary(&mut self, boundary: Boundary) {
+    self.boundary = boundary;
+  }
+
+  /// Area the oscillator moves in, unbounded by default so the boundary
+  /// has no effect until bounds are set
+  pub fn set_bounds(&mut self, min: Coord, max: Coord) {
+    self.min = min;
+    self.max = max;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: f32) {
+    self.samplerate = samplerate;
+    self.sr_recip = 1.0/samplerate;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::interpolation::Linear;
+
+  const SAMPLERATE: f32 = 48000.0;
+
+  fn tables() -> [Table2D<4>; 2] {
+    [
+      Table2D::new([1.0; 4], (0.0, 0.0).into()),
+      Table2D::new([-1.0; 4], (3.0, 4.0).into()),
+    ]
+  }
+
+  #[test]
+  fn euclidean_distance() {
+    assert_eq!(5.0, Coord::new(0.0, 0.0).distance(&Coord::new(3.0, 4.0)));
+  }
+
+  #[test]
+  fn weights_are_normalised() {
+    let tables = tables();
+    let mut osc = VectorOscillator2D::new(SAMPLERATE, (0.0, 0.0).into());
+    // only the first table is within the radius
+    assert_eq!(1.0, osc.play::<4, Linear>(&tables, 100.0, 1.0, 0.0));
+    // halfway, both weigh 0.5
+    osc.set_position((1.5, 2.0).into());
+    assert!(osc.play::<4, Linear>(&tables, 100.0, 10.0, 0.0).abs() < 1e-6);
+    // a fifth of the way
+    osc.set_position((0.6, 0.8).into());
+    let out = osc.play::<4, Linear>(&tables, 100.0, 10.0, 0.0);
+    assert!((out - (0.9 - 0.6) / 1.5).abs() < 1e-5, "{out}");
+    assert_eq!(0.0, osc.play::<4, Linear>(&tables, 100.0, 0.5, 0.0));
+  }
+
+  #[test]
+  fn inverse_distance() {
+    let tables = tables();
+    let mut osc = VectorOscillator2D::new(SAMPLERATE, (0.6, 0.8).into());
+    osc.set_weighting(Weighting::InverseDistance(1.0));
+    // distances 1 and 4
+    let out = osc.play::<4, Linear>(&tables, 100.0, 10.0, 0.0);
+    assert!((out - (1.0 - 0.25) / 1.25).abs() < 1e-5, "{out}");
+    osc.set_position((3.0, 4.0).into());
+    assert_eq!(-1.0, osc.play::<4, Linear>(&tables, 100.0, 10.0, 0.0));
+  }
+
+  #[test]
+  fn movement_is_samplerate_independent() {
+    for samplerate in [44100.0, 96000.0] {
+      let mut osc = VectorOscillator2D::new(samplerate, (0.0, 0.5).into());
+      osc.set_vector((0.25, 0.0).into());
+      for _ in 0..samplerate as usize { osc.play::<4, Linear>(&tables(), 100.0, 1.0, 0.0); }
+      assert!((osc.position().x - 0.25).abs() < 1e-3, "{samplerate}");
+    }
+  }
+
+  #[test]
+  fn unbounded_by_default() {
+    let mut osc = VectorOscillator2D::new(SAMPLERATE, (1.0, 3.5).into());
+    osc.play::<4, Linear>(&tables(), 100.0, 2.5, 0.0);
+    assert_eq!(Coord::new(1.0, 3.5), osc.position());
+    osc.set_vector((-1.0, 1.0).into());
+    for _ in 0..SAMPLERATE as usize { osc.play::<4, Linear>(&tables(), 100.0, 2.5, 0.0); }
+    let position = osc.position();
+    assert!(position.x.abs() < 1e-2 && (position.y - 4.5).abs() < 1e-2, "{position:?}");
+  }
+
+  #[test]
+  fn boundaries() {
+    let mut osc = VectorOscillator2D::new(10.0, (0.9, 0.5).into());
+    osc.set_bounds((0.0, 0.0).into(), (1.0, 1.0).into());
+    osc.set_vector((2.0, 0.0).into());
+    osc.play::<4, Linear>(&tables(), 1.0, 1.0, 0.0);
+    assert!((osc.position().x - 0.1).abs() < 1e-5);
+
+    osc.set_boundary(Boundary::Bounce);
+    osc.set_position((0.9, 0.5).into());
+    osc.play::<4, Linear>(&tables(), 1.0, 1.0, 0.0);
+    assert!((osc.position().x - 0.9).abs() < 1e-5);
+    assert_eq!(-2.0, osc.vector().x);
+    osc.set_position((0.1, 0.5).into());
+    osc.play::<4, Linear>(&tables(), 1.0, 1.0, 0.0);
+    assert!((osc.position().x - 0.1).abs() < 1e-5);
+    assert_eq!(2.0, osc.vector().x);
+
+    osc.set_boundary(Boundary::Clamp);
+    osc.set_position((0.9, 0.5).into());
+    osc.play::<4, Linear>(&tables(), 1.0, 1.0, 0.0);
+    assert_eq!(1.0, osc.position().x);
+  }
 }
@@ -0,0 +1,436 @@
+//! Voice allocation for polyphonic and monophonic instruments.
+
+use alloc::vec::Vec;
+use crate::{midi::MidiEvent, tuning::Tuning};
+
+/// A sound generator played by a [`VoiceManager`].
+pub trait Voice {
+  /// Start a note. `velocity` is `0.0..=1.0`.
+  fn note_on(&mut self, note: u8, velocity: f32, frequency: f32);
+  /// Move to another note without restarting envelopes, used in
+  /// [`VoiceMode::Legato`]. Defaults to restarting at full velocity.
+  fn legato(&mut self, note: u8, frequency: f32) {
+    self.note_on(note, 1.0, frequency);
+  }
+  /// Release the note, the voice may keep sounding while it fades out.
+  fn note_off(&mut self);
+  /// `false` once the voice is silent and free to reuse.
+  fn is_active(&self) -> bool;
+  fn render(&mut self) -> f32;
+  /// Current loudness, used by [`StealPolicy::Quietest`].
+  fn level(&self) -> f32 {
+    1.0
+  }
+}
+
+/// Which voice to take when all are in use. Voices that are already
+/// released are always taken before held ones.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum StealPolicy {
+  /// The longest playing note
+  #[default] Oldest,
+  /// The voice with the lowest [`Voice::level`]
+  Quietest,
+  /// The lowest note, keeping the high notes
+  Lowest,
+  /// The highest note, keeping the low notes
+  Highest,
+  /// Ignore new notes
+  None,
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum VoiceMode {
+  #[default] Poly,
+  /// One voice, restarted on every note change
+  Mono,
+  /// One voice, restarted only when no other note is held
+  Legato,
+}
+
+/// Which of the held notes sounds in mono and legato mode.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum NotePriority {
+  #[default] Last,
+  Low,
+  High,
+}
+
+#[derive(Clone, Copy, Debug, Default)]
+struct Slot {
+  note: u8,
+  held: bool,
+  /// Order of the note on, for stealing the oldest
+  age: u64,
+}
+
+/// Allocates notes to `N` voices, tracked by note number and tuned with a
+/// [`Tuning`].
+///
+/// ```
+/// use rust_dsp::voice::{Voice, VoiceManager, StealPolicy};
+///
+/// #[derive(Default)]
+/// struct Sine { phase: f32, inc: f32, gain: f32 }
+///
+/// impl Voice for Sine {
+///   fn note_on(&mut self, _note: u8, velocity: f32, frequency: f32) {
+///     self.inc = frequency / 48000.0;
+///     self.gain = velocity;
+///   }
+///   fn note_off(&mut self) { self.gain = 0.0; }
+///   fn is_active(&self) -> bool { self.gain > 0.0 }
+///   fn render(&mut self) -> f32 {
+///     self.phase = (self.phase + self.inc).fract();
+///     self.gain * f32::sin(self.phase * core::f32::consts::TAU)
+///   }
+/// }
+///
+/// let mut voices = VoiceManager::<Sine, 8>::new(core::array::from_fn(|_| Sine::default()));
+/// voices.set_steal(StealPolicy::Quietest);
+/// voices.note_on(60, 0.8);
+/// voices.note_on(64, 0.8);
+/// let out = voices.render();
+/// voices.note_off(60);
+/// ```
+pub struct VoiceManager<V: Voice, const N: usize> {
+  voices: [V; N],
+  slots: [Slot; N],
+  tuning: Tuning,
+  mode: VoiceMode,
+  priority: NotePriority,
+  steal: StealPolicy,
+  retrigger: bool,
+  counter: u64,
+  /// Held notes and their velocity in mono and legato mode, in the order
+  /// they were played
+  stack: Vec<(u8, f32)>,
+  /// Note sounding in mono and legato mode
+  mono_note: Option<u8>,
+}
+
+impl<V: Voice, const N: usize> VoiceManager<V, N> {
+  /// Poly mode with oldest note stealing, in the default tuning.
+  pub fn new(voices: [V; N]) -> Self {
+    Self {
+      voices,
+      slots: [Slot::default(); N],
+      tuning: Tuning::default(),
+      mode: VoiceMode::Poly,
+      priority: NotePriority::Last,
+      steal: StealPolicy::Oldest,
+      retrigger: true,
+      counter: 0,
+      stack: Vec::with_capacity(128),
+      mono_note: None,
+    }
+  }
+
+  pub fn note_on(&mut self, note: u8, velocity: f32) {
+    if note >= 128 { return }
+    if self.mode == VoiceMode::Poly {
+      self.poly_note_on(note, velocity);
+    } else {
+      self.stack.retain(|(n, _)| *n != note);
+      self.stack.push((note, velocity));
+      self.update_mono();
+    }
+  }
+
+  pub fn note_off(&mut self, note: u8) {
+    if self.mode == VoiceMode::Poly {
+      for (slot, voice) in self.slots.iter_mut().zip(self.voices.iter_mut()) {
+        if slot.held && slot.note == note {
+          slot.held = false;
+          voice.note_off();
+        }
+      }
+    } else {
+      self.stack.retain(|(n, _)| *n != note);
+      self.update_mono();
+    }
+  }
+
+  /// Release all voices.
+  pub fn all_notes_off(&mut self) {
+    self.stack.clear();
+    self.mono_note = None;
+    for (slot, voice) in self.slots.iter_mut().zip(self.voices.iter_mut()) {
+      if slot.held {
+        slot.held = false;
+        voice.note_off();
+      }
+    }
+  }
+
+  /// Play note on and off events, all notes off (CC 123) releases all
+  /// voices.
+  pub fn process(&mut self, event: &MidiEvent) {
+    match *event {
+      MidiEvent::NoteOn { note, velocity, .. } if velocity > 0 => self.note_on(note, velocity as f32 / 127.0),
+      MidiEvent::NoteOn { note, .. } | MidiEvent::NoteOff { note, .. } => self.note_off(note),
+      MidiEvent::ControlChange { controller: 123, .. } => self.all_notes_off(),
+      _ => (),
+    }
+  }
+
+  /// Sum of the active voices.
+  #[inline]
+  pub fn render(&mut self) -> f32 {
+    self.voices.iter_mut().filter(|v| v.is_active()).map(|v| v.render()).sum()
+  }
+
+  fn poly_note_on(&mut self, note: u8, velocity: f32) {
+    let Some(frequency) = self.tuning.frequency(note) else { return };
+    let same = self.slots.iter().zip(&self.voices)
+      .position(|(s, v)| self.retrigger && s.note == note && (s.held || v.is_active()));
+    let free = || self.voices.iter().zip(&self.slots)
+      .enumerate()
+      .filter(|(_, (v, _))| !v.is_active())
+      .min_by_key(|(_, (_, s))| s.age)
+      .map(|(i, _)| i);
+    let Some(i) = same.or_else(free).or_else(|| self.steal()) else { return };
+    self.counter += 1;
+    self.slots[i] = Slot { note, held: true, age: self.counter };
+    self.voices[i].note_on(note, velocity, frequency);
+  }
+
+  /// Voice to take when all are active, released voices first.
+  fn steal(&self) -> Option<usize> {
+    let released = self.slots.iter().any(|s| !s.held);
+    let candidates = self.slots.iter().enumerate().filter(|(_, s)| !released || !s.held);
+    let key = |i: usize, s: &Slot| -> f32 {
+      match self.steal {
+        StealPolicy::Oldest | StealPolicy::None => s.age as f32,
+        StealPolicy::Quietest => self.voices[i].level(),
+        StealPolicy::Lowest => s.note as f32,
+        StealPolicy::Highest => -(s.note as f32),
+      }
+    };
+    if self.steal == StealPolicy::None && !released { return None; }
+    candidates.min_by(|(i, a), (j, b)| key(*i, a).total_cmp(&key(*j, b))).map(|(i, _)| i)
+  }
+
+  /// Play the priority note of the stack on the first voice.
+  fn update_mono(&mut self) {
+    let next = match self.priority {
+      NotePriority::Last => self.stack.last(),
+      NotePriority::Low => self.stack.iter().min_by_key(|(n, _)| *n),
+      NotePriority::High => self.stack.iter().max_by_key(|(n, _)| *n),
+    }.copied();
+    let Some(voice) = self.voices.first_mut() else { return };
+    match next {
+      None => {
+        if self.mono_note.take().is_some() {
+          self.slots[0].held = false;
+          voice.note_off();
+        }
+      }
+      Some((note, velocity)) => {
+        if self.mono_note == Some(note) { return; }
+        let Some(frequency) = self.tuning.frequency(note) else { return };
+        if self.mode == VoiceMode::Legato && self.mono_note.is_some() {
+          voice.legato(note, frequency);
+        } else {
+          voice.note_on(note, velocity, frequency);
+        }
+        self.mono_note = Some(note);
+        self.counter += 1;
+        self.slots[0] = Slot { note, held: true, age: self.counter };
+      }
+    }
+  }
+
+  /// Voices in allocation order, to set their parameters.
+  pub fn voices_mut(&mut self) -> &mut [V; N] {
+    &mut self.voices
+  }
+
+  pub fn voices(&self) -> &[V; N] {
+    &self.voices
+  }
+
+  /// Number of voices still sounding
+  pub fn active_voices(&self) -> usize {
+    self.voices.iter().filter(|v| v.is_active()).count()
+  }
+
+  /// Switching mode releases all voices.
+  pub fn set_mode(&mut self, mode: VoiceMode) {
+    if mode != self.mode { self.all_notes_off(); }
+    self.mode = mode;
+  }
+
+  pub fn set_priority(&mut self, priority: NotePriority) {
+    self.priority = priority;
+    if self.mode != VoiceMode::Poly { self.update_mono(); }
+  }
+
+  pub fn set_steal(&mut self, steal: StealPolicy) {
+    self.steal = steal;
+  }
+
+  /// Replay a note on the voice already playing it, instead of taking
+  /// another voice. Enabled by default.
+  pub fn set_retrigger(&mut self, retrigger: bool) {
+    self.retrigger = retrigger;
+  }
+
+  /// Applies from the next note.
+  pub fn set_tuning(&mut self, tuning: Tuning) {
+    self.tuning = tuning;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use alloc::vec;
+
+  #[derive(Default)]
+  struct TestVoice {
+    note: u8,
+    velocity: f32,
+    held: bool,
+    /// Samples of release left
+    release: u32,
+    legatos: u32,
+  }
+
+  impl Voice for TestVoice {
+    fn note_on(&mut self, note: u8, velocity: f32, _frequency: f32) {
+      self.note = note;
+      self.velocity = velocity;
+      self.held = true;
+      self.release = 2;
+    }
+    fn legato(&mut self, note: u8, _frequency: f32) {
+      self.note = note;
+      self.legatos += 1;
+    }
+    fn note_off(&mut self) {
+      self.held = false;
+    }
+    fn is_active(&self) -> bool {
+      self.held || self.release > 0
+    }
+    fn render(&mut self) -> f32 {
+      if !self.held { self.release = self.release.saturating_sub(1); }
+      self.velocity
+    }
+    fn level(&self) -> f32 {
+      self.velocity
+    }
+  }
+
+  fn manager<const N: usize>() -> VoiceManager<TestVoice, N> {
+    VoiceManager::new(core::array::from_fn(|_| TestVoice::default()))
+  }
+
+  fn notes<const N: usize>(vm: &VoiceManager<TestVoice, N>) -> Vec<u8> {
+    vm.voices().iter().map(|v| if v.held { v.note } else { 0 }).collect()
+  }
+
+  #[test]
+  fn allocates_free_voices() {
+    let mut vm = manager::<3>();
+    vm.note_on(60, 1.0);
+    vm.note_on(64, 1.0);
+    assert_eq!(vec![60, 64, 0], notes(&vm));
+    vm.note_off(60);
+    assert_eq!(2, vm.active_voices());
+    // the released voice fades out before it is reused
+    vm.note_on(67, 1.0);
+    assert_eq!(vec![0, 64, 67], notes(&vm));
+    (0..2).for_each(|_| { vm.render(); });
+    vm.note_on(69, 1.0);
+    assert_eq!(vec![69, 64, 67], notes(&vm));
+  }
+
+  #[test]
+  fn same_note_retrigger() {
+    let mut vm = manager::<2>();
+    vm.note_on(60, 1.0);
+    vm.note_on(60, 0.5);
+    assert_eq!(vec![60, 0], notes(&vm));
+    assert_eq!(0.5, vm.voices()[0].velocity);
+    vm.set_retrigger(false);
+    vm.note_on(60, 1.0);
+    assert_eq!(vec![60, 60], notes(&vm));
+  }
+
+  #[test]
+  fn steal_policies() {
+    let cases = [
+      (StealPolicy::Oldest, vec![72, 60, 67]),
+      (StealPolicy::Quietest, vec![64, 72, 67]),
+      (StealPolicy::Lowest, vec![64, 72, 67]),
+      (StealPolicy::Highest, vec![64, 60, 72]),
+      (StealPolicy::None, vec![64, 60, 67]),
+    ];
+    for (policy, expected) in cases {
+      let mut vm = manager::<3>();
+      vm.set_steal(policy);
+      vm.note_on(64, 1.0);
+      vm.note_on(60, 0.2);
+      vm.note_on(67, 1.0);
+      vm.note_on(72, 1.0);
+      assert_eq!(expected, notes(&vm), "{policy:?}");
+    }
+  }
+
+  #[test]
+  fn steals_released_first() {
+    let mut vm = manager::<2>();
+    vm.set_steal(StealPolicy::None);
+    vm.note_on(60, 1.0);
+    vm.note_on(64, 1.0);
+    vm.note_off(64);
+    vm.note_on(67, 1.0);
+    assert_eq!(vec![60, 67], notes(&vm));
+  }
+
+  #[test]
+  fn mono_priority() {
+    for (priority, playing) in [(NotePriority::Last, [60, 67, 64]), (NotePriority::Low, [60, 60, 60]), (NotePriority::High, [60, 67, 67])] {
+      let mut vm = manager::<1>();
+      vm.set_mode(VoiceMode::Mono);
+      vm.set_priority(priority);
+      let mut heard = Vec::new();
+      for note in [60, 67, 64] {
+        vm.note_on(note, 1.0);
+        heard.push(vm.voices()[0].note);
+      }
+      assert_eq!(playing.to_vec(), heard, "{priority:?}");
+    }
+  }
+
+  #[test]
+  fn mono_returns_to_held_note() {
+    let mut vm = manager::<1>();
+    vm.set_mode(VoiceMode::Legato);
+    vm.note_on(60, 1.0);
+    vm.note_on(62, 1.0);
+    assert_eq!(62, vm.voices()[0].note);
+    vm.note_off(62);
+    assert_eq!(60, vm.voices()[0].note);
+    assert!(vm.voices()[0].held);
+    assert_eq!(2, vm.voices()[0].legatos);
+    vm.note_off(60);
+    assert!(!vm.voices()[0].held);
+    // a new phrase restarts the voice
+    vm.note_on(64, 0.3);
+    assert_eq!(0.3, vm.voices()[0].velocity);
+  }
+
+  #[test]
+  fn midi_events() {
+    let mut vm = manager::<4>();
+    vm.process(&MidiEvent::NoteOn { channel: 0, note: 60, velocity: 127 });
+    vm.process(&MidiEvent::NoteOn { channel: 0, note: 64, velocity: 127 });
+    vm.process(&MidiEvent::NoteOn { channel: 0, note: 60, velocity: 0 });
+    assert_eq!(vec![0, 64, 0, 0], notes(&vm));
+    vm.process(&MidiEvent::ControlChange { channel: 0, controller: 123, value: 0 });
+    assert_eq!(vec![0, 0, 0, 0], notes(&vm));
+  }
+}
@@ -0,0 +1,163 @@
+use core::f32::consts::TAU;
+
+/// Analog style oscillator morphing sine, triangle, saw and square.
+///
+/// `shape` runs from `0.0` sine over `1/3` triangle and `2/3` saw to `1.0`
+/// square, crossfading neighbouring waveforms. All waveforms cross zero
+/// rising at the start of the cycle, the steps of saw and square are
+/// smoothed with polyBLEPs.
+///
+/// ```
+/// use rust_dsp::vshape::VariableShape;
+///
+/// let mut osc = VariableShape::new(48000);
+/// osc.set_shape(0.5);
+/// let out = osc.play(110.0, 0.0);
+/// ```
+#[derive(Clone, Copy, Debug)]
+pub struct VariableShape {
+  position: f32,
+  shape: f32,
+  width: f32,
+  sr_recip: f32,
+}
+
+impl VariableShape {
+  pub fn new(samplerate: u32) -> Self {
+    Self {
+      position: 0.0,
+      shape: 0.0,
+      width: 0.5,
+      sr_recip: 1.0 / samplerate as f32,
+    }
+  }
+
+  #[inline]
+  pub fn play(&mut self, frequency: f32, phase: f32) -> f32 {
+    let dt = (frequency * self.sr_recip).abs().min(0.5);
+    self.position = (self.position + frequency * self.sr_recip).rem_euclid(1.0);
+    let p = (self.position + phase).rem_euclid(1.0);
+
+    let morph = self.shape * 3.0;
+    let segment = (morph.floor() as usize).min(2);
+    let x = morph - segment as f32;
+    let wave = |n: usize| match n {
+      0 => f32::sin(TAU * p),
+      1 => 1.0 - f32::abs(4.0 * (p + 0.25).fract() - 2.0),
+      2 => {
+        let saw = 2.0 * (p + 0.5).fract() - 1.0;
+        saw - poly_blep((p + 0.5).fract(), dt)
+      }
+      _ => {
+        let square = if p < self.width { 1.0 } else { -1.0 };
+        square + poly_blep(p, dt) - poly_blep((p - self.width).rem_euclid(1.0), dt)
+      }
+    };
+    let a = wave(segment);
+    if x > 0.0 { a + (wave(segment + 1) - a) * x } else { a }
+  }
+
+  /// Waveform, `0.0..=1.0`.
+  pub fn set_shape(&mut self, shape: f32) {
+    self.shape = shape.clamp(0.0, 1.0);
+  }
+
+  /// Width of the square, `0.0..1.0`.
+  pub fn set_pulse_width(&mut self, width: f32) {
+    self.width = width.clamp(0.01, 0.99);
+  }
+
+  /// Restart at `phase`, `0.0..1.0`.
+  pub fn reset(&mut self, phase: f32) {
+    self.position = phase.rem_euclid(1.0);
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.sr_recip = 1.0 / samplerate as f32;
+  }
+}
+
+/// Residual of a rising step from -1.0 to 1.0 at phase `0.0`, for an
+/// increment of `dt`.
+#[inline]
+fn poly_blep(phase: f32, dt: f32) -> f32 {
+  if dt <= 0.0 {
+    0.0
+  } else if phase < dt {
+    let t = phase / dt;
+    t + t - t * t - 1.0
+  } else if phase > 1.0 - dt {
+    let t = (phase - 1.0) / dt;
+    t * t + t + t + 1.0
+  } else {
+    0.0
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+
+  const SAMPLERATE: u32 = 48000;
+
+  fn cycle(shape: f32) -> Vec<f32> {
+    let mut osc = VariableShape::new(SAMPLERATE);
+    osc.set_shape(shape);
+    (0..480).map(|_| osc.play(100.0, 0.0)).collect()
+  }
+
+  #[test]
+  fn sine() {
+    let out = cycle(0.0);
+    for (n, s) in out.iter().enumerate() {
+      assert!((s - f32::sin(TAU * (n + 1) as f32 / 480.0)).abs() < 1e-3);
+    }
+  }
+
+  #[test]
+  fn triangle() {
+    let out = cycle(1.0 / 3.0);
+    assert!((out[119] - 1.0).abs() < 1e-3);
+    assert!((out[359] + 1.0).abs() < 1e-3);
+    assert!((out[59] - 0.5).abs() < 1e-3);
+  }
+
+  #[test]
+  fn saw_and_square_are_bounded() {
+    for shape in [2.0 / 3.0, 0.8, 1.0] {
+      let out = cycle(shape);
+      assert!(out.iter().all(|s| s.abs() <= 1.0 + 1e-3), "{shape}");
+      assert!(out[1..].iter().any(|s| *s > 0.9) && out.iter().any(|s| *s < -0.9), "{shape}");
+    }
+    let square = cycle(1.0);
+    assert!(square[10..230].iter().all(|s| *s > 0.99));
+    assert!(square[250..470].iter().all(|s| *s < -0.99));
+  }
+
+  #[test]
+  fn saw_step_is_smoothed() {
+    let mut osc = VariableShape::new(SAMPLERATE);
+    osc.set_shape(2.0 / 3.0);
+    // 0.1 cycles per sample, the step at 0.5 lies halfway between samples
+    let out: Vec<f32> = (0..6).map(|_| osc.play(4800.0, 0.05)).collect();
+    // naive 0.9 and -0.9, each pulled 0.25 towards the other
+    assert!((out[3] - 0.65).abs() < 1e-3, "{out:?}");
+    assert!((out[4] + 0.65).abs() < 1e-3, "{out:?}");
+  }
+
+  #[test]
+  fn morph_is_continuous() {
+    let mut osc = VariableShape::new(SAMPLERATE);
+    let mut last = osc.play(100.0, 0.0);
+    for n in 0..3000 {
+      osc.set_shape(n as f32 / 3000.0);
+      let out = osc.play(100.0, 0.0);
+      // small steps away from the saw and square edges
+      let p = osc.position;
+      if (p - 0.5).abs() > 0.01 && p > 0.01 && p < 0.99 {
+        assert!((out - last).abs() < 0.1, "{n}");
+      }
+      last = out;
+    }
+  }
+}
@@ -1,8 +1,19 @@
+//! Wavetable oscillators.
+//!
+//! [`shared::Wavetable`], [`owned::Wavetable`] and [`scan::ScanOscillator`]
+//! advance the position by one sample of the frequency before each read,
+//! and add the `phase` argument of `play` to the read position only, in
+//! cycles, for phase modulation. [`PdOscillator`](crate::pd::PdOscillator),
+//! [`VariableShape`](crate::vshape::VariableShape) and
+//! [`VectorOscillator2D`](crate::vector2D::VectorOscillator2D) handle phase
+//! the same way.
+
 use crate::interpolation::Interpolation;
 use alloc::vec::Vec;
 
 pub mod owned;
 pub mod shared;
+pub mod scan;
 #[cfg(feature="std")]
 pub mod arc;
 
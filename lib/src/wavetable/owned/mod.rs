@@ -1,4 +1,5 @@
 use super::*;
+use crate::smooth::{Multiplicative, SmoothedValue};
 
 /// Wavetable oscillator that owns its table of the wave representation.
 ///
@@ -7,18 +8,22 @@ use super::*;
 /// references.
 pub struct Wavetable {
   position: f32,
+  frequency: SmoothedValue<Multiplicative>,
   table: Vec<f32>,
   samplerate: u32,
   sr_recip: f32,
+  wrap: Option<f32>,
 }
 
 impl Clone for Wavetable {
   fn clone(&self) -> Self {
     Self {
       position: self.position,
+      frequency: self.frequency,
       table: self.table.clone(),
       samplerate: self.samplerate,
       sr_recip: self.sr_recip,
+      wrap: self.wrap,
     }
   }
 }
@@ -27,26 +32,53 @@ impl Wavetable {
   pub fn new<const N: usize>(table: &[f32; N], samplerate: u32) -> Self {
     Self { 
       position: 0.0, 
+      frequency: SmoothedValue::new(samplerate, 0.0),
       table: table.to_vec(),
       samplerate,
       sr_recip: 1.0 / samplerate as f32,
+      wrap: None,
     } 
   }
 
   #[inline]
   pub fn play<T: Interpolation>(&mut self, frequency: f32, phase: f32) -> f32 {
+    let frequency = self.frequency.process(frequency);
     let len = self.table.len() as f32;
-    self.position += len * self.sr_recip * frequency;
-    if self.position > len { self.position -= len; }
+    let increment = len * self.sr_recip * frequency;
+    self.position += increment;
+    self.wrap = None;
+    if self.position > len {
+      self.position -= len;
+      self.wrap = Some(f32::min(self.position / increment, 1.0 - f32::EPSILON));
+    }
     let mut pos = self.position + (phase * len);
     while pos > len { pos -= len; }
     while pos < 0.0 { pos += len; }
     T::interpolate(pos, &self.table, self.table.len())
   }
 
+  /// Samples since the phase wrapped during the last `play`, `0.0..1.0`,
+  /// to sync a [`SyncOscillator`](crate::sync::SyncOscillator).
+  #[inline]
+  pub fn sync(&self) -> Option<f32> {
+    self.wrap
+  }
+
+  /// Restart at `phase`, `0.0..1.0`.
+  pub fn reset(&mut self, phase: f32) {
+    self.position = phase.rem_euclid(1.0) * self.table.len() as f32;
+  }
+
+  /// Time in seconds to glide between frequencies, `0.0` (default) is
+  /// immediate. Frequencies need to be above zero to glide.
+  pub fn set_smoothing(&mut self, seconds: f32) {
+    self.frequency.set_time(seconds);
+  }
+
   pub fn set_samplerate(&mut self, samplerate: u32) {
     self.samplerate = samplerate;
     self.sr_recip = 1.0 / samplerate as f32;
+    self.frequency.set_samplerate(samplerate);
   }
 
 }
@@ -0,0 +1,394 @@
+use alloc::{vec, vec::Vec};
+use core::f32::consts::{PI, TAU};
+
+use crate::{analysis::fft::Fft, dsp::math::is_pow2, interpolation::Interpolation};
+
+mod wav;
+
+/// Frame size of Serum wavetables without a `clm` chunk.
+pub const SERUM_FRAME_SIZE: usize = 2048;
+
+/// Multi-frame wavetable prepared for scanning.
+///
+/// Every frame is stored band limited in octaves, level `l` keeping the
+/// lowest `frame_size / 2 >> l` harmonics, together with its spectrum for
+/// spectral morphing.
+pub struct ScanTable {
+  frame_size: usize,
+  frames: usize,
+  /// All frames of a level after each other
+  levels: Vec<Vec<f32>>,
+  /// Bins `0..=frame_size / 2` of each frame
+  magnitudes: Vec<f32>,
+  phases: Vec<f32>,
+  fft: Fft,
+}
+
+impl ScanTable {
+  /// `data` holds the frames after each other, `frame_size` needs to be a
+  /// power of 2.
+  pub fn new(data: &[f32], frame_size: usize) -> Result<Self, &'static str> {
+    if !is_pow2(frame_size) || frame_size < 2 {
+      return Err("Frame size is not a power of 2");
+    }
+    if data.is_empty() || !data.len().is_multiple_of(frame_size) {
+      return Err("Data is not a whole number of frames");
+    }
+    let fft = Fft::new(frame_size)?;
+    let frames = data.len() / frame_size;
+    let half = frame_size / 2;
+    let bins = half + 1;
+    let count = half.trailing_zeros() as usize + 1;
+
+    let mut levels = vec![vec![0.0; data.len()]; count];
+    let mut magnitudes = vec![0.0; frames * bins];
+    let mut phases = vec![0.0; frames * bins];
+    let mut spectrum_re = vec![0.0; frame_size];
+    let mut spectrum_im = vec![0.0; frame_size];
+    let mut re = vec![0.0; frame_size];
+    let mut im = vec![0.0; frame_size];
+
+    for (f, frame) in data.chunks_exact(frame_size).enumerate() {
+      spectrum_re.copy_from_slice(frame);
+      spectrum_im.fill(0.0);
+      fft.forward(&mut spectrum_re, &mut spectrum_im);
+      for k in 0..bins {
+        magnitudes[f * bins + k] = f32::hypot(spectrum_re[k], spectrum_im[k]);
+        phases[f * bins + k] = f32::atan2(spectrum_im[k], spectrum_re[k]);
+      }
+      for (l, level) in levels.iter_mut().enumerate() {
+        let harmonics = half >> l;
+        re.copy_from_slice(&spectrum_re);
+        im.copy_from_slice(&spectrum_im);
+        // bins above the limit on both sides of the spectrum
+        for k in harmonics + 1..frame_size - harmonics {
+          re[k] = 0.0;
+          im[k] = 0.0;
+        }
+        fft.inverse(&mut re, &mut im);
+        level[f * frame_size..(f + 1) * frame_size].copy_from_slice(&re);
+      }
+    }
+
+    Ok(Self { frame_size, frames, levels, magnitudes, phases, fft })
+  }
+
+  /// Load a WAV file, taking the frame size from a Serum `clm` chunk and
+  /// falling back to [`SERUM_FRAME_SIZE`].
+  pub fn from_wav(bytes: &[u8]) -> Result<Self, &'static str> {
+    let wav = wav::parse(bytes)?;
+    Self::new(&wav.samples, wav.frame_size.unwrap_or(SERUM_FRAME_SIZE))
+  }
+
+  /// Load a WAV file of frames of `frame_size`, ignoring a `clm` chunk.
+  pub fn from_wav_frames(bytes: &[u8], frame_size: usize) -> Result<Self, &'static str> {
+    let wav = wav::parse(bytes)?;
+    Self::new(&wav.samples, frame_size)
+  }
+
+  pub fn frame_size(&self) -> usize {
+    self.frame_size
+  }
+
+  pub fn frames(&self) -> usize {
+    self.frames
+  }
+
+  /// Full band `frame`
+  pub fn frame(&self, frame: usize) -> &[f32] {
+    let start = frame.min(self.frames - 1) * self.frame_size;
+    &self.levels[0][start..start + self.frame_size]
+  }
+
+  /// Level keeping at most `harmonics`, if any
+  fn level(&self, harmonics: usize) -> Option<usize> {
+    let half = self.frame_size / 2;
+    (0..self.levels.len()).find(|l| half >> l <= harmonics)
+  }
+}
+
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Morph {
+  /// Crossfade the samples of neighbouring frames
+  #[default] Crossfade,
+  /// Interpolate magnitude and phase of neighbouring frames
+  Spectral,
+}
+
+/// Oscillator scanning through the frames of a [`ScanTable`].
+///
+/// `position` selects the frame, `0.0..=1.0` from first to last. The
+/// frames are band limited for the played frequency, so no harmonic
+/// passes Nyquist. Crossfading fades between the octave levels of the
+/// table as the pitch moves.
+///
+/// Spectral morphing builds a new frame once per cycle, at the wrap, with
+/// one inverse FFT. The buffers for it are allocated on first use.
+///
+/// ```
+/// use rust_dsp::{interpolation::linear::Linear, wavetable::scan::{Morph, ScanOscillator, ScanTable}};
+///
+/// // two frames, a saw and a square
+/// let saw = (0..256).map(|n| n as f32 / 128.0 - 1.0);
+/// let square = (0..256).map(|n| if n < 128 { 1.0 } else { -1.0 });
+/// let data: Vec<f32> = saw.chain(square).collect();
+/// let table = ScanTable::new(&data, 256).unwrap();
+///
+/// let mut osc = ScanOscillator::new(48000);
+/// osc.set_morph(Morph::Spectral);
+/// let out = osc.play::<Linear>(&table, 220.0, 0.5, 0.0);
+/// ```
+pub struct ScanOscillator {
+  /// Position in the cycle, `0.0..1.0`
+  position: f32,
+  morph: Morph,
+  samplerate: u32,
+  sr_recip: f32,
+  /// Spectral frame being played
+  frame: Vec<f32>,
+  re: Vec<f32>,
+  im: Vec<f32>,
+  /// A new spectral frame is due
+  rebuild: bool,
+}
+
+impl ScanOscillator {
+  pub fn new(samplerate: u32) -> Self {
+    Self {
+      position: 0.0,
+      morph: Morph::Crossfade,
+      samplerate,
+      sr_recip: 1.0 / samplerate as f32,
+      frame: Vec::new(),
+      re: Vec::new(),
+      im: Vec::new(),
+      rebuild: true,
+    }
+  }
+
+  pub fn play<T: Interpolation>(&mut self, table: &ScanTable, frequency: f32, position: f32, phase: f32) -> f32 {
+    let size = table.frame_size;
+    self.position += frequency * self.sr_recip;
+    if self.position >= 1.0 || self.position < 0.0 {
+      self.position = self.position.rem_euclid(1.0);
+      self.rebuild = true;
+    }
+    let pos = (self.position + phase).rem_euclid(1.0) * size as f32;
+
+    let harmonics = 0.5 * self.samplerate as f32 / frequency.abs();
+    let position = position.clamp(0.0, 1.0) * (table.frames - 1) as f32;
+    let t1 = (position.floor() as usize).min(table.frames - 1);
+    let t2 = (t1 + 1).min(table.frames - 1);
+    let x = position - t1 as f32;
+
+    match self.morph {
+      Morph::Crossfade => {
+        let Some(rich) = table.level(harmonics as usize) else { return 0.0 };
+        // fade from the next poorer level across the octave, so the timbre
+        // changes smoothly with the pitch
+        let poor = (rich + 1).min(table.levels.len() - 1);
+        let fade = f32::min(f32::log2(harmonics / ((size / 2) >> rich) as f32), 1.0);
+        let read = |level: usize| {
+          let level = &table.levels[level];
+          let a = &level[t1 * size..(t1 + 1) * size];
+          let b = &level[t2 * size..(t2 + 1) * size];
+          T::interpolate(pos, a, size) * (1.0 - x) + T::interpolate(pos, b, size) * x
+        };
+        if poor == rich || fade >= 1.0 {
+          read(rich)
+        } else {
+          let poor = read(poor);
+          poor + (read(rich) - poor) * fade
+        }
+      }
+      Morph::Spectral => {
+        let harmonics = harmonics as usize;
+        if harmonics == 0 { return 0.0; }
+        if self.rebuild || self.frame.len() != size {
+          self.build(table, t1, t2, x, harmonics.min(size / 2));
+        }
+        T::interpolate(pos, &self.frame, size)
+      }
+    }
+  }
+
+  /// Inverse FFT of the spectra of frames `a` and `b`, interpolated by `x`
+  fn build(&mut self, table: &ScanTable, a: usize, b: usize, x: f32, harmonics: usize) {
+    let size = table.frame_size;
+    let bins = size / 2 + 1;
+    self.re.resize(size, 0.0);
+    self.im.resize(size, 0.0);
+    self.re.fill(0.0);
+    self.im.fill(0.0);
+
+    let (m1, m2) = (&table.magnitudes[a * bins..], &table.magnitudes[b * bins..]);
+    let (p1, p2) = (&table.phases[a * bins..], &table.phases[b * bins..]);
+    for k in 0..=harmonics {
+      let magnitude = m1[k] + (m2[k] - m1[k]) * x;
+      // shortest way around the circle
+      let diff = (p2[k] - p1[k] + PI).rem_euclid(TAU) - PI;
+      let (sin, cos) = f32::sin_cos(p1[k] + diff * x);
+      self.re[k] = magnitude * cos;
+      self.im[k] = magnitude * sin;
+      if k > 0 && k < size / 2 {
+        self.re[size - k] = self.re[k];
+        self.im[size - k] = -self.im[k];
+      }
+    }
+    // the Nyquist bin of a real signal has no imaginary part
+    if harmonics == size / 2 { self.im[harmonics] = 0.0; }
+
+    table.fft.inverse(&mut self.re, &mut self.im);
+    self.frame.clear();
+    self.frame.extend_from_slice(&self.re);
+    self.rebuild = false;
+  }
+
+  pub fn set_morph(&mut self, morph: Morph) {
+    self.morph = morph;
+    self.rebuild = true;
+  }
+
+  /// Restart at `phase`, `0.0..1.0`.
+  pub fn reset(&mut self, phase: f32) {
+    self.position = phase.rem_euclid(1.0);
+    self.rebuild = true;
+  }
+
+  pub fn set_samplerate(&mut self, samplerate: u32) {
+    self.samplerate = samplerate;
+    self.sr_recip = 1.0 / samplerate as f32;
+    self.rebuild = true;
+  }
+}
+
+#[cfg(test)]
+mod tests {
+  use super::*;
+  use crate::interpolation::Linear;
+
+  const SAMPLERATE: u32 = 48000;
+  const SIZE: usize = 64;
+
+  fn sine(harmonic: f32, offset: f32) -> impl Iterator<Item = f32> {
+    (0..SIZE).map(move |n| f32::sin(TAU * harmonic * n as f32 / SIZE as f32 + offset))
+  }
+
+  /// WAV file of 16 bit mono `samples` with an optional `clm` chunk
+  fn wav(samples: &[f32], clm: Option<&[u8]>) -> Vec<u8> {
+    let mut chunks = Vec::new();
+    chunks.extend_from_slice(b"fmt ");
+    chunks.extend_from_slice(&16u32.to_le_bytes());
+    for value in [1u16, 1] { chunks.extend_from_slice(&value.to_le_bytes()); }
+    chunks.extend_from_slice(&SAMPLERATE.to_le_bytes());
+    chunks.extend_from_slice(&(SAMPLERATE * 2).to_le_bytes());
+    for value in [2u16, 16] { chunks.extend_from_slice(&value.to_le_bytes()); }
+    if let Some(clm) = clm {
+      chunks.extend_from_slice(b"clm ");
+      chunks.extend_from_slice(&(clm.len() as u32).to_le_bytes());
+      chunks.extend_from_slice(clm);
+      if clm.len() % 2 == 1 { chunks.push(0); }
+    }
+    chunks.extend_from_slice(b"data");
+    chunks.extend_from_slice(&(samples.len() as u32 * 2).to_le_bytes());
+    for s in samples {
+      chunks.extend_from_slice(&((s * 32767.0) as i16).to_le_bytes());
+    }
+    let mut bytes = Vec::new();
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(chunks.len() as u32 + 4).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+    bytes.extend(chunks);
+    bytes
+  }
+
+  #[test]
+  fn invalid_tables() {
+    assert!(ScanTable::new(&[0.0; 100], 100).is_err());
+    assert!(ScanTable::new(&[0.0; 100], 64).is_err());
+    assert!(ScanTable::new(&[], 64).is_err());
+    assert!(ScanTable::from_wav(b"RIFF\0\0\0\0WAVE").is_err());
+  }
+
+  #[test]
+  fn band_limited_frames() {
+    let data: Vec<f32> = sine(1.0, 0.0).zip(sine(12.0, 0.0)).map(|(a, b)| a + b).collect();
+    let table = ScanTable::new(&data, SIZE).unwrap();
+    assert_eq!(1, table.frames());
+    assert!(table.frame(0).iter().zip(&data).all(|(a, b)| (a - b).abs() < 1e-4));
+
+    // four table samples per sample, the 12th harmonic would pass Nyquist
+    let mut osc = ScanOscillator::new(SAMPLERATE);
+    let freq = SAMPLERATE as f32 / 16.0;
+    for n in 1..32 {
+      let expected = f32::sin(TAU * 4.0 * n as f32 / SIZE as f32);
+      let out = osc.play::<Linear>(&table, freq, 0.0, 0.0);
+      assert!((out - expected).abs() < 1e-4, "{n} {out}");
+    }
+    // at a low frequency both are there
+    let mut osc = ScanOscillator::new(SAMPLERATE);
+    let out = osc.play::<Linear>(&table, SAMPLERATE as f32 / 64.0, 0.0, 0.0);
+    assert!((out - data[1]).abs() < 1e-4);
+  }
+
+  #[test]
+  fn levels_fade_with_pitch() {
+    let data: Vec<f32> = sine(1.0, 0.0).zip(sine(12.0, 0.0)).map(|(a, b)| a + b).collect();
+    let table = ScanTable::new(&data, SIZE).unwrap();
+    // just below and above 16 harmonics fit under Nyquist
+    let freq = SAMPLERATE as f32 / 32.0;
+    let play = |freq: f32| {
+      let mut osc = ScanOscillator::new(SAMPLERATE);
+      osc.reset(0.1);
+      osc.play::<Linear>(&table, freq, 0.0, 0.0)
+    };
+    let (below, above) = (play(freq * 1.0001), play(freq * 0.9999));
+    assert!((below - above).abs() < 1e-3, "{below} {above}");
+    // halfway through the octave the 12th harmonic is partly there
+    let freq = freq / f32::sqrt(2.0);
+    let mut osc = ScanOscillator::new(SAMPLERATE);
+    let deviation = (1..200).map(|n| {
+      let fundamental = f32::sin(TAU * freq * n as f32 / SAMPLERATE as f32);
+      (osc.play::<Linear>(&table, freq, 0.0, 0.0) - fundamental).abs()
+    }).fold(0.0, f32::max);
+    assert!((deviation - 0.5).abs() < 0.05, "{deviation}");
+  }
+
+  #[test]
+  fn spectral_morph_keeps_magnitude() {
+    // sine and cosine, a crossfade halfway is 3 dB quieter
+    let data: Vec<f32> = sine(1.0, 0.0).chain(sine(1.0, PI / 2.0)).collect();
+    let table = ScanTable::new(&data, SIZE).unwrap();
+    let freq = SAMPLERATE as f32 / SIZE as f32;
+    let peak = |morph| {
+      let mut osc = ScanOscillator::new(SAMPLERATE);
+      osc.set_morph(morph);
+      (0..SIZE * 2).map(|_| osc.play::<Linear>(&table, freq, 0.5, 0.0).abs()).fold(0.0, f32::max)
+    };
+    assert!((peak(Morph::Crossfade) - f32::sqrt(0.5)).abs() < 1e-3);
+    assert!((peak(Morph::Spectral) - 1.0).abs() < 1e-3);
+
+    // the ends play the frames themselves
+    let mut osc = ScanOscillator::new(SAMPLERATE);
+    osc.set_morph(Morph::Spectral);
+    for n in 1..SIZE {
+      let out = osc.play::<Linear>(&table, freq, 1.0, 0.0);
+      assert!((out - data[SIZE + n]).abs() < 1e-4, "{n}");
+    }
+  }
+
+  #[test]
+  fn wav_with_clm() {
+    let data: Vec<f32> = sine(1.0, 0.0).chain(sine(2.0, 0.0)).chain(sine(3.0, 0.0)).collect();
+    let bytes = wav(&data, Some(b"<!>64 10000000 wavetable (www.xferrecords.com)"));
+    let table = ScanTable::from_wav(&bytes).unwrap();
+    assert_eq!(3, table.frames());
+    assert_eq!(SIZE, table.frame_size());
+    assert!(table.frame(2).iter().zip(&data[2 * SIZE..]).all(|(a, b)| (a - b).abs() < 1e-3));
+
+    // without, frames of the given size or of Serum's default
+    let bytes = wav(&data, None);
+    assert_eq!(6, ScanTable::from_wav_frames(&bytes, 32).unwrap().frames());
+    assert!(ScanTable::from_wav(&bytes).is_err());
+  }
+}
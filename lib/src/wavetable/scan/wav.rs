@@ -0,0 +1,89 @@
+use alloc::vec::Vec;
+
+/// Contents of a WAV file needed to build a [`ScanTable`](super::ScanTable).
+pub(super) struct Wav {
+  /// First channel only
+  pub samples: Vec<f32>,
+  /// Frame size from a Serum `clm` chunk
+  pub frame_size: Option<usize>,
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum Encoding {
+  Int,
+  Float,
+}
+
+/// Parse a RIFF WAVE file with 8, 16, 24 or 32 bit integer or 32 bit float
+/// samples.
+pub(super) fn parse(bytes: &[u8]) -> Result<Wav, &'static str> {
+  if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+    return Err("not a WAV file");
+  }
+  let mut format = None;
+  let mut data = None;
+  let mut frame_size = None;
+
+  let mut pos = 12;
+  while pos + 8 <= bytes.len() {
+    let id = &bytes[pos..pos + 4];
+    let len = u32::from_le_bytes([bytes[pos + 4], bytes[pos + 5], bytes[pos + 6], bytes[pos + 7]]) as usize;
+    let start = pos + 8;
+    // data of a streamed file may claim more than is there
+    let end = start.saturating_add(len).min(bytes.len());
+    let chunk = &bytes[start..end];
+    match id {
+      b"fmt " => {
+        if chunk.len() < 16 { return Err("fmt chunk too short"); }
+        let u16_at = |i: usize| u16::from_le_bytes([chunk[i], chunk[i + 1]]);
+        let mut tag = u16_at(0);
+        // WAVE_FORMAT_EXTENSIBLE carries the format in the subformat GUID
+        if tag == 0xFFFE && chunk.len() >= 26 { tag = u16_at(24); }
+        let encoding = match tag {
+          1 => Encoding::Int,
+          3 => Encoding::Float,
+          _ => return Err("unsupported WAV encoding"),
+        };
+        let channels = u16_at(2) as usize;
+        let bits = u16_at(14) as usize;
+        if channels == 0 { return Err("WAV without channels"); }
+        match (encoding, bits) {
+          (Encoding::Int, 8 | 16 | 24 | 32) | (Encoding::Float, 32) => (),
+          _ => return Err("unsupported WAV bit depth"),
+        }
+        format = Some((encoding, channels, bits / 8));
+      }
+      b"data" => data = Some(chunk),
+      b"clm " => {
+        // e.g. "<!>2048 10000000 wavetable (www.xferrecords.com)"
+        if let Some(text) = chunk.strip_prefix(b"<!>") {
+          let digits = text.iter().take_while(|b| b.is_ascii_digit()).fold(0usize, |n, b| {
+            n.saturating_mul(10).saturating_add((b - b'0') as usize)
+          });
+          if digits > 0 { frame_size = Some(digits); }
+        }
+      }
+      _ => (),
+    }
+    // chunks are padded to an even length
+    pos = start.saturating_add(len).saturating_add(len & 1);
+  }
+
+  let (encoding, channels, width) = format.ok_or("WAV without fmt chunk")?;
+  let data = data.ok_or("WAV without data chunk")?;
+  let samples = data
+    .chunks_exact(channels * width)
+    .map(|frame| {
+      let b = &frame[..width];
+      match (encoding, width) {
+        (Encoding::Float, _) => f32::from_le_bytes([b[0], b[1], b[2], b[3]]),
+        (_, 1) => (b[0] as f32 - 128.0) / 128.0,
+        (_, 2) => i16::from_le_bytes([b[0], b[1]]) as f32 / 32768.0,
+        (_, 3) => i32::from_le_bytes([0, b[0], b[1], b[2]]) as f32 / 2147483648.0,
+        _ => i32::from_le_bytes([b[0], b[1], b[2], b[3]]) as f32 / 2147483648.0,
+      }
+    })
+    .collect();
+
+  Ok(Wav { samples, frame_size })
+}
@@ -1,4 +1,5 @@
 use super::*;
+use crate::smooth::{Multiplicative, SmoothedValue};
 
 /// Wavetable that shares the table containing the wave representation.
 ///
@@ -8,26 +9,40 @@ use super::*;
 #[derive(Clone, Copy, Default, Debug)]
 pub struct Wavetable {
   position: f32,
+  frequency: SmoothedValue<Multiplicative>,
   samplerate: u32,
   sr_recip: f32,
+  wrap: Option<f32>,
+  /// Phase to restart at on the next `play`, once the table size is known
+  reset: Option<f32>,
 }
 
 impl Wavetable {
   pub fn new() -> Self {
     Self {
       position: 0.0,
+      frequency: SmoothedValue::default(),
       samplerate: 0,
       sr_recip: 0.0,
+      wrap: None,
+      reset: None,
     }
   }
 
   /// Play function for wavetable where __SIZE__ is the table size and __TableInterpolation = &impl Interpolation__
   #[inline]
   pub fn play<T: Interpolation>(&mut self, table: &[f32], frequency: f32, phase: f32) -> f32 {
+    let frequency = self.frequency.process(frequency);
     let len = table.len() as f32;
+    if let Some(phase) = self.reset.take() { self.position = phase * len; }
     // increment phase position in table
-    self.position += len * self.sr_recip * frequency;
-    if self.position > len { self.position -= len; }
+    let increment = len * self.sr_recip * frequency;
+    self.position += increment;
+    self.wrap = None;
+    if self.position > len {
+      self.position -= len;
+      self.wrap = Some(f32::min(self.position / increment, 1.0 - f32::EPSILON));
+    }
     // add FM (phase modulation)
     let mut pos = self.position + (phase * len);
     while pos > len { pos -= len; }
@@ -35,8 +50,27 @@ impl Wavetable {
     T::interpolate(pos, table, table.len())
   }
 
+  /// Samples since the phase wrapped during the last `play`, `0.0..1.0`,
+  /// to sync a [`SyncOscillator`](crate::sync::SyncOscillator).
+  #[inline]
+  pub fn sync(&self) -> Option<f32> {
+    self.wrap
+  }
+
+  /// Restart at `phase`, `0.0..1.0`, from the next `play`.
+  pub fn reset(&mut self, phase: f32) {
+    self.reset = Some(phase.rem_euclid(1.0));
+  }
+
+  /// Time in seconds to glide between frequencies, `0.0` (default) is
+  /// immediate. Frequencies need to be above zero to glide.
+  pub fn set_smoothing(&mut self, seconds: f32) {
+    self.frequency.set_time(seconds);
+  }
+
   pub fn set_samplerate(&mut self, samplerate: u32) {
     self.samplerate = samplerate;
     self.sr_recip = 1.0 / samplerate as f32;
+    self.frequency.set_samplerate(samplerate);
   }
 }